@@ -0,0 +1,83 @@
+use crate::{Entry, Result, storage};
+
+/// An entry which is made of exactly `N` bytes (e.g. a hash or a fixed-size ID),
+/// encoded as-is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Bytes<const N: usize>(pub [u8; N]);
+
+impl<const N: usize> Bytes<N> {
+    /// Returns the bytes of the entry.
+    #[inline]
+    pub const fn into_inner(self) -> [u8; N] {
+        self.0
+    }
+}
+
+impl<const N: usize> Entry for Bytes<N> {
+    const SIZE: usize = N;
+
+    #[inline]
+    async fn read(reader: &mut storage::Reader) -> Result<Self> {
+        reader.read_bytes().await.map(Self)
+    }
+
+    #[inline]
+    async fn write(&self, writer: &mut storage::Writer) -> Result<()> {
+        writer.write_bytes(self.0).await
+    }
+}
+
+impl<const N: usize> AsRef<[u8]> for Bytes<N> {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<const N: usize> From<[u8; N]> for Bytes<N> {
+    #[inline]
+    fn from(bytes: [u8; N]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl<const N: usize> From<Bytes<N>> for [u8; N] {
+    #[inline]
+    fn from(bytes: Bytes<N>) -> Self {
+        bytes.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        DFooter, Reader, Writer,
+        tests::{block_on, memory},
+    };
+
+    use super::*;
+
+    #[test]
+    fn round_trip() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let entries = [*b"abc", *b"xyz"].map(Bytes::from);
+
+            let mut writer = Writer::create(None, storage.clone()).await?;
+            for entry in entries {
+                writer.write_unique(entry).await?;
+            }
+
+            let id = writer.finish().await?;
+            let stat = storage.stat_link(id, storage::Kind::Delta).await?;
+            assert_eq!(stat.content_length, (6 + DFooter::SIZE) as u64);
+
+            let reader = Reader::<Bytes<3>>::open(id, storage).await?;
+            assert_eq!(reader.get_index_of(&Bytes(*b"xyz")), Some(1));
+            assert_eq!(reader.get_at(0).map(AsRef::as_ref), Some(&b"abc"[..]));
+            assert_eq!(<[u8; 3]>::from(entries[1]), *b"xyz");
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,203 @@
+use std::{
+    collections::VecDeque,
+    hash::{BuildHasher, RandomState},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::{Entry, LinkId, Reader, Result, Storage};
+
+/// The readers kept in a [`ChainCache`], along with the ID of the latest link they
+/// loaded.
+type Readers<T, S> = VecDeque<(LinkId, Arc<Reader<T, S>>)>;
+
+/// A cache of the readers for the chains of a storage which have been opened most
+/// recently, keyed by the ID of the latest link they loaded.
+///
+/// Readers are shared using [`Arc`]s and are thus only usable to read entries, which
+/// makes it safe to serve the same reader to multiple callers.
+///
+/// Link IDs are only unique within a storage (e.g. [`LinkId::from_name()`] returns the
+/// same ID for the same name, whatever the storage the link is written to), which is
+/// why each cache only opens chains from the storage it was created for.
+pub struct ChainCache<T: Entry, S = RandomState> {
+    /// The storage from which the chains are opened.
+    storage: Storage,
+
+    /// The maximum number of readers kept in the cache.
+    capacity: usize,
+
+    /// The readers kept in the cache, from the most recently used one to the least
+    /// recently used one.
+    readers: Mutex<Readers<T, S>>,
+}
+
+impl<T: Entry, S: BuildHasher + Default> ChainCache<T, S> {
+    /// Creates a new cache for the chains of the given storage, which keeps at most
+    /// `capacity` readers.
+    pub fn new(storage: Storage, capacity: usize) -> Self {
+        Self {
+            storage,
+            capacity,
+            readers: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Returns the reader which loaded the chain up to `latest`, opening it from the
+    /// cache's storage if it isn't present in the cache.
+    ///
+    /// If the cache is full, this evicts the least recently used reader.
+    pub async fn get_or_open(&self, latest: LinkId) -> Result<Arc<Reader<T, S>>> {
+        if let Some(reader) = self.get(latest) {
+            return Ok(reader);
+        }
+
+        // The lock isn't held while opening the reader, meaning that concurrent calls for
+        // the same chain might open it multiple times – only one of them is kept.
+        let reader = Arc::new(Reader::open(latest, self.storage.clone()).await?);
+
+        let mut readers = self.lock();
+        if let Some(reader) = Self::promote(&mut readers, latest) {
+            return Ok(reader);
+        }
+
+        if self.capacity == 0 {
+            return Ok(reader);
+        }
+
+        if readers.len() == self.capacity {
+            readers.pop_back();
+        }
+
+        readers.push_front((latest, reader.clone()));
+
+        Ok(reader)
+    }
+
+    /// Returns the reader which loaded the chain up to `latest`, if it is present in
+    /// the cache.
+    pub fn get(&self, latest: LinkId) -> Option<Arc<Reader<T, S>>> {
+        Self::promote(&mut self.lock(), latest)
+    }
+
+    /// Removes the reader which loaded the chain up to `latest` from the cache, if it
+    /// is present.
+    pub fn remove(&self, latest: LinkId) -> Option<Arc<Reader<T, S>>> {
+        let mut readers = self.lock();
+        let position = readers.iter().position(|(id, _)| *id == latest)?;
+
+        readers.remove(position).map(|(_, reader)| reader)
+    }
+
+    /// Moves the reader which loaded the chain up to `latest` to the front of
+    /// `readers`, returning it, if it is present.
+    fn promote(readers: &mut Readers<T, S>, latest: LinkId) -> Option<Arc<Reader<T, S>>> {
+        let position = readers.iter().position(|(id, _)| *id == latest)?;
+        let entry = readers.remove(position)?;
+        let reader = entry.1.clone();
+
+        readers.push_front(entry);
+
+        Some(reader)
+    }
+
+    /// Locks the readers kept in the cache.
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, Readers<T, S>> {
+        // The lock is never held across operations which can panic.
+        self.readers
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future::join;
+    use uuid::Uuid;
+
+    use crate::{
+        Writer,
+        tests::{U64, block_on, chain, memory},
+    };
+
+    use super::*;
+
+    #[test]
+    fn get_or_open() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..3, 3..4]).await?;
+            let cache = ChainCache::<U64>::new(storage, 2);
+
+            let first = cache.get_or_open(links[0]).await?;
+            assert!(Arc::ptr_eq(&first, &cache.get_or_open(links[0]).await?));
+
+            // The least recently used reader is evicted.
+            cache.get_or_open(links[1]).await?;
+            cache.get(links[0]).unwrap();
+            cache.get_or_open(links[2]).await?;
+            assert!(cache.get(links[1]).is_none());
+
+            assert_eq!(cache.remove(links[0]).unwrap().len(), 2);
+            assert!(cache.get(links[0]).is_none());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn shared_readers() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..3]).await?;
+
+            // Concurrent calls for the same chain share the reader which is kept.
+            let cache = ChainCache::<U64>::new(storage.clone(), 1);
+            let (first, second) =
+                join(cache.get_or_open(links[1]), cache.get_or_open(links[1])).await;
+            let kept = cache.get(links[1]).unwrap();
+            assert!(Arc::ptr_eq(&first?, &kept) || Arc::ptr_eq(&second?, &kept));
+
+            // Evicted readers can still be used by the callers holding them.
+            cache.get_or_open(links[0]).await?;
+            assert!(cache.get(links[1]).is_none());
+            assert_eq!(kept.get_at(2), Some(&U64(2)));
+
+            // Readers which failed to open and readers opened by a cache without
+            // capacity aren't kept.
+            assert!(cache.get_or_open(LinkId::random()).await.is_err());
+            assert!(cache.get(links[0]).is_some());
+
+            let cache = ChainCache::<U64>::new(storage, 0);
+            cache.get_or_open(links[0]).await?;
+            assert!(cache.get(links[0]).is_none());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn same_id_in_different_storages() -> Result<()> {
+        block_on(async {
+            let id = LinkId::from_name(Uuid::nil(), b"latest");
+
+            let mut caches = Vec::new();
+            for entries in [0..1, 0..2] {
+                let storage = memory();
+                let mut writer = Writer::<U64>::create(None, storage.clone()).await?;
+                writer.with_id(id)?;
+                for entry in entries {
+                    writer.write_unique(U64(entry)).await?;
+                }
+
+                writer.finish().await?;
+                caches.push(ChainCache::<U64>::new(storage, 1));
+            }
+
+            assert_eq!(caches[0].get_or_open(id).await?.len(), 1);
+            assert_eq!(caches[1].get_or_open(id).await?.len(), 2);
+
+            Ok(())
+        })
+    }
+}
@@ -1,6 +1,8 @@
+use std::io::SeekFrom;
+
 use crate::{
-    Error, LinkId, Result,
-    storage::{self, Reader, Writer},
+    ChainIndex, Error, LinkId, Result, Storage,
+    storage::{self, Encryption, Reader, Writer},
 };
 
 /// The footer of a delta file, containing information about it.
@@ -17,12 +19,26 @@ use crate::{
 /// 2. `index`, encoded in big-endian order.
 /// 3. `total`, encoded in big-endian order.
 /// 4. `count`, encoded in big-endian order.
-/// 5. `VERSION`, encoded in big-endian order.
+/// 5. how the rest of the file is encrypted, as the ID of the encryption scheme
+///    encoded as a single byte (where `0` represents none), followed by the nonce
+///    used to encrypt it (or 12 zeros).
+/// 6. `MAGIC`, stored as-is.
+/// 7. `VERSION`, encoded in big-endian order.
+///
+/// Version `0` of the storage format did not store how the rest of the file is
+/// encrypted (i.e. it never was), nor `MAGIC`.
+///
+/// `MAGIC` allows detecting when a file which isn't a delta file (e.g. a snapshot
+/// file or an object which wasn't written by this crate) is being read as one.
+///
+/// Values files use the same footer, but store `VALUES_MAGIC` instead of `MAGIC`, so
+/// that a values file can't be read as a delta file (or the other way around).
 ///
-/// `VERSION` is stored last to make sure that if we add more fields in later
-/// versions of the storage format, the version that was used to encode a file is
-/// stored at the same offset from the end of the file, to make sure that we detect
-/// any incompabilities when trying to decode a snapshot.
+/// `MAGIC` and `VERSION` are stored last to make sure that if we add more fields in
+/// later versions of the storage format, they are stored at the same offset from
+/// the end of the file, to make sure that we detect any incompabilities when trying
+/// to decode a delta.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Footer {
     /// The ID of the previous link which this link extends.
     pub previous: Option<LinkId>,
@@ -30,7 +46,7 @@ pub struct Footer {
     /// The index of this link in the chain of links.
     ///
     /// This is equal to the number of previous links in the chain.
-    pub index: u32,
+    pub index: ChainIndex,
 
     /// The number of entries present in the link's snapshot.
     ///
@@ -40,37 +56,126 @@ pub struct Footer {
 
     /// The number of entries present in the link's delta.
     pub count: u32,
+
+    /// The version of the storage format which was used to encode the file.
+    pub version: u16,
 }
 
 impl Footer {
     /// The expected size of the footer of a delta file.
     ///
     /// Future storage formats might have a bigger footer than this value.
-    pub const SIZE: usize = 30; // 16 + 3 * 4 + 2
+    pub const SIZE: usize = 47; // 16 + 3 * 4 + 13 + 4 + 2
+
+    /// The magic bytes stored in the footer of a delta file.
+    pub const MAGIC: [u8; 4] = *b"CDD\0";
+
+    /// The magic bytes stored in the footer of a values file.
+    pub const VALUES_MAGIC: [u8; 4] = *b"CDV\0";
+
+    /// Returns the size of the footer of a file encoded with the given version of the
+    /// storage format.
+    #[inline]
+    const fn size(version: u16) -> usize {
+        match version {
+            0 => 30, // 16 + 3 * 4 + 2
+            _ => Self::SIZE,
+        }
+    }
 
     /// Reads the [`Footer`] supposedly stored at the end of the file being read by
     /// `reader`.
     ///
+    /// This supports all of the versions of the storage format since
+    /// `storage::MIN_VERSION` (or `storage::LEGACY_VERSION` if the storage opted into
+    /// it), decoding the footer according to the version the file was encoded with.
+    ///
     /// This updates the `reader` so that it will act as-if the footer did not exist.
     pub async fn read(reader: &mut Reader) -> Result<Self> {
-        if reader.file_size() < Self::SIZE {
+        Self::read_as(reader, Self::MAGIC).await
+    }
+
+    /// Reads the [`Footer`] supposedly stored at the end of the values file being read
+    /// by `reader`, like [`read()`][1] does for delta files.
+    ///
+    /// [1]: Self::read()
+    pub(crate) async fn read_values(reader: &mut Reader) -> Result<Self> {
+        Self::read_as(reader, Self::VALUES_MAGIC).await
+    }
+
+    /// Reads the [`Footer`] supposedly stored at the end of the file being read by
+    /// `reader`, which must store the given `magic` bytes.
+    async fn read_as(reader: &mut Reader, magic: [u8; 4]) -> Result<Self> {
+        let (footer, encryption) = Self::decode(reader, magic).await?;
+
+        let end = reader.file_size() - Self::size(footer.version);
+        reader.set_file_size(end);
+        reader.seek(SeekFrom::Start(0))?;
+        reader.unseal(encryption).await?;
+
+        Ok(footer)
+    }
+
+    /// Decodes the [`Footer`] supposedly stored at the end of the file being read by
+    /// `reader`, along with how the rest of the file is encrypted, without updating the
+    /// `reader` to act as-if the footer did not exist (see [`read()`][1]).
+    ///
+    /// The footer must store the given `magic` bytes (i.e. [`MAGIC`][2] for delta files
+    /// and [`VALUES_MAGIC`][3] for values files).
+    ///
+    /// This only reads the end of the file, which means that `reader` can also read
+    /// only the last bytes of the file.
+    ///
+    /// [1]: Self::read()
+    /// [2]: Self::MAGIC
+    /// [3]: Self::VALUES_MAGIC
+    pub(crate) async fn decode(
+        reader: &mut Reader,
+        magic: [u8; 4],
+    ) -> Result<(Self, Option<Encryption>)> {
+        // Files smaller than the smallest footer can't be decoded, whatever their version.
+        let min_size = Self::size(storage::LEGACY_VERSION);
+        if reader.file_size() < min_size {
             return Err(Error::FileSize {
-                expected: Self::SIZE,
+                expected: min_size,
                 got: reader.file_size(),
             });
         }
 
-        reader.goto(-2)?;
+        // The version is always stored last, whatever the version of the storage format,
+        // which allows us to decode each file according to its own version.
+        reader.seek(SeekFrom::End(-2))?;
         let version = reader.read_u16().await?;
 
-        if version != storage::VERSION {
+        if !(reader.min_version()..=storage::VERSION).contains(&version) {
             return Err(Error::Version {
                 expected: storage::VERSION,
                 got: version,
             });
         }
 
-        reader.goto(-(Self::SIZE as isize))?;
+        let size = Self::size(version);
+        if reader.file_size() < size {
+            return Err(Error::FileSize {
+                expected: size,
+                got: reader.file_size(),
+            });
+        }
+
+        // Version `0` of the storage format didn't store any magic bytes.
+        if version >= 1 {
+            reader.seek(SeekFrom::End(-6))?;
+            let got = reader.read_bytes().await?;
+
+            if got != magic {
+                return Err(Error::BadMagic {
+                    expected: magic,
+                    got,
+                });
+            }
+        }
+
+        reader.seek(SeekFrom::End(-(size as i64)))?;
 
         let previous = reader.read_u128().await?;
         let previous = if previous == 0 {
@@ -79,38 +184,206 @@ impl Footer {
             Some(LinkId::from_u128(previous))
         };
 
-        let index = reader.read_u32().await?;
+        let index = ChainIndex::new(reader.read_u32().await?);
         let total = reader.read_u32().await?;
         let count = reader.read_u32().await?;
 
-        let end = reader.file_size() - Self::SIZE;
-        reader.set_file_size(end);
+        let encryption = if version >= 1 {
+            Encryption::read(reader).await?
+        } else {
+            None
+        };
 
-        Ok(Self {
+        let footer = Self {
             previous,
             index,
             total,
             count,
-        })
+            version,
+        };
+
+        Ok((footer, encryption))
+    }
+
+    /// Returns the number of entries in the chain up to the link whose delta (or values)
+    /// file this is the footer of.
+    ///
+    /// This is `total`, unless the file was encoded with `storage::LEGACY_VERSION`, in
+    /// which case `total` might be wrong and is recomputed from the footers of the
+    /// previous links (see [`Storage::legacy_total()`][1]).
+    ///
+    /// [1]: crate::Storage::legacy_total()
+    pub(crate) async fn chain_total(&self, storage: &Storage) -> Result<u32> {
+        if self.version != storage::LEGACY_VERSION {
+            return Ok(self.total);
+        }
+
+        storage.legacy_total(self.previous, self.count).await
     }
 
-    /// Writes the [`Footer`] to the writer.
+    /// Checks that the content of the delta file of `link` being read by `reader` (from
+    /// which the [`Footer`] was read) is made of exactly `count` entries of
+    /// `entry_size` bytes each.
+    ///
+    /// This detects truncated or mis-encoded files before decoding any of their
+    /// entries.
+    pub(crate) fn check_size(
+        &self,
+        link: LinkId,
+        reader: &Reader,
+        entry_size: usize,
+    ) -> Result<()> {
+        if reader.file_size() != self.count as usize * entry_size {
+            return Err(Error::Inconsistent {
+                link,
+                reason: "file size doesn't match its number of entries",
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Writes the [`Footer`] to the writer, encoding it according to its `version`.
+    ///
+    /// If the file is encrypted, this first encrypts all of the content written to it.
+    ///
+    /// Fails if the file is encrypted but `version` doesn't support encryption.
     pub async fn write(&self, writer: &mut Writer) -> Result<()> {
+        self.write_as(writer, Self::MAGIC).await
+    }
+
+    /// Writes the [`Footer`] to the writer of a values file, like [`write()`][1] does
+    /// for delta files.
+    ///
+    /// [1]: Self::write()
+    pub(crate) async fn write_values(&self, writer: &mut Writer) -> Result<()> {
+        self.write_as(writer, Self::VALUES_MAGIC).await
+    }
+
+    /// Writes the [`Footer`] to the writer, storing the given `magic` bytes.
+    async fn write_as(&self, writer: &mut Writer, magic: [u8; 4]) -> Result<()> {
+        let encryption = writer.seal().await?;
+        let bytes = self.encode(encryption, magic)?;
+
+        writer.write_slice(&bytes).await
+    }
+
+    /// Returns the bytes stored at the end of an unencrypted delta file with this
+    /// [`Footer`], which only depend on its fields.
+    ///
+    /// Fails like [`write()`][1] does.
+    ///
+    /// [1]: Self::write()
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        self.encode(None, Self::MAGIC)
+    }
+
+    /// Encodes the [`Footer`] according to its `version`, for a file whose content was
+    /// encrypted as described by `encryption`, storing the given `magic` bytes.
+    fn encode(&self, encryption: Option<Encryption>, magic: [u8; 4]) -> Result<Vec<u8>> {
         let Self {
             previous,
             index,
             total,
             count,
+            version,
         } = self;
 
+        if *version < 1 && encryption.is_some() {
+            return Err(Error::Unsupported {
+                operation: "encryption with this version of the storage format",
+            });
+        }
+
         let previous = previous.as_ref().map(LinkId::as_u128).unwrap_or_default();
+        let mut bytes = Vec::with_capacity(Self::size(*version));
 
-        writer.write_u128(previous).await?;
-        writer.write_u32(*index).await?;
-        writer.write_u32(*total).await?;
-        writer.write_u32(*count).await?;
-        writer.write_u16(storage::VERSION).await?;
+        bytes.extend_from_slice(&previous.to_be_bytes());
+        bytes.extend_from_slice(&index.get().to_be_bytes());
+        bytes.extend_from_slice(&total.to_be_bytes());
+        bytes.extend_from_slice(&count.to_be_bytes());
 
-        Ok(())
+        if *version >= 1 {
+            Encryption::encode(encryption, &mut bytes);
+            bytes.extend_from_slice(&magic);
+        }
+
+        bytes.extend_from_slice(&version.to_be_bytes());
+
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{block_on, memory};
+
+    use super::*;
+
+    fn footer(version: u16) -> Footer {
+        Footer {
+            previous: Some(LinkId::from_u128(42)),
+            index: ChainIndex::new(3),
+            total: 10,
+            count: 4,
+            version,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let storage = memory().with_legacy_format();
+
+        for version in [storage::LEGACY_VERSION, storage::VERSION] {
+            let footer = footer(version);
+            let bytes = footer.to_bytes().unwrap();
+            assert_eq!(bytes.len(), Footer::size(version));
+
+            let mut reader = storage.open_slice(&bytes);
+            assert_eq!(block_on(Footer::read(&mut reader)).unwrap(), footer);
+            assert_eq!(reader.file_size(), 0);
+        }
+    }
+
+    #[test]
+    fn legacy_version_requires_opt_in() {
+        let bytes = footer(storage::LEGACY_VERSION).to_bytes().unwrap();
+
+        let mut reader = memory().open_slice(&bytes);
+        let error = block_on(Footer::read(&mut reader)).unwrap_err();
+        assert!(error.is_version_mismatch());
+
+        // Any object ending with two zero bytes would otherwise be a version 0 footer.
+        let mut reader = memory().open_slice(&[0xAB; 64]);
+        let error = block_on(Footer::read(&mut reader)).unwrap_err();
+        assert!(error.is_version_mismatch());
+    }
+
+    #[test]
+    fn version_1_stores_everything() {
+        let bytes = footer(1).to_bytes().unwrap();
+
+        assert_eq!(bytes.len(), Footer::SIZE);
+        assert_eq!(bytes[Footer::SIZE - 6..Footer::SIZE - 2], Footer::MAGIC);
+    }
+
+    #[test]
+    fn bad_magic() {
+        let mut bytes = footer(storage::VERSION).to_bytes().unwrap();
+        bytes[Footer::SIZE - 6] = b'X';
+
+        let mut reader = Reader::from_slice(&bytes);
+        let error = block_on(Footer::read(&mut reader)).unwrap_err();
+        assert!(matches!(error, Error::BadMagic { .. }));
+    }
+
+    #[test]
+    fn newer_version() {
+        let mut bytes = footer(storage::VERSION).to_bytes().unwrap();
+        bytes[Footer::SIZE - 2..].copy_from_slice(&(storage::VERSION + 1).to_be_bytes());
+
+        let mut reader = Reader::from_slice(&bytes);
+        let error = block_on(Footer::read(&mut reader)).unwrap_err();
+        assert!(error.is_version_mismatch());
     }
 }
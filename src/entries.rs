@@ -1,65 +1,314 @@
-use std::hash::{BuildHasher, RandomState};
+use std::{
+    hash::{BuildHasher, Hash, RandomState},
+    iter::Zip,
+    ops::RangeFrom,
+    sync::Arc,
+};
 
 use hashbrown::HashTable;
 
 use crate::Entry;
 
+use self::chunks::Chunks;
+
+mod chunks;
+
+/// Hashes the key of an entry using the given hasher (see [`Entries::with_key()`]).
+pub(crate) type Key<T, S> = Arc<dyn Fn(&S, &T) -> u64 + Send + Sync>;
+
 /// A set of unique entries, each with a `u32` assigned to them.
 ///
 /// This is like an `IndexSet`, but using `u32`s instead of `usize`s.
+///
+/// As long as all of the entries which were inserted are dense (i.e. their
+/// [`Entry::dense_key()`] is the `u32` assigned to them), no hash table is
+/// maintained, and the `u32` assigned to an entry is computed from the entry itself.
+///
+/// Entries are stored in a single contiguous allocation by default, but can instead be
+/// stored in fixed-size chunks (see [`with_chunk_size()`][1]), to avoid requiring a
+/// huge allocation when storing a lot of entries.
+///
+/// [1]: Self::with_chunk_size()
 pub struct Entries<T: Entry, S = RandomState> {
     /// Maps the hashes of the entries in `entries` to their index in it.
-    indexes: HashTable<u32>,
+    ///
+    /// This is `None` as long as all of the entries in `entries` are dense.
+    indexes: Option<HashTable<u32>>,
 
     /// Stores the actual entries which were inserted into the set.
     // TODO(MLB): optionally cache the hash
-    entries: Vec<T>,
+    entries: Chunks<T>,
 
     /// The hasher used to determine where the entries' index should be stored in
     /// `indexes`.
     hasher: S,
+
+    /// Hashes the key of the entries instead of the entries themselves, if they are
+    /// found by key (see [`Entries::with_key()`]).
+    ///
+    /// `indexes` is always maintained when this is `Some`, even if all of the entries
+    /// are dense.
+    key: Option<Key<T, S>>,
+
+    /// The fraction of additional capacity reserved in `indexes` whenever enough
+    /// capacity is reserved for some entries (see [`Entries::with_index_headroom()`]).
+    headroom: f32,
 }
 
 impl<T: Entry, S: BuildHasher + Default> Entries<T, S> {
     /// Creates a new [`Entries`] with enough capacity to insert at least `capacity`
     /// entries.
     #[inline]
-    #[expect(unused)]
     pub fn with_capacity(capacity: usize) -> Self {
+        let mut entries = Self::default();
+        entries.reserve(capacity);
+
+        entries
+    }
+
+    /// Creates a new [`Entries`] containing the given entries, each being assigned its
+    /// position in `entries`.
+    ///
+    /// This reuses the allocation of `entries`, and builds the hash table (if any of the
+    /// entries isn't dense) with the right capacity in a single pass, which is faster
+    /// than inserting the entries one after the other.
+    ///
+    /// The caller _must_ guarantee that the entries are unique (which is only checked in
+    /// debug builds).
+    ///
+    /// ## Panic
+    ///
+    /// Panics if `entries` contains more than `u32::MAX` entries.
+    #[inline]
+    pub fn from_unique_vec(entries: Vec<T>) -> Self {
+        let mut this = Self::default();
+        this.extend_unique(entries);
+
+        this
+    }
+
+    /// Creates a new empty [`Entries`] which stores its entries like this one (i.e.
+    /// either contiguously or in chunks of the same size).
+    #[inline]
+    pub fn new_like(&self) -> Self {
+        Self {
+            indexes: None,
+            entries: self.entries.new_like(),
+            hasher: S::default(),
+            key: self.key.clone(),
+            headroom: self.headroom,
+        }
+    }
+
+    /// Creates a new [`Entries`] which stores at most `chunk_size` entries (rounded up
+    /// to the next power of two) in each of its allocations.
+    #[inline]
+    pub fn with_chunk_size(chunk_size: u32) -> Self {
         Self {
-            indexes: HashTable::with_capacity(capacity),
-            entries: Vec::with_capacity(capacity),
+            indexes: None,
+            entries: Chunks::with_chunk_size(chunk_size),
             hasher: S::default(),
+            key: None,
+            headroom: 0.0,
         }
     }
 }
 
 impl<T: Entry, S: BuildHasher> Entries<T, S> {
+    /// Makes the hash table used to find the `u32` assigned to entries which aren't
+    /// dense use the given hasher, rebuilding it if any such entry is already present.
+    pub fn with_hasher(mut self, hasher: S) -> Self {
+        self.hasher = hasher;
+
+        if self.indexes.is_some() {
+            self.rebuild();
+        }
+
+        self
+    }
+
+    /// Makes the hash table used to find the `u32` assigned to entries hash the key
+    /// returned by `project` for each entry instead of the entry itself, allowing to
+    /// find entries by key with [`get_index_of_by()`][1] (e.g. by an `id` field, while
+    /// ignoring a `timestamp` one).
+    ///
+    /// Equal entries must have equal keys, and [`get_index_of_by()`][1] must be called
+    /// with the same projection, since it hashes the key it is given and only compares
+    /// it with the keys of the entries whose key has the same hash. Since the hash
+    /// table is needed to find entries by key, it is maintained even if all of the
+    /// entries are dense.
+    ///
+    /// [1]: Self::get_index_of_by()
+    #[inline]
+    pub fn with_key<K: Hash>(self, project: impl Fn(&T) -> K + Send + Sync + 'static) -> Self {
+        self.with_key_hash(Arc::new(move |hasher: &S, entry: &T| {
+            hasher.hash_one(project(entry))
+        }))
+    }
+
+    /// Makes the hash table hash the keys of the entries using `key` (see
+    /// [`with_key()`][1]).
+    ///
+    /// [1]: Self::with_key()
+    pub(crate) fn with_key_hash(mut self, key: Key<T, S>) -> Self {
+        self.key = Some(key);
+
+        if !self.is_empty() {
+            self.rebuild();
+        }
+
+        self
+    }
+
+    /// Rebuilds the hash table from scratch (if it is needed).
+    fn rebuild(&mut self) {
+        self.indexes = None;
+
+        let empty = self.entries.new_like();
+        let entries = std::mem::replace(&mut self.entries, empty);
+        self.extend_unique(entries.into_iter().collect());
+    }
+
+    /// Makes the hash table used to find the `u32` assigned to entries which aren't
+    /// dense reserve `headroom` times more capacity than needed (e.g. 30% more with
+    /// `0.3`) whenever capacity is reserved, on top of the capacity it always keeps
+    /// free.
+    ///
+    /// A hash table with more free capacity has less collisions, which mostly makes
+    /// [`get_index_of()`][1] faster for entries which aren't present, at the cost of
+    /// using more memory (4 bytes and a control byte per additional slot). Since a
+    /// bigger table is also less cache-friendly, this can make finding entries which
+    /// are present slower, so it should be measured against the actual workload.
+    ///
+    /// [1]: Self::get_index_of()
+    #[inline]
+    pub fn with_index_headroom(mut self, headroom: f32) -> Self {
+        self.headroom = if headroom.is_finite() {
+            headroom.max(0.0)
+        } else {
+            0.0
+        };
+
+        self
+    }
+
+    /// Returns the capacity to reserve in the hash table when it needs to be able to
+    /// store `additional` more indexes than the `len` it already stores.
+    #[inline]
+    fn index_additional(&self, len: usize, additional: usize) -> usize {
+        additional + ((len + additional) as f32 * self.headroom) as usize
+    }
+
     /// Returns the number of entries present.
     #[inline]
     pub fn len(&self) -> u32 {
         self.entries.len() as u32
     }
 
+    /// Returns `true` if the entries are stored in chunks (see
+    /// [`with_chunk_size()`][1]).
+    ///
+    /// [1]: Self::with_chunk_size()
+    #[inline]
+    pub(crate) fn is_chunked(&self) -> bool {
+        self.entries.is_chunked()
+    }
+
     /// Returns `true` if no entry has been inserted yet.
     #[inline]
     pub fn is_empty(&self) -> bool {
-        self.entries.is_empty()
+        self.len() == 0
+    }
+
+    /// Removes all of the entries, keeping the capacity which was allocated for them.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.indexes = None;
+        self.entries.clear();
+    }
+
+    /// Removes the entries for which `f` returns `false`, given the `u32` assigned to
+    /// them and the entries themselves, and assigns consecutive `u32`s to the remaining
+    /// ones, in order.
+    ///
+    /// Returns the `u32` now assigned to each of the entries which were present (in the
+    /// order of the `u32`s which were assigned to them), or `None` for the ones which
+    /// were removed, so that references to the entries can be updated (e.g. when
+    /// writing a snapshot without the removed entries).
+    ///
+    /// Since the remaining entries might not be dense anymore, the hash table is rebuilt
+    /// from scratch if needed.
+    pub fn retain(&mut self, mut f: impl FnMut(u32, &T) -> bool) -> Vec<Option<u32>> {
+        let mut remap = Vec::with_capacity(self.entries.len());
+        let mut retained = Vec::new();
+
+        self.indexes = None;
+        let empty = self.entries.new_like();
+        let entries = std::mem::replace(&mut self.entries, empty);
+
+        for (index, entry) in (0..).zip(entries) {
+            if f(index, &entry) {
+                remap.push(Some(retained.len() as u32));
+                retained.push(entry);
+            } else {
+                remap.push(None);
+            }
+        }
+
+        self.extend_unique(retained);
+
+        remap
     }
 
     /// Returns the entry represented by the given `u32`, if there is one.
     #[inline]
     pub fn get_at(&self, index: u32) -> Option<&T> {
-        self.entries.get(index as usize)
+        self.entries.get(index)
+    }
+
+    /// Returns all of the entries as a single slice, where the position of each entry is
+    /// the `u32` assigned to it.
+    ///
+    /// Returns `None` if the entries are stored in chunks (see
+    /// [`with_chunk_size()`][1]) and don't all fit in the first one.
+    ///
+    /// [1]: Self::with_chunk_size()
+    #[inline]
+    pub fn as_slice(&self) -> Option<&[T]> {
+        self.entries.as_slice()
     }
 
     /// Returns the `u32` assigned to the given `entry`, if it has been inserted.
     #[inline]
     pub fn get_index_of(&self, entry: &T) -> Option<u32> {
-        let hash = self.hasher.hash_one(entry);
-        let eq = |index: &u32| entry == &self.entries[*index as usize];
+        let Some(indexes) = &self.indexes else {
+            let index = entry.dense_key()?;
+            return (self.get_at(index)? == entry).then_some(index);
+        };
 
-        self.indexes.find(hash, eq).copied()
+        let hash = hash_entry(&self.hasher, &self.key, entry);
+        let eq = |index: &u32| entry == &self.entries[*index];
+
+        indexes.find(hash, eq).copied()
+    }
+
+    /// Returns the `u32` assigned to an entry whose key (as returned by `project`) is
+    /// `key`, if there is one.
+    ///
+    /// This requires the entries to be found by the same projection (see
+    /// [`with_key()`][1]), and always returns `None` if they aren't found by key. If
+    /// several entries have the same key, the `u32` assigned to any of them is returned.
+    ///
+    /// [1]: Self::with_key()
+    #[inline]
+    pub fn get_index_of_by<K: Hash + Eq>(&self, key: &K, project: impl Fn(&T) -> K) -> Option<u32> {
+        self.key.as_ref()?;
+        let indexes = self.indexes.as_ref()?;
+
+        let hash = self.hasher.hash_one(key);
+        let eq = |index: &u32| &project(&self.entries[*index]) == key;
+
+        indexes.find(hash, eq).copied()
     }
 
     /// Iterates over the entries ordered by the `u32` which represent them.
@@ -71,15 +320,28 @@ impl<T: Entry, S: BuildHasher> Entries<T, S> {
             .map(|(index, entry)| (index as u32, entry))
     }
 
+    /// Returns the number of entries which can be inserted without reallocating the
+    /// storage of the entries.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.entries.capacity()
+    }
+
     /// Reserves enough capacity to insert at least `additional` entries.
     pub fn reserve(&mut self, additional: usize) {
         // TODO(MLB): cap at a capacity of `u32::MAX`
-        let hasher = |index: &u32| {
-            let entry = &self.entries[*index as usize];
-            self.hasher.hash_one(entry)
-        };
+        let len = self.entries.len();
+        let index_additional = self.index_additional(len, additional);
+
+        if let Some(indexes) = &mut self.indexes {
+            let hasher = |index: &u32| {
+                let entry = &self.entries[*index];
+                hash_entry(&self.hasher, &self.key, entry)
+            };
+
+            indexes.reserve(index_additional, hasher);
+        }
 
-        self.indexes.reserve(additional, hasher);
         self.entries.reserve(additional);
     }
 
@@ -93,27 +355,231 @@ impl<T: Entry, S: BuildHasher> Entries<T, S> {
     pub fn insert_unique(&mut self, entry: T) -> u32 {
         assert!(self.entries.len() < u32::MAX as usize, "too many entries");
 
-        let hash = self.hasher.hash_one(&entry);
         let index = self.entries.len() as u32;
+        if self.indexes.is_none() && self.key.is_none() && entry.dense_key() == Some(index) {
+            self.entries.push(entry);
+            return index;
+        }
+
+        let hash = hash_entry(&self.hasher, &self.key, &entry);
+        let capacity = self.index_additional(0, self.entries.capacity());
+        let indexes = self.indexes.get_or_insert_with(|| {
+            // The entries which were inserted until now were all dense – we need to build
+            // the hash table from scratch.
+            let mut indexes = HashTable::with_capacity(capacity);
+            for (index, entry) in self.entries.iter().enumerate() {
+                let hash = hash_entry(&self.hasher, &self.key, entry);
+                indexes.insert_unique(hash, index as u32, |_| unreachable!());
+            }
+
+            indexes
+        });
+
         let hasher = |index: &u32| {
-            let entry = &self.entries[*index as usize];
-            self.hasher.hash_one(entry)
+            let entry = &self.entries[*index];
+            hash_entry(&self.hasher, &self.key, entry)
         };
 
-        self.indexes.insert_unique(hash, index, hasher);
+        indexes.insert_unique(hash, index, hasher);
         self.entries.push(entry);
 
         index
     }
+
+    /// Inserts all of the given entries, which aren't already present, assigning them
+    /// consecutive `u32`s in order.
+    ///
+    /// This is like calling [`insert_unique()`][1] with each of the entries, but reuses
+    /// the allocation of `entries` if no entry is present yet (and entries aren't stored
+    /// in chunks), and only grows the hash table once.
+    ///
+    /// The caller _must_ guarantee that the entries are unique and have not been
+    /// inserted already (which is only checked in debug builds).
+    ///
+    /// ## Panic
+    ///
+    /// Panics if this would make the set contain more than `u32::MAX` entries.
+    ///
+    /// [1]: Self::insert_unique()
+    pub(crate) fn extend_unique(&mut self, entries: Vec<T>) {
+        let start = self.entries.len();
+        assert!(
+            start + entries.len() <= u32::MAX as usize,
+            "too many entries"
+        );
+
+        self.entries.append_vec(entries);
+
+        let mut dense = (start..self.entries.len()).map(|index| index as u32);
+        if self.indexes.is_none()
+            && self.key.is_none()
+            && dense.all(|index| self.entries[index].dense_key() == Some(index))
+        {
+            return;
+        }
+
+        // If the entries which were present were all dense, the hash table needs to be
+        // built from scratch.
+        let start = if self.indexes.is_some() { start } else { 0 };
+        let additional = self.index_additional(start, self.entries.len() - start);
+        let indexes = self.indexes.get_or_insert_with(HashTable::new);
+
+        let hasher = |index: &u32| hash_entry(&self.hasher, &self.key, &self.entries[*index]);
+        indexes.reserve(additional, hasher);
+
+        for index in start..self.entries.len() {
+            let entry = &self.entries[index as u32];
+            let hash = hash_entry(&self.hasher, &self.key, entry);
+
+            debug_assert!(
+                indexes
+                    .find(hash, |index| &self.entries[*index] == entry)
+                    .is_none(),
+                "duplicate entry"
+            );
+
+            // Enough capacity was reserved for all of the entries.
+            indexes.insert_unique(hash, index as u32, |_| unreachable!());
+        }
+    }
+}
+
+/// Hashes the given entry using `hasher`, or its key if the entries are found by key
+/// (see [`Entries::with_key()`]).
+#[inline]
+fn hash_entry<T: Entry, S: BuildHasher>(hasher: &S, key: &Option<Key<T, S>>, entry: &T) -> u64 {
+    match key {
+        Some(key) => key(hasher, entry),
+        None => hasher.hash_one(entry),
+    }
+}
+
+impl<T: Entry, S> IntoIterator for Entries<T, S> {
+    type Item = (u32, T);
+    type IntoIter = Zip<RangeFrom<u32>, <Chunks<T> as IntoIterator>::IntoIter>;
+
+    /// Consumes the entries, iterating over them ordered by the `u32` which represent
+    /// them.
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        (0..).zip(self.entries)
+    }
 }
 
 impl<T: Entry, S: Default> Default for Entries<T, S> {
     #[inline]
     fn default() -> Self {
         Self {
-            indexes: HashTable::default(),
-            entries: Vec::default(),
+            indexes: None,
+            entries: Chunks::new(),
             hasher: S::default(),
+            key: None,
+            headroom: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Result, storage, tests::U64};
+
+    use super::*;
+
+    /// An entry whose dense key is itself.
+    #[derive(Debug, PartialEq, Eq, Hash)]
+    struct Dense(u32);
+
+    impl Entry for Dense {
+        const SIZE: usize = 4;
+
+        async fn read(reader: &mut storage::Reader) -> Result<Self> {
+            Ok(Self(reader.read_u32().await?))
+        }
+
+        async fn write(&self, writer: &mut storage::Writer) -> Result<()> {
+            writer.write_u32(self.0).await
         }
+
+        fn dense_key(&self) -> Option<u32> {
+            Some(self.0)
+        }
+    }
+
+    #[test]
+    fn dense_entries_skip_the_hash_table() {
+        let mut entries = Entries::<Dense>::from_unique_vec((0..3).map(Dense).collect());
+        entries.insert_unique(Dense(3));
+
+        assert!(entries.indexes.is_none());
+        assert_eq!(entries.get_index_of(&Dense(2)), Some(2));
+        assert_eq!(entries.get_index_of(&Dense(4)), None);
+
+        // Inserting an entry which isn't dense builds the hash table for all of them.
+        entries.insert_unique(Dense(10));
+        entries.extend_unique(vec![Dense(5)]);
+
+        assert!(entries.indexes.is_some());
+        for (index, entry) in [0, 1, 2, 3, 10, 5].into_iter().enumerate() {
+            assert_eq!(entries.get_index_of(&Dense(entry)), Some(index as u32));
+        }
+
+        assert_eq!(entries.get_index_of(&Dense(4)), None);
+    }
+
+    #[test]
+    fn from_unique_vec() {
+        let vec = (0..100).rev().map(U64).collect::<Vec<_>>();
+        let ptr = vec.as_ptr();
+
+        // The entries are assigned their position, and aren't moved.
+        let entries = Entries::<U64>::from_unique_vec(vec);
+        assert_eq!(entries.as_slice().map(<[_]>::as_ptr), Some(ptr));
+        assert_eq!(entries.len(), 100);
+        assert_eq!(entries.get_index_of(&U64(99)), Some(0));
+        assert_eq!(entries.get_index_of(&U64(0)), Some(99));
+        assert_eq!(entries.get_index_of(&U64(100)), None);
+    }
+
+    #[test]
+    fn index_headroom() {
+        let capacity = |headroom| {
+            let mut entries = Entries::<U64>::default().with_index_headroom(headroom);
+            entries.extend_unique((0..100).map(U64).collect());
+            assert_eq!(entries.get_index_of(&U64(42)), Some(42));
+
+            entries.indexes.as_ref().map_or(0, HashTable::capacity)
+        };
+
+        assert!(capacity(0.0) < 200);
+        assert!(capacity(1.0) >= 200);
+
+        // Invalid headrooms are ignored.
+        assert_eq!(capacity(-1.0), capacity(0.0));
+        assert_eq!(capacity(f32::NAN), capacity(0.0));
+    }
+
+    #[test]
+    fn find_by_key() {
+        let key = |entry: &U64| entry.0 + 1000;
+
+        let mut entries = Entries::<U64>::default().with_key(key);
+        entries.extend_unique((0..3).map(U64).collect());
+        entries.insert_unique(U64(3));
+        assert_eq!(entries.get_index_of_by(&1002, key), Some(2));
+        assert_eq!(entries.get_index_of_by(&2, key), None);
+        assert_eq!(entries.get_index_of(&U64(3)), Some(3));
+
+        // Entries can only be found by key once the projection is set.
+        let entries = Entries::<U64>::from_unique_vec((0..3).map(U64).collect());
+        assert_eq!(entries.get_index_of_by(&1002, key), None);
+
+        let entries = entries.with_key(key);
+        assert_eq!(entries.get_index_of_by(&1002, key), Some(2));
+
+        // The hash table is maintained even for dense entries.
+        let entries = Entries::<Dense>::from_unique_vec(vec![Dense(0), Dense(1)]);
+        let entries = entries.with_key(|entry: &Dense| entry.0);
+        assert!(entries.indexes.is_some());
+        assert_eq!(entries.get_index_of_by(&1, |entry| entry.0), Some(1));
     }
 }
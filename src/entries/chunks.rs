@@ -0,0 +1,240 @@
+use std::{iter::Flatten, ops::Index, vec};
+
+/// A list of entries which are stored in fixed-size chunks.
+///
+/// This allows storing huge amounts of entries without requiring a single huge
+/// contiguous allocation, while still allowing accessing any of them in `O(1)`.
+///
+/// Chunks contain `2^shift` entries, which allows finding the chunk and the position
+/// inside of it of any entry with a shift and a mask. If `shift` is `32`, all of the
+/// entries are stored in a single chunk, which grows like a [`Vec`].
+pub struct Chunks<T> {
+    /// The chunks storing the entries.
+    ///
+    /// All of the chunks but the last non-empty one are full, and all of the chunks
+    /// after it (which were allocated by [`reserve()`][1]) are empty.
+    ///
+    /// [1]: Self::reserve()
+    chunks: Vec<Vec<T>>,
+
+    /// The base-2 logarithm of the number of entries stored in each chunk.
+    shift: u32,
+
+    /// The number of entries stored in all of the chunks.
+    len: usize,
+}
+
+impl<T> Chunks<T> {
+    /// The value of `shift` for which all of the entries are stored in a single chunk.
+    const UNCHUNKED: u32 = u32::BITS;
+
+    /// Creates a new [`Chunks`] storing all of the entries in a single chunk.
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_shift(Self::UNCHUNKED)
+    }
+
+    /// Creates a new [`Chunks`] storing at most `chunk_size` entries in each chunk.
+    ///
+    /// `chunk_size` is rounded up to the next power of two.
+    #[inline]
+    pub fn with_chunk_size(chunk_size: u32) -> Self {
+        let shift = chunk_size.max(1).next_power_of_two().trailing_zeros();
+        Self::with_shift(shift)
+    }
+
+    /// Creates a new empty [`Chunks`] storing as many entries in each chunk as this one.
+    #[inline]
+    pub fn new_like(&self) -> Self {
+        Self::with_shift(self.shift)
+    }
+
+    /// Creates a new [`Chunks`] storing `2^shift` entries in each chunk.
+    #[inline]
+    fn with_shift(shift: u32) -> Self {
+        Self {
+            chunks: Vec::new(),
+            shift,
+            len: 0,
+        }
+    }
+
+    /// Returns the number of entries present.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the entries are stored in fixed-size chunks rather than in a
+    /// single one.
+    #[inline]
+    pub fn is_chunked(&self) -> bool {
+        self.shift != Self::UNCHUNKED
+    }
+
+    /// Returns the number of entries which can be stored without allocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        if self.shift == Self::UNCHUNKED {
+            return self.chunks.first().map(Vec::capacity).unwrap_or_default();
+        }
+
+        self.chunks.len() << self.shift
+    }
+
+    /// Returns the entry at the given index, if there is one.
+    #[inline]
+    pub fn get(&self, index: u32) -> Option<&T> {
+        if index as usize >= self.len {
+            return None;
+        }
+
+        let (chunk, offset) = self.locate(index as usize);
+        Some(&self.chunks[chunk][offset])
+    }
+
+    /// Returns all of the entries as a single slice, ordered by their index, if all of
+    /// them are stored in the same chunk.
+    #[inline]
+    pub fn as_slice(&self) -> Option<&[T]> {
+        match self.chunks.first() {
+            Some(chunk) if chunk.len() == self.len => Some(chunk),
+            Some(_) => None,
+            None => Some(&[]),
+        }
+    }
+
+    /// Iterates over the entries, ordered by their index.
+    #[inline]
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &T> {
+        (0..self.len as u32).map(|index| &self[index])
+    }
+
+    /// Removes all of the entries, keeping the chunks which were allocated.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.chunks.iter_mut().for_each(Vec::clear);
+        self.len = 0;
+    }
+
+    /// Reserves enough capacity to push at least `additional` entries.
+    pub fn reserve(&mut self, additional: usize) {
+        if self.shift == Self::UNCHUNKED {
+            if self.chunks.is_empty() {
+                self.chunks.push(Vec::new());
+            }
+
+            self.chunks[0].reserve(additional);
+            return;
+        }
+
+        let required = self.len + additional;
+        while self.capacity() < required {
+            self.chunks.push(Vec::with_capacity(1 << self.shift));
+        }
+    }
+
+    /// Pushes an entry after all of the ones already present.
+    pub fn push(&mut self, entry: T) {
+        let (chunk, _) = self.locate(self.len);
+        if chunk == self.chunks.len() {
+            // Chunks are allocated with their full size upfront, so that they never need
+            // to be reallocated.
+            let capacity = if self.shift == Self::UNCHUNKED {
+                0
+            } else {
+                1 << self.shift
+            };
+
+            self.chunks.push(Vec::with_capacity(capacity));
+        }
+
+        self.chunks[chunk].push(entry);
+        self.len += 1;
+    }
+
+    /// Pushes all of the given entries after the ones already present.
+    ///
+    /// If no entry is present and all of the entries are stored in a single chunk, this
+    /// reuses the allocation of `entries` rather than moving them.
+    pub fn append_vec(&mut self, entries: Vec<T>) {
+        if self.shift == Self::UNCHUNKED && self.len == 0 {
+            self.len = entries.len();
+            self.chunks.clear();
+            self.chunks.push(entries);
+            return;
+        }
+
+        self.reserve(entries.len());
+        for entry in entries {
+            self.push(entry);
+        }
+    }
+
+    /// Returns the index of the chunk containing the entry at the given index, along
+    /// with the entry's position inside of it.
+    #[inline]
+    fn locate(&self, index: usize) -> (usize, usize) {
+        let index = index as u64;
+        let mask = (1 << self.shift) - 1;
+
+        ((index >> self.shift) as usize, (index & mask) as usize)
+    }
+}
+
+impl<T> IntoIterator for Chunks<T> {
+    type Item = T;
+    type IntoIter = Flatten<vec::IntoIter<Vec<T>>>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.chunks.into_iter().flatten()
+    }
+}
+
+impl<T> Index<u32> for Chunks<T> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, index: u32) -> &T {
+        self.get(index).expect("index out of bounds")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_are_bounded() {
+        let mut chunks = Chunks::with_chunk_size(3);
+        assert!(chunks.is_chunked());
+
+        chunks.append_vec((0..6).collect());
+        chunks.reserve(3);
+        for entry in 6..10 {
+            chunks.push(entry);
+        }
+
+        assert_eq!(chunks.len(), 10);
+        assert!(chunks.chunks.iter().all(|chunk| chunk.capacity() == 4));
+        assert_eq!(
+            chunks.iter().copied().collect::<Vec<_>>(),
+            (0..10).collect::<Vec<_>>()
+        );
+        assert_eq!((chunks.get(9), chunks.get(10)), (Some(&9), None));
+        assert_eq!(chunks.as_slice(), None);
+
+        // Clearing the entries keeps the chunks for the next ones.
+        chunks.clear();
+        chunks.push(10);
+        assert_eq!((chunks.len(), chunks.capacity()), (1, 12));
+        assert_eq!(chunks.as_slice(), Some(&[10][..]));
+
+        let mut chunks = Chunks::new();
+        assert!(!chunks.is_chunked());
+
+        chunks.append_vec((0..10).collect());
+        assert_eq!(chunks.as_slice(), Some(&(0..10).collect::<Vec<_>>()[..]));
+    }
+}
@@ -1,11 +1,43 @@
 use std::fmt::{self, Display, Formatter};
 
-use crate::{LinkId, storage::Kind};
+use crate::{ChainIndex, LinkId, LinkSummary, storage::Kind};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// The errors which can happen while reading or writing chains.
+///
+/// New variants can be added without it being a breaking change, which means that
+/// matching on this needs a wildcard arm – the `is_*()` methods allow classifying
+/// errors without matching on their variants.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
+    /// The magic bytes stored in a file's footer do not match the ones expected for
+    /// the kind of file being read.
+    BadMagic { expected: [u8; 4], got: [u8; 4] },
+
+    /// Some of the links committed by a [`Batch`][1] extend the same previous link as
+    /// another link (see [`Error::Forked`]).
+    ///
+    /// All of the links were committed, and `committed` contains information about each
+    /// of them, in the order they were added to the batch. `forks` contains the ID of
+    /// each forked link, along with the ID of another link extending the same previous
+    /// link.
+    ///
+    /// [1]: crate::Batch
+    BatchForked {
+        committed: Vec<LinkSummary>,
+        forks: Vec<(LinkId, LinkId)>,
+    },
+
+    /// Writing a new entry would make the chain contain more entries than the limit set
+    /// on the writer.
+    CapacityExceeded { limit: u32, attempted: u32 },
+
+    /// The content of an encrypted file could not be decrypted, either because the key
+    /// is wrong or because the file was modified.
+    Decryption,
+
     /// The chain is disconnected.
     ///
     /// When loading the links start from `latest` and going backward, we should
@@ -23,12 +55,59 @@ pub enum Error {
     /// The newly created link is empty, which isn't allowed.
     Empty,
 
+    /// The file is encrypted but the storage wasn't configured with a key to decrypt
+    /// it.
+    Encrypted,
+
+    /// Reading the entry at the given offset in a file of the given link still failed
+    /// transiently after being attempted `attempts` times.
+    EntryRead {
+        link: LinkId,
+        offset: usize,
+        attempts: u32,
+        error: Box<Error>,
+    },
+
+    /// The encoded entry doesn't contain the expected number of bytes.
+    EntrySize { expected: usize, got: usize },
+
     /// The file is smaller than expected.
     FileSize { expected: usize, got: usize },
 
+    /// The given link was created with the given index, but `sibling` extends the same
+    /// previous link, which means that the chain forked.
+    Forked {
+        index: ChainIndex,
+        link: LinkId,
+        sibling: LinkId,
+    },
+
+    /// The file of the given link is inconsistent with its footer, for the given
+    /// reason.
+    Inconsistent { link: LinkId, reason: &'static str },
+
+    /// The base of the incremental snapshot of the given link is not a full snapshot
+    /// containing the expected number of entries.
+    InvalidBase { link: LinkId, base: LinkId },
+
+    /// The snapshot of the given link is sorted but doesn't store a valid position for
+    /// each of its entries.
+    InvalidPermutation { link: LinkId },
+
     /// The reader does not contain the entries present in the expected link.
     InvalidReader { expected: LinkId, got: LinkId },
 
+    /// The reader cannot be moved to the given position, as it is outside of the file.
+    InvalidSeek { position: i64, file_size: usize },
+
+    /// An I/O error occurred while interacting with a local file.
+    Io(std::io::Error),
+
+    /// The snapshot of the given link doesn't contain the same entry as the deltas of
+    /// the links up to it for the given `u32` (which is the number of entries present
+    /// in the deltas if the snapshot contains more entries).
+    Mismatch { link: LinkId, index: u32 },
+
     /// A snapshot cannot be created from a reader if no previous link ID has been
     /// provided when creating the writer.
     MissingPrevious,
@@ -37,17 +116,78 @@ pub enum Error {
     /// delta for the link.
     NotEmpty,
 
+    /// The storage returned less bytes than were requested when reading a file.
+    ShortRead { expected: usize, got: usize },
+
     /// An error occurred while interacting with the storage.
     Storage(opendal::Error),
 
+    /// The given storage operation took longer than the timeout set on the storage.
+    Timeout { operation: &'static str },
+
     /// The maximum number of entries ([`u32::MAX`]) has been reached, no new entry can
     /// be inserted.
     TooManyEntries,
 
+    /// The operation is not supported by the storage.
+    Unsupported { operation: &'static str },
+
     /// The storage format version used to encode a file is unsupported.
     Version { expected: u16, got: u16 },
 }
 
+impl Error {
+    /// Returns `true` if the error is caused by a file (or the link it belongs to) not
+    /// existing.
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            Self::DoesNotExist { .. } => true,
+            Self::Storage(error) => error.kind() == opendal::ErrorKind::NotFound,
+            Self::Io(error) => error.kind() == std::io::ErrorKind::NotFound,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the error is caused by a file having been encoded with a
+    /// version of the storage format which isn't supported.
+    #[inline]
+    pub fn is_version_mismatch(&self) -> bool {
+        matches!(self, Self::Version { .. })
+    }
+
+    /// Returns `true` if retrying the operation which failed might succeed (e.g. if it
+    /// timed out, or if the storage failed temporarily).
+    ///
+    /// This includes reading an entry which still failed after all of the retries set
+    /// with [`Storage::with_entry_retries()`][1].
+    ///
+    /// [1]: crate::Storage::with_entry_retries()
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::EntryRead { error, .. } => error.is_retryable(),
+            _ => self.is_transient(),
+        }
+    }
+
+    /// Returns `true` if the error might not happen again when retrying the operation
+    /// which failed.
+    #[inline]
+    pub(crate) fn is_transient(&self) -> bool {
+        match self {
+            Self::Storage(error) => error.is_temporary(),
+            Self::Timeout { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    #[inline]
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
 impl From<opendal::Error> for Error {
     #[inline]
     fn from(error: opendal::Error) -> Self {
@@ -58,6 +198,32 @@ impl From<opendal::Error> for Error {
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
+            Self::BadMagic { expected, got } => write!(
+                f,
+                "Invalid magic bytes: expected \"{}\" but file contains \"{}\"",
+                expected.escape_ascii(),
+                got.escape_ascii()
+            ),
+
+            Self::BatchForked { committed, forks } => {
+                write!(f, "Forked chains: ")?;
+                for (position, (link, sibling)) in forks.iter().enumerate() {
+                    if position > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{sibling} extends the same link as {link}")?;
+                }
+
+                write!(f, " (all {} links were committed)", committed.len())
+            }
+
+            Self::CapacityExceeded { limit, attempted } => write!(
+                f,
+                "Capacity exceeded: the chain would contain {attempted} entries but is limited to {limit}"
+            ),
+
+            Self::Decryption => write!(f, "Decryption failed: wrong key or modified file"),
             Self::Disconnected {
                 latest,
                 expected,
@@ -69,16 +235,70 @@ impl Display for Error {
 
             Self::DoesNotExist { link, kind } => write!(f, "File does not exist: {link}.{kind}"),
             Self::Empty => write!(f, "Link is empty"),
+            Self::Encrypted => write!(f, "File is encrypted but no key was provided"),
+            Self::EntryRead {
+                link,
+                offset,
+                attempts,
+                error,
+            } => write!(
+                f,
+                "Failed to read the entry at offset {offset} of {link} after {attempts} attempts: {error}"
+            ),
+
+            Self::EntrySize { expected, got } => write!(
+                f,
+                "Invalid entry size: expected {expected} bytes but got {got} bytes"
+            ),
+
             Self::FileSize { expected, got } => write!(
                 f,
                 "File is too small: expected >= {expected} bytes but it only contains {got} bytes"
             ),
 
+            Self::Forked {
+                index,
+                link,
+                sibling,
+            } => write!(
+                f,
+                "Forked chain: {sibling} extends the same link as {link}, at index {index}"
+            ),
+
+            Self::Inconsistent { link, reason } => {
+                write!(f, "Inconsistent link: {link} ({reason})")
+            }
+
+            Self::InvalidBase { link, base } => write!(
+                f,
+                "Invalid base: the snapshot of {link} cannot be based on the snapshot of {base}"
+            ),
+
+            Self::InvalidPermutation { link } => write!(
+                f,
+                "Invalid permutation: the sorted snapshot of {link} doesn't store a valid position for each entry"
+            ),
+
             Self::InvalidReader { expected, got } => write!(
                 f,
                 "Invalid reader: should be at {expected} but is instead at {got}"
             ),
 
+            Self::InvalidSeek {
+                position,
+                file_size,
+            } => write!(
+                f,
+                "Invalid seek: position {position} is outside of the file, which contains {file_size} bytes"
+            ),
+
+            Self::Io(error) => write!(f, "{error}"),
+
+            Self::Mismatch { link, index } => write!(
+                f,
+                "Mismatch: the snapshot of {link} and its deltas differ at entry {index}"
+            ),
+
             Self::MissingPrevious => {
                 write!(
                     f,
@@ -87,8 +307,16 @@ impl Display for Error {
             }
 
             Self::NotEmpty => write!(f, "Cannot create a snapshot with a non-empty delta"),
+            Self::ShortRead { expected, got } => write!(
+                f,
+                "Short read: expected {expected} bytes but the storage returned {got} bytes"
+            ),
             Self::Storage(error) => write!(f, "{error}"),
+            Self::Timeout { operation } => write!(f, "Storage operation timed out: {operation}"),
             Self::TooManyEntries => write!(f, "Reached the maximum number of entries"),
+            Self::Unsupported { operation } => {
+                write!(f, "Unsupported operation for this storage: {operation}")
+            }
 
             Self::Version { expected, got } => write!(
                 f,
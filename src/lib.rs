@@ -24,32 +24,79 @@ use std::{
     hash::Hash,
 };
 
-pub(crate) use self::{
-    delta::Footer as DFooter, entries::Entries, snapshot::Footer as SFooter, storage::Storage,
-};
+pub(crate) use self::{delta::Footer as DFooter, snapshot::Footer as SFooter, storage::Storage};
 
 use uuid::Uuid;
 
+mod bytes;
+mod cache;
 mod delta;
 mod entries;
 mod error;
 mod reader;
 mod snapshot;
+mod verify;
 mod writer;
 
+#[cfg(test)]
+mod tests;
+
 pub mod storage;
 
 pub use self::{
+    bytes::Bytes,
+    cache::ChainCache,
+    delta::Footer as DeltaFooter,
+    entries::Entries,
     error::{Error, Result},
-    reader::Reader,
-    writer::{LazyWriter, Writer},
+    reader::{
+        ArenaReader, CappedReader, FromBytes, GetEntry, NewEntries, Partial, RawReader, Reader,
+        ReaderBuilder, SetDiff, load_entries_into,
+    },
+    snapshot::Footer as SnapshotFooter,
+    verify::{
+        LinkReport, Report, verify, verify_snapshot_consistency, verify_stream,
+        verify_with_concurrency,
+    },
+    writer::{Batch, FinishStrategy, LazyWriter, LinkSummary, Writer, WriterBuilder},
 };
 
+#[cfg(feature = "mmap")]
+pub use self::reader::{DecodeEntry, MmapReader};
+
 /// The ID of a link in a chain, extending all previous links (unless it is the
 /// first one) with new entries.
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct LinkId(Uuid);
 
+/// The index of a link in a chain.
+///
+/// This is equal to the number of previous links in the chain, and is stored as a
+/// `u32`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChainIndex(u32);
+
+/// Information about the link whose file an entry is being read from (see
+/// [`Entry::read_with_context()`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LinkContext {
+    /// The ID of the link.
+    pub link: LinkId,
+
+    /// The index of the link in its chain.
+    pub index: ChainIndex,
+
+    /// The ID of the previous link which the link extends, if any.
+    pub previous: Option<LinkId>,
+
+    /// The number of entries in the chain up to the link (included).
+    pub total: u32,
+
+    /// The version of the storage format which was used to encode the file.
+    pub version: u16,
+}
+
 /// An entry which can be inserted into a chain of links.
 ///
 /// Each unique entry will have a unique `u32` assigned to it, so that entries can
@@ -57,6 +104,9 @@ pub struct LinkId(Uuid);
 #[trait_variant::make(Send)]
 pub trait Entry: Eq + Hash + Sized {
     /// The size of an entry when encoded.
+    ///
+    /// This can be `0` for entries which don't need to store any bytes, in which case
+    /// the number of entries in each link is only determined by its footer.
     const SIZE: usize;
 
     /// Reads an entry from the given reader.
@@ -64,13 +114,122 @@ pub trait Entry: Eq + Hash + Sized {
     /// This _must_ read exactly `SIZE` bytes.
     async fn read(reader: &mut storage::Reader) -> Result<Self>;
 
+    /// Reads an entry from the given reader, which is reading a file encoded with the
+    /// given version of the storage format.
+    ///
+    /// This allows a single implementation to decode entries which were encoded with
+    /// an older layout. By default, this ignores `version` and calls [`read()`][1].
+    ///
+    /// This _must_ read exactly `SIZE` bytes.
+    ///
+    /// [1]: Self::read()
+    fn read_versioned(
+        reader: &mut storage::Reader,
+        version: u16,
+    ) -> impl Future<Output = Result<Self>> + Send {
+        let _ = version;
+        Self::read(reader)
+    }
+
+    /// Reads an entry from the given reader, which is reading a file of the link
+    /// described by `context`.
+    ///
+    /// This allows decoding entries whose encoding depends on the link storing them
+    /// (e.g. relatively to its index). By default, this calls
+    /// [`read_versioned()`][1] with `context.version`.
+    ///
+    /// Note that the entries stored in a snapshot are read with the context of the link
+    /// whose file stores them (e.g. the link to which the snapshot belongs, or the base
+    /// of an incremental snapshot), rather than of the link which introduced them.
+    ///
+    /// This _must_ read exactly `SIZE` bytes.
+    ///
+    /// [1]: Self::read_versioned()
+    fn read_with_context(
+        reader: &mut storage::Reader,
+        context: &LinkContext,
+    ) -> impl Future<Output = Result<Self>> + Send {
+        Self::read_versioned(reader, context.version)
+    }
+
+    /// Decodes an entry from the given bytes, synchronously.
+    ///
+    /// This allows decoding entries which are already in memory (e.g. in a
+    /// memory-mapped file) without any I/O. By default, this calls [`read()`][1] with
+    /// a reader over `bytes`, which never needs to wait.
+    ///
+    /// Fails if `bytes` doesn't contain exactly `SIZE` bytes.
+    ///
+    /// [1]: Self::read()
+    fn read_from_slice(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != Self::SIZE {
+            return Err(Error::EntrySize {
+                expected: Self::SIZE,
+                got: bytes.len(),
+            });
+        }
+
+        let mut reader = storage::Reader::from_slice(bytes);
+        storage::complete_now(Self::read(&mut reader))
+    }
+
     /// Writes the entry to the given writer.
     ///
-    /// This _must_ write exactly `SIZE` bytes.
+    /// This _must_ write exactly `SIZE` bytes, but can write them in several pieces
+    /// (e.g. by calling [`Writer::write_slice()`][1] with each chunk of the encoding of
+    /// a large entry). Writing an entry fails with [`Error::EntrySize`] otherwise.
+    ///
+    /// [1]: storage::Writer::write_slice()
     async fn write(&self, writer: &mut storage::Writer) -> Result<()>;
+
+    /// Returns the `u32` which this entry would be assigned if entries were inserted
+    /// densely, i.e. if the entry is effectively its own index (e.g. sequential IDs).
+    ///
+    /// As long as all of the entries loaded by a reader are assigned the `u32` returned
+    /// by this, the reader doesn't need to maintain a hash table to map entries to
+    /// their `u32`, roughly halving its memory usage. It falls back to using a hash
+    /// table as soon as an entry isn't dense.
+    ///
+    /// This must be consistent with [`Eq`] (i.e. equal entries must return the same
+    /// value). By default, this returns `None`.
+    #[inline]
+    fn dense_key(&self) -> Option<u32> {
+        None
+    }
+}
+
+/// The unit type is used as the value of entries when no value is associated to them
+/// (e.g. by default for [`Reader`]).
+impl Entry for () {
+    const SIZE: usize = 0;
+
+    #[inline]
+    async fn read(_: &mut storage::Reader) -> Result<Self> {
+        Ok(())
+    }
+
+    #[inline]
+    async fn write(&self, _: &mut storage::Writer) -> Result<()> {
+        Ok(())
+    }
 }
 
 impl LinkId {
+    /// Derives a link ID from `name` in the given `namespace`, using it as a version 5
+    /// UUID (i.e. based on a SHA-1 hash).
+    ///
+    /// The same `namespace` and `name` always produce the same ID, which allows choosing
+    /// the IDs of new links (see [`Writer::with_id()`][1]) deterministically, e.g. to
+    /// derive them from the links' content. Since version 5 UUIDs always have their
+    /// version bits set, this never returns the nil UUID, which represents the absence
+    /// of a link in footers.
+    ///
+    /// [1]: crate::Writer::with_id()
+    #[inline]
+    pub fn from_name(namespace: Uuid, name: &[u8]) -> Self {
+        Self(Uuid::new_v5(&namespace, name))
+    }
+
     /// Generates a new random link ID.
     #[inline]
     pub(crate) fn random() -> Self {
@@ -91,6 +250,60 @@ impl LinkId {
     }
 }
 
+impl ChainIndex {
+    /// Creates a chain index from the given `u32`.
+    #[inline]
+    pub const fn new(index: u32) -> Self {
+        Self(index)
+    }
+
+    /// Returns the chain index as a `u32`.
+    #[inline]
+    pub const fn get(self) -> u32 {
+        self.0
+    }
+
+    /// Returns the index of the link following the one at this index.
+    #[inline]
+    pub(crate) fn next(self) -> Self {
+        Self(self.0 + 1)
+    }
+}
+
+impl LinkContext {
+    /// Returns the context of the delta (or values) file of `link`, whose footer is
+    /// `footer`.
+    #[inline]
+    pub(crate) fn delta(link: LinkId, footer: &DFooter) -> Self {
+        Self {
+            link,
+            index: footer.index,
+            previous: footer.previous,
+            total: footer.total,
+            version: footer.version,
+        }
+    }
+
+    /// Returns the context of the snapshot file of `link`, whose footer is `footer`.
+    #[inline]
+    pub(crate) fn snapshot(link: LinkId, footer: &SFooter) -> Self {
+        Self {
+            link,
+            index: footer.index,
+            previous: footer.previous,
+            total: footer.count,
+            version: footer.version,
+        }
+    }
+}
+
+impl Display for ChainIndex {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 impl Debug for LinkId {
     #[inline]
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
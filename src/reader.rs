@@ -1,9 +1,43 @@
-use std::hash::{BuildHasher, RandomState};
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashSet},
+    hash::{BuildHasher, Hash, RandomState},
+    io::SeekFrom,
+    marker::PhantomData,
+    ops::Range,
+};
 
-use crate::{DFooter, Entries, Entry, Error, LinkId, Result, SFooter, Storage, storage::Kind::*};
+use futures::{AsyncWrite, AsyncWriteExt, Stream, StreamExt, TryStreamExt, stream};
+use futures_timer::Delay;
+
+use crate::{
+    ChainIndex, DFooter, Entries, Entry, Error, LinkContext, LinkId, Result, SFooter, Storage,
+    storage::{self, Kind, Kind::*, Retries, complete_now},
+};
+
+mod arena;
+mod builder;
+mod capped;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod raw;
+
+pub use self::arena::{ArenaReader, FromBytes};
+pub use self::builder::ReaderBuilder;
+pub use self::capped::CappedReader;
+#[cfg(feature = "mmap")]
+pub use self::mmap::{DecodeEntry, MmapReader};
+pub use self::raw::RawReader;
 
 /// A reader which allows getting the entries of a chain stored in some storage.
-pub struct Reader<T: Entry, S = RandomState> {
+///
+/// If `V` isn't zero-sized, the value associated to each entry (see
+/// [`Writer::write_unique_with_value()`][1]) is also loaded, and can be retrieved with
+/// [`get_value_at()`][2].
+///
+/// [1]: crate::Writer::write_unique_with_value()
+/// [2]: Self::get_value_at()
+pub struct Reader<T: Entry, S = RandomState, V = ()> {
     /// The storage containing the chain's links.
     storage: Storage,
 
@@ -11,13 +45,114 @@ pub struct Reader<T: Entry, S = RandomState> {
     latest: LinkId,
 
     /// The index in the chain of the latest link which has been loaded.
-    index: u32,
+    index: ChainIndex,
+
+    /// The ID of the link which the latest link which has been loaded extends, if any.
+    previous: Option<LinkId>,
+
+    /// The ID of the first link in the chain, if it is known.
+    ///
+    /// This is only unknown if the entries were loaded from the snapshot of a link
+    /// which isn't the first one, without reading the previous links' files.
+    root: Option<LinkId>,
+
+    /// The `u32` assigned to the first entry which has been loaded.
+    ///
+    /// This is only non-zero if only the latest links of the chain have been loaded
+    /// (see [`Reader::open_partial()`]), in which case the `u32` assigned to each entry
+    /// is its position in `entries` plus `start`.
+    start: u32,
+
+    /// The ID of the link preceding the oldest link which has been loaded, if only the
+    /// latest links of the chain have been loaded.
+    resume: Option<LinkId>,
 
     /// The entries which have been loaded.
     entries: Entries<T, S>,
+
+    /// The values associated to the entries which have been loaded, ordered like them.
+    ///
+    /// This is always empty if `V` is zero-sized. Otherwise, this might contain less
+    /// values than `entries` contains entries if loading the values of the latest links
+    /// failed while reloading the reader.
+    values: Vec<V>,
+
+    /// The IDs of the links which have been loaded, along with the `u32` assigned to
+    /// the first entry they introduced, ordered by their index in the chain.
+    ///
+    /// If the entries were loaded from a snapshot, the link to which the snapshot
+    /// belongs is considered to have introduced all of the entries it contains.
+    links: Vec<(u32, LinkId)>,
+
+    /// The ID of the link from whose snapshot the oldest entries which have been loaded
+    /// were loaded, if they weren't loaded from deltas.
+    snapshot: Option<LinkId>,
+
+    /// The versions of the storage format which were used to encode the files of the
+    /// links which have been loaded.
+    versions: BTreeSet<u16>,
+
+    /// The number of bytes which have been read from the storage so far.
+    bytes_read: u64,
+}
+
+/// A reader which can return the entry represented by a `u32`, either borrowed from
+/// the reader or owned.
+///
+/// A [`Reader`] keeps all of the entries it loaded in memory and returns them
+/// borrowed, while a memory-mapped reader (`MmapReader`, with the `mmap` feature)
+/// decodes them when they are accessed and returns them owned. This allows writing a
+/// single code path which works with both, at the cost of requiring `T: Clone`, and
+/// of checking for errors which a [`Reader`] never returns.
+pub trait GetEntry<T: Clone> {
+    /// Returns the entry represented by the given `u32`, if there is one.
+    fn get_cow_at(&self, index: u32) -> Result<Option<Cow<'_, T>>>;
+}
+
+/// A reader which might only have loaded the latest links of a chain, as returned by
+/// [`Reader::open_partial()`].
+pub struct Partial<T: Entry, S = RandomState, V = ()> {
+    /// The reader, which can be used to get the entries which have been loaded.
+    pub reader: Reader<T, S, V>,
+
+    /// The ID of the link from which loading should continue (see
+    /// [`Reader::continue_loading()`]), if the chain hasn't been fully loaded.
+    pub continue_from: Option<LinkId>,
+}
+
+/// The entries which were added to a chain between two of its links, as returned by
+/// [`Reader::watch()`].
+pub struct NewEntries<T> {
+    /// The ID of the link up to which the entries were loaded.
+    pub latest: LinkId,
+
+    /// The `u32` assigned to the first entry in `entries`.
+    pub start: u32,
+
+    /// The entries which were added, ordered by the `u32` which represent them.
+    pub entries: Vec<T>,
+}
+
+/// The differences between the entries loaded by a reader and a set of entries, as
+/// returned by [`Reader::diff_set()`].
+pub struct SetDiff<'a, T> {
+    /// The entries which are in the set but which haven't been loaded by the reader.
+    pub missing: Vec<&'a T>,
+
+    /// The entries which have been loaded by the reader but which aren't in the set,
+    /// ordered by the `u32` which represent them.
+    pub extra: Vec<(u32, &'a T)>,
+}
+
+impl<T> SetDiff<'_, T> {
+    /// Returns `true` if the reader contains exactly the expected entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty()
+    }
 }
 
-impl<T: Entry, S: BuildHasher + Default> Reader<T, S> {
+impl<T: Entry, S: BuildHasher + Default, V: Entry> Reader<T, S, V> {
     /// Creates a new reader from the given storage, loading the necessary links' files.
     ///
     /// `latest` is the latest link in the chain, such that [`get_at()`][1] and
@@ -26,88 +161,516 @@ impl<T: Entry, S: BuildHasher + Default> Reader<T, S> {
     ///
     /// [1]: Self::get_at()
     /// [2]: Self::get_index_of()
+    #[inline]
     pub async fn open(latest: LinkId, storage: Storage) -> Result<Self> {
+        Self::open_with(latest, storage, Entries::default(), true, None, None).await
+    }
+
+    /// Creates a new reader from the given storage, loading the necessary links' files,
+    /// and storing the entries in chunks of at most `chunk_size` entries (rounded up to
+    /// the next power of two).
+    ///
+    /// Contrarily to [`open()`][1], which stores all of the entries in a single
+    /// allocation, this allows loading huge chains even when memory is too fragmented
+    /// to allocate a contiguous block big enough for all of their entries.
+    ///
+    /// [1]: Self::open()
+    #[inline]
+    pub async fn open_chunked(latest: LinkId, storage: Storage, chunk_size: u32) -> Result<Self> {
+        let entries = Entries::with_chunk_size(chunk_size);
+        Self::open_with(latest, storage, entries, true, None, None).await
+    }
+
+    /// Creates a new reader from the given storage, loading the necessary links' files
+    /// into `entries` (from which any entry is removed first).
+    ///
+    /// This allows configuring how the entries are stored, e.g. in chunks (see
+    /// [`Entries::with_chunk_size()`]) or with more capacity reserved to find them faster
+    /// (see [`Entries::with_index_headroom()`]).
+    #[inline]
+    pub async fn open_into(
+        latest: LinkId,
+        storage: Storage,
+        mut entries: Entries<T, S>,
+    ) -> Result<Self> {
+        entries.clear();
+        Self::open_with(latest, storage, entries, true, None, None).await
+    }
+
+    /// Creates a new reader from the given storage, only loading the deltas of all of
+    /// the links from `genesis` (the first link of the chain) to `latest`.
+    ///
+    /// Contrarily to [`open()`][1], this never reads any snapshot, which allows
+    /// rebuilding the entries of a chain whose snapshots are corrupt or missing, as long
+    /// as all of its deltas exist.
+    ///
+    /// Fails with [`Error::DoesNotExist`] if the delta of any of the links is missing
+    /// (including for links whose delta was skipped, see
+    /// [`Writer::with_snapshot_only()`][2]), or with [`Error::Disconnected`] if the first
+    /// link reached by following the chain from `latest` isn't `genesis`.
+    ///
+    /// [1]: Self::open()
+    /// [2]: crate::Writer::with_snapshot_only()
+    #[inline]
+    pub async fn open_delta_only(
+        genesis: LinkId,
+        latest: LinkId,
+        storage: Storage,
+    ) -> Result<Self> {
+        Self::open_with(
+            latest,
+            storage,
+            Entries::default(),
+            false,
+            Some(genesis),
+            None,
+        )
+        .await
+    }
+
+    /// Creates a new reader from the given storage, loading at most `budget` (rounded up
+    /// to `1`) of the latest links' files, starting with `latest`.
+    ///
+    /// If the chain was fully loaded (i.e. if its first link or a link with a snapshot
+    /// was reached within `budget` links), `continue_from` is `None`. Otherwise, the
+    /// reader can already be used to get the entries of the links which have been
+    /// loaded (the entries represented by smaller `u32`s being unavailable until then,
+    /// see [`first_loaded()`][2]), and [`continue_loading()`][1] can be called with
+    /// `continue_from` to load the previous links later on.
+    ///
+    /// This allows loading huge chains in phases (e.g. across requests with a limited
+    /// budget).
+    ///
+    /// [1]: Self::continue_loading()
+    /// [2]: Self::first_loaded()
+    pub async fn open_partial(
+        latest: LinkId,
+        storage: Storage,
+        budget: usize,
+    ) -> Result<Partial<T, S, V>> {
+        let budget = Some(budget.max(1));
+        let reader =
+            Self::open_with(latest, storage, Entries::default(), true, None, budget).await?;
+
+        Ok(Partial {
+            continue_from: reader.resume,
+            reader,
+        })
+    }
+
+    /// Creates a new reader from the given storage, only loading the deltas of at most
+    /// `max_links` (rounded up to `1`) of the latest links, starting with `latest`.
+    ///
+    /// Contrarily to [`open_partial()`][1], this never reads any snapshot, which makes
+    /// it cheap to only use the entries added by the latest links. The entries of the
+    /// previous links are unavailable (e.g. [`get_at()`][2] returns `None` for them),
+    /// and [`first_loaded()`][3] returns the `u32` assigned to the oldest entry which
+    /// was loaded.
+    ///
+    /// Fails with [`Error::DoesNotExist`] if the delta of any of the links is missing
+    /// (including for links whose delta was skipped, see
+    /// [`Writer::with_snapshot_only()`][4]).
+    ///
+    /// [1]: Self::open_partial()
+    /// [2]: Self::get_at()
+    /// [3]: Self::first_loaded()
+    /// [4]: crate::Writer::with_snapshot_only()
+    #[inline]
+    pub async fn open_suffix(latest: LinkId, max_links: usize, storage: Storage) -> Result<Self> {
+        let budget = Some(max_links.max(1));
+        Self::open_with(latest, storage, Entries::default(), false, None, budget).await
+    }
+
+    /// Continues loading the chain of a reader created with [`open_partial()`][1],
+    /// loading at most `budget` (rounded up to `1`) of the links' files, starting with
+    /// `continue_from`.
+    ///
+    /// Returns the ID of the link from which loading should continue next time, or
+    /// `None` once the chain has been fully loaded (in which case calling this again
+    /// does nothing).
+    ///
+    /// Fails with [`Error::InvalidReader`] if loading should continue from another
+    /// link than `continue_from`. If this fails or is cancelled, the reader is left
+    /// exactly as it was.
+    ///
+    /// [1]: Self::open_partial()
+    pub async fn continue_loading(
+        &mut self,
+        continue_from: LinkId,
+        budget: usize,
+    ) -> Result<Option<LinkId>> {
+        let Some(resume) = self.resume else {
+            return Ok(None);
+        };
+
+        if resume != continue_from {
+            return Err(Error::InvalidReader {
+                expected: continue_from,
+                got: resume,
+            });
+        }
+
+        let entries = self.entries.new_like();
+        let budget = Some(budget.max(1));
+        let storage = self.storage.clone();
+        let mut older = Self::open_with(resume, storage, entries, true, None, budget).await?;
+
+        if older.len() != self.start {
+            return Err(Error::Inconsistent {
+                link: resume,
+                reason: "partially loaded chain entry count mismatch",
+            });
+        }
+
+        // The entries which were already loaded are more recent than the ones which were
+        // just loaded, so they need to be moved after them.
+        let entries = std::mem::replace(&mut self.entries, older.entries);
+        let entries = entries.into_iter().map(|(_, entry)| entry).collect();
+        self.entries.extend_unique(entries);
+
+        older.values.append(&mut self.values);
+        self.values = older.values;
+
+        older.links.append(&mut self.links);
+        self.links = older.links;
+
+        self.snapshot = older.snapshot;
+        self.versions.extend(older.versions);
+        self.start = older.start;
+        self.resume = older.resume;
+        self.root = older.root;
+        self.bytes_read += older.bytes_read;
+
+        Ok(self.resume)
+    }
+
+    /// Returns the entry represented by the given `u32`, if there is one, loading it
+    /// from the storage if it hasn't been loaded yet.
+    ///
+    /// Contrarily to [`get_at()`][1], which only ever returns entries which have already
+    /// been loaded, this continues loading the chain of a reader created with
+    /// [`open_partial()`][2] or [`open_suffix()`][3] (see [`continue_loading()`][4]) one
+    /// file at a time until the entry has been loaded. The entries which are loaded
+    /// this way stay loaded.
+    ///
+    /// If this fails or is cancelled, the links which were loaded until then stay
+    /// loaded.
+    ///
+    /// [1]: Self::get_at()
+    /// [2]: Self::open_partial()
+    /// [3]: Self::open_suffix()
+    /// [4]: Self::continue_loading()
+    pub async fn get_or_load_at(&mut self, index: u32) -> Result<Option<&T>> {
+        while index < self.start {
+            let Some(resume) = self.resume else {
+                break;
+            };
+
+            self.continue_loading(resume, 1).await?;
+        }
+
+        Ok(self.get_at(index))
+    }
+
+    /// Creates a new reader from the content of the file of the given kind for the
+    /// `latest` link, which is already in memory (e.g. in a memory-mapped file), without
+    /// any I/O.
+    ///
+    /// The file must contain all of the entries of the chain up to `latest`, i.e. it
+    /// must either be a full snapshot, or the delta of the first link of the chain.
+    /// `storage` is only used to load the links extending `latest` when reloading the
+    /// reader.
+    ///
+    /// Fails if `V` isn't zero-sized, since the values are stored in other files.
+    pub fn open_from_slice(
+        latest: LinkId,
+        kind: Kind,
+        bytes: &[u8],
+        storage: Storage,
+    ) -> Result<Self> {
+        if V::SIZE > 0 {
+            return Err(Error::Unsupported {
+                operation: "open values from slice",
+            });
+        }
+
+        let mut reader = storage.open_slice(bytes);
         let mut entries = Entries::default();
+
+        let (index, previous, version) = match kind {
+            Snapshot => {
+                let footer = complete_now(SFooter::read(&mut reader))?;
+                if footer.base.is_some() {
+                    return Err(Error::Unsupported {
+                        operation: "open incremental snapshot from slice",
+                    });
+                }
+
+                entries.reserve(footer.count as usize);
+                let insert = |entry| {
+                    entries.insert_unique(entry);
+                };
+
+                complete_now(read_snapshot(&storage, latest, reader, &footer, insert))?;
+                (footer.index, footer.previous, footer.version)
+            }
+
+            Delta => {
+                let footer = complete_now(DFooter::read(&mut reader))?;
+                footer.check_size(latest, &reader, T::SIZE)?;
+                if footer.previous.is_some() {
+                    return Err(Error::Unsupported {
+                        operation: "open non-first delta from slice",
+                    });
+                }
+
+                entries.reserve(footer.count as usize);
+                let context = LinkContext::delta(latest, &footer);
+                for _ in 0..footer.count {
+                    let entry = complete_now(T::read_with_context(&mut reader, &context))?;
+                    entries.insert_unique(entry);
+                }
+
+                (footer.index, None, footer.version)
+            }
+
+            Values => {
+                return Err(Error::Unsupported {
+                    operation: "open values file as entries",
+                });
+            }
+        };
+
+        Ok(Self {
+            storage,
+
+            latest,
+            index,
+            previous,
+            root: previous.is_none().then_some(latest),
+            start: 0,
+            resume: None,
+            entries,
+            values: Vec::new(),
+            links: vec![(0, latest)],
+            snapshot: matches!(kind, Snapshot).then_some(latest),
+            versions: BTreeSet::from([version]),
+
+            bytes_read: 0,
+        })
+    }
+
+    /// Reloads the reader so that all of the entries present in the `latest` link can
+    /// be used, opening the chain again if `latest` doesn't extend the latest link which
+    /// has been loaded.
+    ///
+    /// Contrarily to [`reload()`][1], which fails with [`Error::Disconnected`] in that
+    /// case, this allows following a chain which was re-rooted (e.g. by compacting it
+    /// into a new snapshot link) – all of the loaded entries are then replaced by the
+    /// ones of the chain ending at `latest`.
+    ///
+    /// If this fails, the reader is left at the latest link which was fully merged, like
+    /// with [`reload()`][1]. In particular, if opening the chain again fails, the reader
+    /// is left exactly as it was before calling this.
+    ///
+    /// [1]: Self::reload()
+    pub async fn reload_or_reopen(&mut self, latest: LinkId) -> Result<()> {
+        match self.reload(latest).await {
+            Err(Error::Disconnected { .. }) => {}
+            result => return result,
+        }
+
+        let entries = self.entries.new_like();
+        let storage = self.storage.clone();
+        let mut reader = Self::open_with(latest, storage, entries, true, None, None).await?;
+        reader.bytes_read += self.bytes_read;
+
+        *self = reader;
+
+        Ok(())
+    }
+
+    /// Creates a new reader from the given storage, loading the necessary links' files
+    /// into `entries`.
+    ///
+    /// If `snapshots` is `false`, snapshots are ignored and only deltas are loaded. If
+    /// `genesis` is set, the deltas are loaded until reaching the first link of the
+    /// chain, which must be `genesis`.
+    ///
+    /// If `budget` is set, at most `budget` links are loaded, which might leave the
+    /// reader only partially loaded.
+    async fn open_with(
+        latest: LinkId,
+        storage: Storage,
+        mut entries: Entries<T, S>,
+        snapshots: bool,
+        genesis: Option<LinkId>,
+        budget: Option<usize>,
+    ) -> Result<Self> {
+        // Unless they are stored in chunks, the entries are first loaded into a single
+        // `Vec` (see below).
+        let mut loaded = Vec::new();
+        let reserve = |entries: &mut Entries<T, S>, loaded: &mut Vec<T>, additional| {
+            if entries.is_chunked() {
+                entries.reserve(additional);
+            } else {
+                loaded.reserve(additional);
+            }
+        };
+
         let mut deltas = Vec::new();
-        let mut total = 0;
+        let mut bytes_read = 0;
+        let mut links = Vec::new();
 
         let mut next = latest;
-        let mut latest_index = 0;
+        let mut latest_index = ChainIndex::default();
+        let mut latest_previous = None;
+        let mut root = None;
+        let mut snapshot = None;
+        let mut start = 0;
+        let mut resume = None;
+        let mut versions = BTreeSet::new();
 
+        // The footer of the latest link contains the number of entries in the whole chain,
+        // which allows reserving enough capacity for all of them at once, before reading
+        // any of them.
         loop {
             // Snapshot files do not neccessarily exist – they are optional.
             //
             // We load all deltas until we either reach the end of the chain or a snapshot.
-            if let Some(mut reader) = storage.open_maybe(next, Snapshot).await? {
+            if snapshots && let Some(mut reader) = storage.open_maybe(next, Snapshot).await? {
                 let footer = SFooter::read(&mut reader).await?;
-                total += footer.count as usize;
 
                 if next == latest {
                     latest_index = footer.index;
+                    latest_previous = footer.previous;
+                    reserve(&mut entries, &mut loaded, footer.count as usize);
                 }
 
-                entries.reserve(total);
-
-                for _ in 0..footer.count {
-                    let entry = T::read(&mut reader).await?;
-                    entries.insert_unique(entry);
+                if footer.previous.is_none() {
+                    root = Some(next);
                 }
 
+                // Entries stored in chunks must never be stored in a single allocation,
+                // so they are inserted right away.
+                bytes_read += if entries.is_chunked() {
+                    let insert = |entry| {
+                        entries.insert_unique(entry);
+                    };
+
+                    read_snapshot(&storage, next, reader, &footer, insert).await?
+                } else {
+                    let insert = |entry| loaded.push(entry);
+                    read_snapshot(&storage, next, reader, &footer, insert).await?
+                };
+
+                links.push((footer.count, next));
+                snapshot = Some(next);
+                versions.insert(footer.version);
                 break;
             }
 
             // If no snapshot exists for the link, we instead try to load the delta for it.
             let mut reader = storage.open(next, Delta).await?;
             let footer = DFooter::read(&mut reader).await?;
+            footer.check_size(next, &reader, T::SIZE)?;
 
             if next == latest {
                 latest_index = footer.index;
+                latest_previous = footer.previous;
+
+                // Only a part of the entries are loaded when there is a budget.
+                if budget.is_none() {
+                    reserve(&mut entries, &mut loaded, footer.total as usize);
+                }
             }
 
+            // The entries are read one after the other, which can reuse a single request.
+            reader.read_sequentially();
+
             let mut delta = Vec::with_capacity(footer.count as usize);
-            total += footer.count as usize;
+            let retries = storage.retries();
+            let context = LinkContext::delta(next, &footer);
 
             for _ in 0..footer.count {
-                // TODO(MLB): validate that exactly `T::SIZE` bytes were read
-                let entry = T::read(&mut reader).await?;
+                let entry = read_entry(&mut reader, &context, retries).await?;
 
                 delta.push(entry);
             }
 
             deltas.push(delta);
+            links.push((footer.count, next));
+            versions.insert(footer.version);
+            bytes_read += reader.bytes_read();
 
             // Unless this is the last link in the chain we try to load the previous one.
             let Some(previous) = footer.previous else {
+                if let Some(genesis) = genesis
+                    && genesis != next
+                {
+                    return Err(Error::Disconnected {
+                        latest,
+                        expected: genesis,
+                        got: next,
+                    });
+                }
+
+                root = Some(next);
                 break;
             };
 
-            next = previous;
-        }
+            if budget.is_some_and(|budget| links.len() >= budget) {
+                let total = footer.chain_total(&storage).await?;
+                start = total.checked_sub(footer.count).ok_or(Error::Inconsistent {
+                    link: next,
+                    reason: "delta file contains more entries than the chain",
+                })?;
+                resume = Some(previous);
+                break;
+            }
 
-        // `entries` is empty if only read deltas – it should otherwise contain some
-        // entries. If it is empty, then we reserve some capacity. If it isn't it should
-        // already have enough capacity to insert all of the entries in `deltas`.
-        if entries.is_empty() {
-            entries.reserve(total);
+            next = previous;
         }
 
+        // Unless they are stored in chunks, all of the entries are inserted at once, which
+        // allows reusing the allocation reserved for them and building the hash table in
+        // a single pass.
         for delta in deltas.into_iter().rev() {
-            for entry in delta {
-                entries.insert_unique(entry);
+            if entries.is_chunked() {
+                entries.extend_unique(delta);
+            } else {
+                loaded.extend(delta);
             }
         }
 
+        entries.extend_unique(loaded);
+
+        let mut values = Vec::new();
+        if V::SIZE > 0 {
+            bytes_read += read_values(&storage, latest, start, &mut values).await?;
+        }
+
         Ok(Self {
             storage,
 
             latest,
             index: latest_index,
+            previous: latest_previous,
+            root,
+            start,
+            resume,
             entries,
+            values,
+            links: link_starts(start, links).collect(),
+            snapshot,
+            versions,
+
+            bytes_read,
         })
     }
 }
 
-impl<T: Entry, S: BuildHasher> Reader<T, S> {
+impl<T: Entry, S: BuildHasher, V: Entry> Reader<T, S, V> {
     /// Returns the ID of the last link in the chain which has been loaded by this
     /// reader.
     #[inline]
@@ -118,24 +681,211 @@ impl<T: Entry, S: BuildHasher> Reader<T, S> {
     /// Returns the index of the last link in the chain which has been loaded by this
     /// reader.
     #[inline]
-    pub fn index(&self) -> u32 {
+    pub fn current_index(&self) -> ChainIndex {
         self.index
     }
 
+    /// Returns the index of the last link in the chain which has been loaded by this
+    /// reader.
+    #[deprecated = "use `current_index()` instead"]
+    #[inline]
+    pub fn index(&self) -> ChainIndex {
+        self.current_index()
+    }
+
+    /// Returns the ID of the link which the latest link which has been loaded extends,
+    /// or `None` if it is the first link in the chain.
+    #[inline]
+    pub fn head_previous(&self) -> Option<LinkId> {
+        self.previous
+    }
+
+    /// Returns the ID of the first link in the chain, if it is known.
+    ///
+    /// This is `None` if the entries were loaded from the snapshot of a link which
+    /// isn't the first one in the chain, since finding the first link would then
+    /// require reading the footers of all of the previous links.
+    #[inline]
+    pub fn root_id(&self) -> Option<LinkId> {
+        self.root
+    }
+
+    /// Returns the number of bytes which have been read from the storage by this reader
+    /// so far, across [`open()`][1] and all of the links merged by [`reload()`][2]
+    /// (including the links merged before opening the chain again, for
+    /// [`reload_or_reopen()`][3]).
+    ///
+    /// [1]: Self::open()
+    /// [2]: Self::reload()
+    /// [3]: Self::reload_or_reopen()
+    #[inline]
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Returns `true` if the oldest entries which have been loaded were loaded from the
+    /// snapshot of a link, rather than from the deltas of all of the links up to the
+    /// first one in the chain (or up to where a partial reader stopped loading).
+    #[inline]
+    pub fn loaded_from_snapshot(&self) -> bool {
+        self.snapshot.is_some()
+    }
+
+    /// Returns the number of links whose delta has been loaded on top of the snapshot
+    /// from which the oldest entries were loaded (see [`loaded_from_snapshot()`][1]), or
+    /// the number of links which have been loaded if there is no such snapshot.
+    ///
+    /// This grows as the reader is reloaded with new links, and allows detecting when
+    /// a new snapshot should be written (e.g. if it exceeds some threshold).
+    ///
+    /// [1]: Self::loaded_from_snapshot()
+    pub fn deltas_since_snapshot(&self) -> usize {
+        let Some(snapshot) = self.snapshot else {
+            return self.links.len();
+        };
+
+        let since = self.links.iter().rev();
+        since.take_while(|(_, link)| *link != snapshot).count()
+    }
+
+    /// Returns the versions of the storage format which were used to encode the files of
+    /// the links which have been loaded.
+    ///
+    /// This allows detecting chains containing links encoded with an old version of the
+    /// storage format (see [`storage::MIN_VERSION`] and [`storage::VERSION`]), which
+    /// should be rewritten before support for it is dropped. Note that if the entries
+    /// were loaded from a snapshot, the versions of the previous links' files are
+    /// unknown.
+    #[inline]
+    pub fn format_versions(&self) -> BTreeSet<u16> {
+        self.versions.clone()
+    }
+
+    /// Reserves enough capacity to load at least `additional` more entries.
+    ///
+    /// Reloading the reader already reserves enough capacity for all of the entries it
+    /// merges once it has read the links' footers, but a caller which knows how far
+    /// behind the reader is (e.g. from the total number of entries of the latest link)
+    /// can use this to reserve it ahead of time.
+    pub fn reserve(&mut self, additional: usize) {
+        self.entries.reserve(additional);
+        if V::SIZE > 0 {
+            self.values.reserve(additional);
+        }
+    }
+
+    /// Consumes the reader, returning the ID of the latest link which has been loaded,
+    /// along with the entries which have been loaded and the storage containing the
+    /// chain's links.
+    ///
+    /// This allows reusing the loaded entries (e.g. in a custom structure) or the
+    /// storage (e.g. to open another chain) without loading them again. Note that the
+    /// position of each entry is the `u32` which represents it minus
+    /// [`first_loaded()`][1].
+    ///
+    /// [1]: Self::first_loaded()
+    #[inline]
+    pub fn into_parts(self) -> (LinkId, Entries<T, S>, Storage) {
+        (self.latest, self.entries, self.storage)
+    }
+
     /// Reloads the reader so that all of the entries present in the `latest` link can
     /// be used.
+    ///
+    /// The footers of all of the links between the latest link which has been loaded and
+    /// `latest` are read first, and the links are then merged one at a time, starting
+    /// with the oldest one. Each link is either fully merged or not at all, and
+    /// [`latest()`][1] is updated after each one.
+    ///
+    /// This means that if this fails or is cancelled (i.e. the future is dropped), the
+    /// reader is left at the latest link which was fully merged, which is a link between
+    /// the one which was loaded before calling this and `latest`. Calling this again
+    /// then only merges the remaining links. If this fails while reading the footers
+    /// (e.g. with [`Error::Disconnected`]), the reader is left exactly as it was.
+    ///
+    /// [1]: Self::latest()
+    #[inline]
     pub async fn reload(&mut self, latest: LinkId) -> Result<()> {
+        self.reload_from(latest, false, 1).await
+    }
+
+    /// Reloads the reader like [`reload()`][1], reading the entries of up to
+    /// `concurrency` (rounded up to `1`) links' deltas concurrently.
+    ///
+    /// The footers are still read one after the other, since the ID of each link's
+    /// previous link is stored in its footer, but the deltas' entries are then read
+    /// concurrently and merged in order as soon as they are. This can be far faster
+    /// when catching up with many small links stored on high-latency storage. Failing
+    /// or being cancelled leaves the reader like with [`reload()`][1].
+    ///
+    /// [1]: Self::reload()
+    #[inline]
+    pub async fn reload_with_concurrency(
+        &mut self,
+        latest: LinkId,
+        concurrency: usize,
+    ) -> Result<()> {
+        self.reload_from(latest, false, concurrency).await
+    }
+
+    /// Reloads the reader so that all of the entries present in the `latest` link can
+    /// be used, using snapshots when possible.
+    ///
+    /// Contrarily to [`reload()`][1], which loads the deltas of all of the links between
+    /// the latest link which has been loaded and `latest`, this checks whether each of
+    /// those links has a snapshot, and if one does, replaces all of the loaded entries
+    /// with the ones from the snapshot before loading the deltas of the links following
+    /// it. This requires checking whether a snapshot exists for each link, but can be
+    /// far cheaper when the reader is far behind.
+    ///
+    /// Like with [`reload()`][1], if this fails or is cancelled, the reader is left at
+    /// the latest link which was fully merged (the snapshot's link being merged first).
+    ///
+    /// [1]: Self::reload()
+    #[inline]
+    pub async fn reload_with_snapshots(&mut self, latest: LinkId) -> Result<()> {
+        self.reload_from(latest, true, 1).await
+    }
+
+    /// Reloads the reader so that all of the entries present in the `latest` link can
+    /// be used, stopping at the first link with a snapshot if `use_snapshots` is `true`,
+    /// and reading the entries of up to `concurrency` deltas concurrently.
+    async fn reload_from(
+        &mut self,
+        latest: LinkId,
+        use_snapshots: bool,
+        concurrency: usize,
+    ) -> Result<()> {
         let mut deltas = Vec::new();
+        let mut snapshot = None;
         let mut additional = 0;
 
         // TODO(MLB): set a threshold above which we try loading a snapshot (i.e. if there are more
         //            than `N` entries to load or more than `M` deltas)
 
+        // The links are walked back from `latest`, only reading their footers, to find the
+        // links which need to be merged.
         let mut next = latest;
-        let mut latest_index = 0;
-
         while next != self.latest {
-            let mut reader = self.storage.open(next, Delta).await?;
+            // Links whose delta file was skipped can only be loaded from their snapshot.
+            let delta = if use_snapshots {
+                None
+            } else {
+                self.storage.open_maybe(next, Delta).await?
+            };
+
+            if delta.is_none()
+                && let Some(mut reader) = self.storage.open_maybe(next, Snapshot).await?
+            {
+                let footer = SFooter::read(&mut reader).await?;
+                snapshot = Some((next, reader, footer));
+                break;
+            }
+
+            let mut reader = match delta {
+                Some(reader) => reader,
+                None => self.storage.open(next, Delta).await?,
+            };
 
             let footer = DFooter::read(&mut reader).await?;
             let Some(previous) = footer.previous else {
@@ -146,54 +896,242 @@ impl<T: Entry, S: BuildHasher> Reader<T, S> {
                 });
             };
 
-            if next == latest {
-                latest_index = footer.index;
+            additional += footer.count as usize;
+            deltas.push((next, reader, footer));
+            next = previous;
+        }
+
+        // The links are then merged one at a time, starting with the oldest one. Each link's
+        // entries are read before any of them is merged, so that the reader always reflects
+        // the latest link which was fully merged, even if this fails or is cancelled.
+        if let Some((link, reader, footer)) = snapshot {
+            let mut entries = Vec::with_capacity(footer.count as usize);
+            let insert = |entry| entries.push(entry);
+            let bytes_read = read_snapshot(&self.storage, link, reader, &footer, insert).await?;
+
+            // The snapshot contains all of the entries up to its link, which replace the
+            // ones which were loaded until now.
+            self.entries.clear();
+            self.entries.reserve(entries.len() + additional);
+            self.links.clear();
+            self.links.push((0, link));
+            self.snapshot = Some(link);
+            self.versions.clear();
+            self.versions.insert(footer.version);
+            self.resume = None;
+
+            // The values which were loaded don't start with the first entry anymore.
+            if self.start != 0 {
+                self.values.clear();
+                self.start = 0;
             }
 
-            let mut delta = Vec::with_capacity(footer.count as usize);
-            additional += footer.count as usize;
+            for entry in entries {
+                self.entries.insert_unique(entry);
+            }
+
+            if footer.previous.is_none() {
+                self.root = Some(link);
+            }
+
+            self.latest = link;
+            self.index = footer.index;
+            self.previous = footer.previous;
+            self.bytes_read += bytes_read;
+        } else {
+            self.entries.reserve(additional);
+        }
+
+        let retries = self.storage.retries();
+        let read_delta = |(link, mut reader, footer): (LinkId, storage::Reader, DFooter)| async move {
+            footer.check_size(link, &reader, T::SIZE)?;
+            reader.read_sequentially();
 
+            let mut entries = Vec::with_capacity(footer.count as usize);
+            let context = LinkContext::delta(link, &footer);
             for _ in 0..footer.count {
-                // TODO(MLB): validate that exactly `T::SIZE` bytes were read
-                let entry = T::read(&mut reader).await?;
+                let entry = read_entry(&mut reader, &context, retries).await?;
 
-                delta.push(entry);
+                entries.push(entry);
             }
 
-            deltas.push(delta);
-            next = previous;
-        }
+            Ok::<_, Error>((link, reader, footer, entries))
+        };
 
-        self.entries.reserve(additional);
+        // The deltas are read in order (up to `concurrency` at once), so that they can
+        // still be merged one at a time as soon as they are read.
+        let mut deltas = stream::iter(deltas.into_iter().rev())
+            .map(read_delta)
+            .buffered(concurrency.max(1));
 
         // TODO(MLB): allow to optionally "layer" the deltas instead of merging them
-        for delta in deltas.into_iter().rev() {
-            for entry in delta {
+        while let Some((link, reader, footer, entries)) = deltas.try_next().await? {
+            self.links.push((self.len(), link));
+            for entry in entries {
                 self.entries.insert_unique(entry);
             }
+
+            self.latest = link;
+            self.index = footer.index;
+            self.previous = footer.previous;
+            self.versions.insert(footer.version);
+            self.bytes_read += reader.bytes_read();
         }
 
-        self.latest = latest;
-        self.index = latest_index;
+        // The values are only loaded once all of the entries have been merged, and only
+        // for the entries whose value hasn't been loaded yet.
+        let loaded = self.start + self.values.len() as u32;
+        if V::SIZE > 0 && loaded < self.len() {
+            let storage = &self.storage;
+            self.bytes_read += read_values(storage, self.latest, loaded, &mut self.values).await?;
+        }
 
         Ok(())
     }
 
-    /// Returns the number of entries present.
-    #[inline]
-    #[allow(clippy::len_without_is_empty)] // `is_empty` would otherwise always return `false`
-    pub fn len(&self) -> u32 {
-        self.entries.len()
-    }
+    /// Reloads the reader every time a new latest link is received from `heads`,
+    /// yielding the entries which were added to the chain since the previous reload.
+    ///
+    /// Discovering new links is left to the caller (e.g. using notifications sent by
+    /// the storage or by the writer), which avoids having to poll the storage.
+    ///
+    /// If reloading fails, the error is yielded and the reader is left at the latest link
+    /// which was fully merged (see [`reload()`][1]), so the stream can still be used to
+    /// wait for the next link. The entries of the links which were merged are then
+    /// yielded along with the ones of the next successful reload.
+    ///
+    /// [1]: Self::reload()
+    pub fn watch<'a>(
+        &'a mut self,
+        heads: impl Stream<Item = LinkId> + 'a,
+    ) -> impl Stream<Item = Result<NewEntries<T>>> + 'a
+    where
+        T: Clone,
+    {
+        let heads = Box::pin(heads);
+
+        // The `u32` assigned to the first entry which hasn't been yielded yet, if a reload
+        // failed after merging some links.
+        let pending = None;
+
+        stream::unfold(
+            (self, heads, pending),
+            |(reader, mut heads, pending)| async move {
+                let latest = loop {
+                    let latest = heads.next().await?;
+                    if latest != reader.latest {
+                        break latest;
+                    }
+                };
+
+                let start = pending.unwrap_or(reader.len());
+                let result = reader.reload(latest).await.map(|()| {
+                    // The new entries are looked up directly, rather than skipping all of the
+                    // ones which were already loaded.
+                    let entries = (start..reader.len())
+                        .filter_map(|index| reader.get_at(index))
+                        .cloned()
+                        .collect();
+
+                    NewEntries {
+                        latest,
+                        start,
+                        entries,
+                    }
+                });
+
+                let pending = result.is_err().then_some(start);
+                Some((result, (reader, heads, pending)))
+            },
+        )
+    }
+
+    /// Returns the number of entries present.
+    #[inline]
+    #[allow(clippy::len_without_is_empty)] // `is_empty` would otherwise always return `false`
+    pub fn len(&self) -> u32 {
+        self.start + self.entries.len()
+    }
+
+    /// Returns the `u32` assigned to the first entry which has been loaded.
+    ///
+    /// This is always `0` unless the reader was created with [`open_partial()`][1] and
+    /// the chain hasn't been fully loaded yet, in which case the entries represented by
+    /// smaller `u32`s aren't available (e.g. [`get_index_of()`][2] returns `None` for
+    /// them).
+    ///
+    /// [1]: Self::open_partial()
+    /// [2]: Self::get_index_of()
+    #[inline]
+    pub fn first_loaded(&self) -> u32 {
+        self.start
+    }
 
     /// Returns the entry represented by the given `u32`, if there is one.
+    ///
+    /// This returns `None` both if `index` is out of range (i.e. not smaller than
+    /// [`len()`][1]) and if the entry wasn't loaded (i.e. if `index` is smaller than
+    /// [`first_loaded()`][2]). Use [`try_get_at()`][3] to tell them apart.
+    ///
+    /// [1]: Self::len()
+    /// [2]: Self::first_loaded()
+    /// [3]: Self::try_get_at()
     #[inline]
     pub fn get_at(&self, index: u32) -> Option<&T> {
         // TODO(MLB): optionally be lazy and only load when this is called
         // TODO(MLB): if lazy, load the entries in blocks to amortize
-        // TODO(MLB): also, potentially pre-allocate or chunk the `Entries`
+        // TODO(MLB): also, potentially pre-allocate the `Entries`
+
+        self.entries.get_at(index.checked_sub(self.start)?)
+    }
+
+    /// Returns the entry represented by the given `u32`, if there is one, failing if
+    /// the entry exists but wasn't loaded.
+    ///
+    /// Contrarily to [`get_at()`][1], this only returns `Ok(None)` if `index` is out of
+    /// range, and fails with [`Error::Unsupported`] if the entry wasn't loaded (i.e. if
+    /// `index` is smaller than [`first_loaded()`][2], as for readers created with
+    /// [`open_partial()`][3] or [`open_suffix()`][4]), in which case
+    /// [`get_or_load_at()`][5] can load it.
+    ///
+    /// [1]: Self::get_at()
+    /// [2]: Self::first_loaded()
+    /// [3]: Self::open_partial()
+    /// [4]: Self::open_suffix()
+    /// [5]: Self::get_or_load_at()
+    #[inline]
+    pub fn try_get_at(&self, index: u32) -> Result<Option<&T>> {
+        if index < self.start {
+            return Err(Error::Unsupported {
+                operation: "getting an entry which wasn't loaded",
+            });
+        }
 
-        self.entries.get_at(index)
+        Ok(self.get_at(index))
+    }
+
+    /// Loads the entries represented by the `u32`s in `range` from the storage ahead of
+    /// accessing them, so that [`get_at()`][1] doesn't have to wait for them.
+    ///
+    /// All of the entries are loaded by [`open()`][2], so this does nothing, and only
+    /// exists so that the same code can prefetch entries from this reader and from a
+    /// memory-mapped one (see `MmapReader::prefetch()`, with the `mmap` feature).
+    ///
+    /// [1]: Self::get_at()
+    /// [2]: Self::open()
+    #[inline]
+    pub fn prefetch(&self, range: Range<u32>) -> Result<()> {
+        let _ = range;
+        Ok(())
+    }
+
+    /// Returns the value associated to the entry represented by the given `u32`, if
+    /// there is one.
+    ///
+    /// This always returns `None` if `V` is zero-sized.
+    #[inline]
+    pub fn get_value_at(&self, index: u32) -> Option<&V> {
+        self.values.get(index.checked_sub(self.start)? as usize)
     }
 
     /// Returns the `u32` assigned to the given `entry`, if it is present.
@@ -201,12 +1139,1511 @@ impl<T: Entry, S: BuildHasher> Reader<T, S> {
     pub fn get_index_of(&self, entry: &T) -> Option<u32> {
         // NOTE(MLB): if `get_at()` becomes lazy, this cannot or at least it'll require
         //            loading all of the entries
-        self.entries.get_index_of(entry)
+        let index = self.entries.get_index_of(entry)?;
+        Some(self.start + index)
+    }
+
+    /// Returns the `u32` assigned to an entry whose key (as returned by `project`) is
+    /// `key`, if one is present.
+    ///
+    /// This requires the reader to have been opened with the same projection (see
+    /// [`ReaderBuilder::key()`][1]), and always returns `None` otherwise.
+    ///
+    /// [1]: crate::ReaderBuilder::key()
+    #[inline]
+    pub fn get_index_of_by<K: Hash + Eq>(&self, key: &K, project: impl Fn(&T) -> K) -> Option<u32> {
+        let index = self.entries.get_index_of_by(key, project)?;
+        Some(self.start + index)
+    }
+
+    /// Returns the `u32` assigned to the given `entry`, if it is present, searching for
+    /// it in the storage if it wasn't loaded.
+    ///
+    /// Contrarily to [`get_index_of()`][1], this also finds the entries of the links
+    /// which weren't loaded by a reader created with [`open_partial()`][2] or
+    /// [`open_suffix()`][3]. When the entry wasn't loaded, this reads the deltas of
+    /// these links one after the other, starting with the latest one, until it finds
+    /// the entry, and reads the whole snapshot of the first link it reaches without a
+    /// delta. Finding out that an entry isn't present can thus read all of them.
+    ///
+    /// The links which are read aren't loaded (see [`continue_loading()`][4]), so
+    /// searching for the same entry again reads them again.
+    ///
+    /// [1]: Self::get_index_of()
+    /// [2]: Self::open_partial()
+    /// [3]: Self::open_suffix()
+    /// [4]: Self::continue_loading()
+    pub async fn get_index_of_fetch(&mut self, entry: &T) -> Result<Option<u32>> {
+        if let Some(index) = self.get_index_of(entry) {
+            return Ok(Some(index));
+        }
+
+        let retries = self.storage.retries();
+        let mut next = self.resume;
+
+        while let Some(link) = next {
+            let Some(mut reader) = self.storage.open_maybe(link, Delta).await? else {
+                // Snapshots contain all of the entries of the previous links as well.
+                let mut reader = self.storage.open(link, Snapshot).await?;
+                let footer = SFooter::read(&mut reader).await?;
+
+                let mut position = 0;
+                let mut found = None;
+                let insert = |other: T| {
+                    if found.is_none() && other == *entry {
+                        found = Some(position);
+                    }
+
+                    position += 1;
+                };
+
+                self.bytes_read +=
+                    read_snapshot(&self.storage, link, reader, &footer, insert).await?;
+                return Ok(found);
+            };
+
+            let footer = DFooter::read(&mut reader).await?;
+            footer.check_size(link, &reader, T::SIZE)?;
+            reader.read_sequentially();
+
+            let mut found = None;
+            let context = LinkContext::delta(link, &footer);
+            for position in 0..footer.count {
+                let other: T = read_entry(&mut reader, &context, retries).await?;
+                if other == *entry {
+                    found = Some(position);
+                    break;
+                }
+            }
+
+            self.bytes_read += reader.bytes_read();
+            if let Some(position) = found {
+                let total = footer.chain_total(&self.storage).await?;
+                let start = total.checked_sub(footer.count).ok_or(Error::Inconsistent {
+                    link,
+                    reason: "delta file contains more entries than the chain",
+                })?;
+
+                return Ok(Some(start + position));
+            }
+
+            next = footer.previous;
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the `u32` assigned to the given `entry`, along with the ID of the link
+    /// which introduced it, if it is present.
+    ///
+    /// If the entry was loaded from a snapshot, the link to which the snapshot belongs
+    /// is returned, even if the entry was introduced by one of the previous links.
+    pub fn locate(&self, entry: &T) -> Option<(u32, LinkId)> {
+        let index = self.get_index_of(entry)?;
+        let position = self.links.partition_point(|(start, _)| *start <= index);
+        let (_, link) = self.links.get(position.checked_sub(1)?)?;
+
+        Some((index, *link))
+    }
+
+    /// Returns all of the entries which have been loaded as a single slice, where the
+    /// position of each entry is the `u32` which represents it minus
+    /// [`first_loaded()`][2].
+    ///
+    /// This allows processing all of the entries at once (e.g. with vectorized code)
+    /// without checking bounds for each of them. Returns `None` if the reader was
+    /// opened with [`open_chunked()`][1] and the entries don't all fit in a single
+    /// chunk.
+    ///
+    /// [1]: Self::open_chunked()
+    /// [2]: Self::first_loaded()
+    #[inline]
+    pub fn as_slice(&self) -> Option<&[T]> {
+        self.entries.as_slice()
     }
 
     /// Iterates over the entries ordered by the `u32` which represent them.
     #[inline]
     pub fn iter(&self) -> impl ExactSizeIterator<Item = (u32, &T)> {
-        self.entries.iter()
+        let start = self.start;
+        self.entries
+            .iter()
+            .map(move |(index, entry)| (start + index, entry))
+    }
+
+    /// Iterates over the entries which were added by the links extending `base` (up to
+    /// the latest link which has been loaded), ordered by the `u32` which represent
+    /// them.
+    ///
+    /// `base` must be one of the links of the chain, which isn't checked. Unless its
+    /// entries were loaded from its own files, this reads its footer to find how many
+    /// entries it contains.
+    ///
+    /// Fails with [`Error::InvalidReader`] if `base` contains more entries than the
+    /// reader, or if some of the entries added after `base` haven't been loaded (see
+    /// [`first_loaded()`][1]).
+    ///
+    /// [1]: Self::first_loaded()
+    pub async fn iter_since(
+        &self,
+        base: LinkId,
+    ) -> Result<impl ExactSizeIterator<Item = (u32, &T)>> {
+        let total = match self.links.iter().position(|(_, link)| *link == base) {
+            Some(position) => match self.links.get(position + 1) {
+                Some((start, _)) => *start,
+                None => self.len(),
+            },
+
+            None => self.storage.read_total(base).await?.0,
+        };
+
+        let skip = total.checked_sub(self.start);
+        let Some(skip) = skip.filter(|_| total <= self.len()) else {
+            return Err(Error::InvalidReader {
+                expected: base,
+                got: self.latest,
+            });
+        };
+
+        Ok(self.iter().skip(skip as usize))
+    }
+
+    /// Splits the given candidates into those which are present and those which are
+    /// absent, in a single pass.
+    ///
+    /// This allows only writing the entries which are absent with
+    /// [`Writer::write_unique()`][1], which requires entries to be unique.
+    ///
+    /// [1]: crate::Writer::write_unique()
+    pub fn partition_present<'a>(&self, candidates: &'a [T]) -> (Vec<&'a T>, Vec<&'a T>) {
+        candidates
+            .iter()
+            .partition(|entry| self.get_index_of(entry).is_some())
+    }
+
+    /// Compares the entries loaded by the reader with the given entries as sets (i.e.
+    /// ignoring the `u32` assigned to them and the order of `expected`).
+    ///
+    /// This is mostly useful in tests, to assert that a chain contains exactly a given
+    /// set of entries.
+    pub fn diff_set<'a>(&'a self, expected: &'a [T]) -> SetDiff<'a, T> {
+        let missing = expected
+            .iter()
+            .filter(|entry| self.get_index_of(entry).is_none())
+            .collect();
+
+        let expected = expected.iter().collect::<HashSet<_>>();
+        let extra = self
+            .iter()
+            .filter(|(_, entry)| !expected.contains(entry))
+            .collect();
+
+        SetDiff { missing, extra }
+    }
+
+    /// Iterates over the entries loaded by the reader which haven't been loaded by
+    /// `other` (e.g. the reader of another chain), ordered by the `u32` which represent
+    /// them in this reader.
+    ///
+    /// Entries are looked up in `other` with [`get_index_of()`][1], so those which
+    /// `other` didn't load (see [`first_loaded()`][2]) are considered absent from it.
+    ///
+    /// [1]: Self::get_index_of()
+    /// [2]: Self::first_loaded()
+    pub fn difference<'a, S2: BuildHasher + Default, V2: Entry>(
+        &'a self,
+        other: &'a Reader<T, S2, V2>,
+    ) -> impl Iterator<Item = (u32, &'a T)> {
+        self.iter()
+            .filter(|(_, entry)| other.get_index_of(entry).is_none())
+    }
+
+    /// Iterates over the entries loaded by both the reader and `other` (e.g. the reader
+    /// of another chain), ordered by the `u32` which represent them in this reader.
+    ///
+    /// Entries are looked up in `other` like with [`difference()`][1].
+    ///
+    /// [1]: Self::difference()
+    pub fn intersection<'a, S2: BuildHasher + Default, V2: Entry>(
+        &'a self,
+        other: &'a Reader<T, S2, V2>,
+    ) -> impl Iterator<Item = (u32, &'a T)> {
+        self.iter()
+            .filter(|(_, entry)| other.get_index_of(entry).is_some())
+    }
+
+    /// Exports the entries to the given writer, one line per entry, ordered by the
+    /// `u32` which represent them.
+    ///
+    /// Each line is rendered by `render` (e.g. as `index<TAB>entry` for TSV, or as a
+    /// JSON object for JSONL), which must not include the trailing newline.
+    pub async fn export<W, F>(&self, mut writer: W, render: F) -> Result<()>
+    where
+        W: AsyncWrite + Unpin,
+        F: Fn(u32, &T) -> String,
+    {
+        for (index, entry) in self.iter() {
+            let mut line = render(index, entry);
+            line.push('\n');
+
+            writer.write_all(line.as_bytes()).await?;
+        }
+
+        writer.flush().await?;
+
+        Ok(())
+    }
+
+    /// Returns the entry which has been inserted last, along with the `u32` assigned to
+    /// it, if there is one.
+    #[inline]
+    pub fn get_last(&self) -> Option<(u32, &T)> {
+        let index = self.len().checked_sub(1)?;
+        let entry = self.get_at(index)?;
+
+        Some((index, entry))
+    }
+
+    /// Iterates over the last `n` entries which have been inserted, ordered by the
+    /// `u32` which represent them.
+    ///
+    /// If less than `n` entries are present, this iterates over all of them.
+    #[inline]
+    pub fn last_n(&self, n: usize) -> impl ExactSizeIterator<Item = (u32, &T)> {
+        let len = self.entries.len() as usize;
+        self.iter().skip(len.saturating_sub(n))
+    }
+}
+
+/// Loads all of the entries of the chain up to `latest` from the given storage,
+/// appending them to `out` ordered by the `u32` which represent them.
+///
+/// Contrarily to [`Reader::open()`], this doesn't index the entries (and doesn't keep
+/// them in a separate allocation), which makes it the cheapest way of getting all of
+/// them when [`Reader::get_index_of()`] isn't needed. Like it, this loads the deltas
+/// of the latest links up to the first link with a snapshot.
+///
+/// If this fails, `out` is left as it was.
+pub async fn load_entries_into<T: Entry>(
+    latest: LinkId,
+    storage: &Storage,
+    out: &mut Vec<T>,
+) -> Result<()> {
+    let len = out.len();
+    let result = load_into(latest, storage, out).await;
+
+    if result.is_err() {
+        out.truncate(len);
+    }
+
+    result
+}
+
+/// Appends all of the entries of the chain up to `latest` to `out`, like
+/// [`load_entries_into()`] but leaving the entries which were loaded if it fails.
+async fn load_into<T: Entry>(latest: LinkId, storage: &Storage, out: &mut Vec<T>) -> Result<()> {
+    let mut deltas = Vec::new();
+    let mut snapshot = None;
+
+    // The links are walked back from `latest`, only reading their footers, so that their
+    // entries can then be appended starting with the oldest ones.
+    let mut next = Some(latest);
+    while let Some(link) = next {
+        if let Some(mut reader) = storage.open_maybe(link, Snapshot).await? {
+            let footer = SFooter::read(&mut reader).await?;
+            snapshot = Some((link, reader, footer));
+            break;
+        }
+
+        let mut reader = storage.open(link, Delta).await?;
+        let footer = DFooter::read(&mut reader).await?;
+        footer.check_size(link, &reader, T::SIZE)?;
+
+        next = footer.previous;
+        deltas.push((link, reader, footer));
+    }
+
+    let additional = match (&snapshot, deltas.first()) {
+        (Some((_, _, footer)), None) => footer.count,
+        (_, Some((_, _, footer))) => footer.total,
+        (None, None) => 0,
+    };
+
+    out.reserve(additional as usize);
+
+    if let Some((link, reader, footer)) = snapshot {
+        let insert = |entry| out.push(entry);
+        read_snapshot(storage, link, reader, &footer, insert).await?;
+    }
+
+    let retries = storage.retries();
+    for (link, mut reader, footer) in deltas.into_iter().rev() {
+        reader.read_sequentially();
+
+        let context = LinkContext::delta(link, &footer);
+        for _ in 0..footer.count {
+            let entry = read_entry(&mut reader, &context, retries).await?;
+            out.push(entry);
+        }
+    }
+
+    Ok(())
+}
+
+/// How the entries stored in the links' files are decoded.
+pub(crate) trait Decode {
+    /// The type of the decoded entries.
+    type Entry;
+
+    /// Decodes an entry from the given reader, which is reading a file of the link
+    /// described by `context`.
+    fn decode(
+        &self,
+        reader: &mut storage::Reader,
+        context: &LinkContext,
+    ) -> impl Future<Output = Result<Self::Entry>> + Send;
+}
+
+/// Reads an entry from `reader` using its [`Entry`] implementation, retrying according
+/// to `retries` if reading it fails transiently.
+#[inline]
+pub(crate) async fn read_entry<T: Entry>(
+    reader: &mut storage::Reader,
+    context: &LinkContext,
+    retries: Option<Retries>,
+) -> Result<T> {
+    decode_retrying(reader, &Typed(PhantomData), context, retries).await
+}
+
+/// Decodes an entry from `reader` with `decode`, retrying according to `retries` if
+/// reading it fails transiently.
+async fn decode_retrying<D: Decode>(
+    reader: &mut storage::Reader,
+    decode: &D,
+    context: &LinkContext,
+    retries: Option<Retries>,
+) -> Result<D::Entry> {
+    let Some(Retries { max, backoff }) = retries else {
+        return decode.decode(reader, context).await;
+    };
+
+    let offset = reader.seek(SeekFrom::Current(0))?;
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+
+        match decode.decode(reader, context).await {
+            Ok(entry) => return Ok(entry),
+            Err(error) if !error.is_transient() => return Err(error),
+            Err(error) if attempts > max => {
+                return Err(Error::EntryRead {
+                    link: context.link,
+                    offset,
+                    attempts,
+                    error: Box::new(error),
+                });
+            }
+
+            Err(_) => {
+                Delay::new(backoff.saturating_mul(1 << (attempts - 1).min(16))).await;
+                reader.seek(SeekFrom::Start(offset as u64))?;
+            }
+        }
+    }
+}
+
+/// Decodes entries using their [`Entry`] implementation.
+struct Typed<T>(PhantomData<fn() -> T>);
+
+impl<T: Entry + Clone, S: BuildHasher, V: Entry> GetEntry<T> for Reader<T, S, V> {
+    #[inline]
+    fn get_cow_at(&self, index: u32) -> Result<Option<Cow<'_, T>>> {
+        Ok(self.get_at(index).map(Cow::Borrowed))
+    }
+}
+
+impl<T: Entry> Decode for Typed<T> {
+    type Entry = T;
+
+    #[inline]
+    fn decode(
+        &self,
+        reader: &mut storage::Reader,
+        context: &LinkContext,
+    ) -> impl Future<Output = Result<T>> + Send {
+        T::read_with_context(reader, context)
+    }
+}
+
+/// Reads the entries of the snapshot of `link` being read by `reader`, whose `footer`
+/// has already been read, passing them to `insert` ordered by the `u32` which represent
+/// them.
+///
+/// If the snapshot is incremental, this first reads the entries of its base, and if it
+/// is appended, the entries of the previous link's snapshot stored at its start.
+///
+/// Returns the number of bytes which were read.
+#[inline]
+pub(crate) async fn read_snapshot<T: Entry>(
+    storage: &Storage,
+    link: LinkId,
+    reader: storage::Reader,
+    footer: &SFooter,
+    insert: impl FnMut(T),
+) -> Result<u64> {
+    let decode = Typed(PhantomData);
+    read_snapshot_with(storage, link, reader, footer, &decode, insert).await
+}
+
+/// Reads the entries of the snapshot of `link` like [`read_snapshot()`], decoding them
+/// with `decode`.
+pub(crate) async fn read_snapshot_with<D: Decode>(
+    storage: &Storage,
+    link: LinkId,
+    mut reader: storage::Reader,
+    footer: &SFooter,
+    decode: &D,
+    mut insert: impl FnMut(D::Entry),
+) -> Result<u64> {
+    let mut bytes_read = 0;
+    let retries = storage.retries();
+
+    // The snapshots containing the entries which precede the ones stored in this one are
+    // found from the latest to the oldest, but need to be read in the opposite order.
+    let mut older = Vec::new();
+    let mut next = read_older(storage, link, &reader, footer).await?;
+
+    while let Some((link, reader, footer)) = next {
+        next = read_older(storage, link, &reader, &footer).await?;
+        older.push((link, reader, footer));
+    }
+
+    for (link, mut reader, footer) in older.into_iter().rev() {
+        read_stored(link, &mut reader, &footer, decode, retries, &mut insert).await?;
+        bytes_read += reader.bytes_read();
+    }
+
+    read_stored(link, &mut reader, footer, decode, retries, &mut insert).await?;
+
+    Ok(bytes_read + reader.bytes_read())
+}
+
+/// Opens the snapshot containing the entries which precede the ones stored in the
+/// snapshot of `link` being read by `reader`, returning the ID of the link it belongs
+/// to, a reader for it and its footer, if there is one.
+///
+/// This is the previous link's snapshot stored at the start of appended snapshots, or
+/// the base of incremental snapshots.
+async fn read_older(
+    storage: &Storage,
+    link: LinkId,
+    reader: &storage::Reader,
+    footer: &SFooter,
+) -> Result<Option<(LinkId, storage::Reader, SFooter)>> {
+    // Appended snapshots start with the previous link's snapshot, whose footer ends
+    // right where the entries stored in this one start.
+    if footer.prefix > 0 {
+        let mut reader = reader.prefix(footer.prefix as usize);
+        let prefix_footer = SFooter::read(&mut reader).await?;
+
+        if prefix_footer.count != footer.prefix_count {
+            return Err(Error::Inconsistent {
+                link,
+                reason: "appended snapshot prefix count mismatch",
+            });
+        }
+
+        let previous = footer.previous.unwrap_or(link);
+        return Ok(Some((previous, reader, prefix_footer)));
+    }
+
+    // Incremental snapshots only contain the entries which aren't present in their
+    // base's (full) snapshot.
+    let Some(base) = footer.base else {
+        return Ok(None);
+    };
+
+    let mut reader = storage.open(base, Snapshot).await?;
+    let base_footer = SFooter::read(&mut reader).await?;
+
+    if base_footer.base.is_some() || base_footer.count != footer.base_count {
+        return Err(Error::InvalidBase { link, base });
+    }
+
+    Ok(Some((base, reader, base_footer)))
+}
+
+/// Reads the entries stored in the snapshot of `link` being read by `reader` (i.e.
+/// excluding the ones stored in its base), passing them to `insert` ordered by the
+/// `u32` which represent them.
+///
+/// Fails with [`Error::Inconsistent`] if the snapshot doesn't contain exactly the
+/// number of entries stored in its footer.
+async fn read_stored<D: Decode>(
+    link: LinkId,
+    reader: &mut storage::Reader,
+    footer: &SFooter,
+    decode: &D,
+    retries: Option<Retries>,
+    insert: &mut impl FnMut(D::Entry),
+) -> Result<()> {
+    // Reading past the footer means that the snapshot contains less entries than its
+    // footer says.
+    let mismatch = |error| match error {
+        Error::FileSize { .. } => Error::Inconsistent {
+            link,
+            reason: "snapshot entry count mismatch",
+        },
+
+        error => error,
+    };
+
+    // The entries (and the positions of the entries of sorted snapshots) are read one
+    // after the other, which can reuse a single request.
+    reader.read_sequentially();
+
+    let count = footer
+        .count
+        .saturating_sub(footer.base_count)
+        .saturating_sub(footer.prefix_count);
+    let context = LinkContext::snapshot(link, footer);
+    if footer.sorted {
+        // Sorted snapshots store their entries before the position of each of them, so
+        // all of them need to be read before any can be inserted.
+        let mut entries = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let entry = decode_retrying(reader, decode, &context, retries).await;
+            entries.push(Some(entry.map_err(mismatch)?));
+        }
+
+        for _ in 0..count {
+            let position = reader.read_u32().await.map_err(mismatch)? as usize;
+            let Some(entry) = entries.get_mut(position).and_then(Option::take) else {
+                return Err(Error::InvalidPermutation { link });
+            };
+
+            insert(entry);
+        }
+    } else {
+        for _ in 0..count {
+            let entry = decode_retrying(reader, decode, &context, retries).await;
+            insert(entry.map_err(mismatch)?);
+        }
+    }
+
+    // Bytes remaining before the footer mean that the snapshot contains more entries
+    // than its footer says.
+    if reader.remaining() != 0 {
+        return Err(Error::Inconsistent {
+            link,
+            reason: "snapshot entry count mismatch",
+        });
+    }
+
+    Ok(())
+}
+
+/// Reads the values of the entries of the chain up to `latest` into `values`, starting
+/// with the value of the entry represented by `start`, and returns the number of bytes
+/// which were read.
+///
+/// The values files of the links are read from `latest` backward until reaching the
+/// link which introduced the entry represented by `start`, whether or not the links
+/// have a snapshot.
+async fn read_values<V: Entry>(
+    storage: &Storage,
+    latest: LinkId,
+    start: u32,
+    values: &mut Vec<V>,
+) -> Result<u64> {
+    let mut files = Vec::new();
+    let mut next = Some(latest);
+
+    while let Some(link) = next {
+        let mut reader = storage.open(link, Values).await?;
+        let footer = DFooter::read_values(&mut reader).await?;
+        footer.check_size(link, &reader, V::SIZE)?;
+
+        let Some(first) = footer.chain_total(storage).await?.checked_sub(footer.count) else {
+            return Err(Error::Inconsistent {
+                link,
+                reason: "values file contains more values than the chain contains entries",
+            });
+        };
+
+        next = footer.previous.filter(|_| first > start);
+        files.push((link, reader, footer, first));
+    }
+
+    let retries = storage.retries();
+    let mut bytes_read = 0;
+    for (link, mut reader, footer, first) in files.into_iter().rev() {
+        // The values of the entries before `start` were already loaded.
+        let skip = start.saturating_sub(first).min(footer.count);
+        reader.seek(SeekFrom::Start(skip as u64 * V::SIZE as u64))?;
+
+        values.reserve((footer.count - skip) as usize);
+        let context = LinkContext::delta(link, &footer);
+        for _ in skip..footer.count {
+            values.push(read_entry(&mut reader, &context, retries).await?);
+        }
+
+        bytes_read += reader.bytes_read();
+    }
+
+    Ok(bytes_read)
+}
+
+/// Converts the given links, along with the number of entries each of them introduced,
+/// ordered from the latest to the oldest, into the links along with the `u32` assigned
+/// to the first entry they introduced, ordered from the oldest to the latest.
+fn link_starts(mut start: u32, links: Vec<(u32, LinkId)>) -> impl Iterator<Item = (u32, LinkId)> {
+    links.into_iter().rev().map(move |(count, link)| {
+        let link_start = start;
+        start += count;
+
+        (link_start, link)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{pin::pin, time::Duration};
+
+    use futures::future::{Either, select};
+
+    use crate::{
+        Writer,
+        tests::{U64, block_on, chain, extend, legacy_chain, memory, probed, snapshot, write_file},
+    };
+
+    use super::*;
+
+    #[test]
+    fn failed_reload_keeps_fully_merged_links() -> Result<()> {
+        block_on(async {
+            let (storage, probe) = probed();
+            let ids = chain(&storage, [0..2, 2..4, 4..6, 6..8]).await?;
+            let mut reader = Reader::<U64>::open(ids[0], storage.clone()).await?;
+
+            // The footers of all of the links can be read, but not the entries of the
+            // second of the three links to merge.
+            probe.fail_reads(Some((ids[2], Delta)));
+            assert!(matches!(
+                reader.reload(ids[3]).await,
+                Err(Error::Storage(_))
+            ));
+
+            // The first link was fully merged, and no entry of the other links was.
+            assert_eq!(reader.latest(), ids[1]);
+            assert_eq!(reader.len(), 4);
+            assert_eq!(reader.get_at(3), Some(&U64(3)));
+            assert_eq!(reader.get_index_of(&U64(4)), None);
+
+            probe.fail_reads(None);
+            reader.reload(ids[3]).await?;
+
+            assert_eq!(reader.latest(), ids[3]);
+            assert_eq!(reader.len(), 8);
+            assert_eq!(reader.get_index_of(&U64(4)), Some(4));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn last_entries() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let ids = chain(&storage, [0..3, 3..5]).await?;
+
+            let reader = Reader::<U64>::open(ids[1], storage.clone()).await?;
+            assert_eq!(reader.get_last(), Some((4, &U64(4))));
+            assert_eq!(
+                reader.last_n(2).collect::<Vec<_>>(),
+                [(3, &U64(3)), (4, &U64(4))]
+            );
+            assert_eq!(reader.last_n(10).len(), 5);
+            assert_eq!(reader.last_n(0).len(), 0);
+
+            // Only the entries which have been loaded are iterated over.
+            let Partial { reader, .. } = Reader::<U64>::open_partial(ids[1], storage, 1).await?;
+            assert_eq!(reader.get_last(), Some((4, &U64(4))));
+            assert_eq!(
+                reader.last_n(10).collect::<Vec<_>>(),
+                [(3, &U64(3)), (4, &U64(4))]
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn chain_index() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let ids = chain(&storage, [0..1, 1..2, 2..3]).await?;
+
+            for (position, &id) in ids.iter().enumerate() {
+                let footer = storage.read_footer(id, Delta).await?;
+                assert_eq!(footer.index, ChainIndex::new(position as u32));
+            }
+
+            let reader = Reader::<U64>::open(ids[2], storage).await?;
+            let index = reader.current_index();
+            assert_eq!((index.get(), index.to_string()), (2, "2".to_string()));
+            assert!(ChainIndex::default() < index);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn watch() -> Result<()> {
+        block_on(async {
+            let (storage, probe) = probed();
+            let ids = chain(&storage, [0..2, 2..4, 4..6, 6..8]).await?;
+            let mut reader = Reader::<U64>::open(ids[0], storage).await?;
+
+            // Heads which were already loaded are skipped, and reading the last link's
+            // entries fails when it is first received.
+            let failing = [None, None, Some((ids[3], Delta)), None];
+            let heads = stream::iter([ids[1], ids[1], ids[3], ids[3]].into_iter().zip(failing));
+            let heads = heads.map(|(head, failing)| {
+                probe.fail_reads(failing);
+                head
+            });
+
+            let mut watch = pin!(reader.watch(heads));
+
+            let new = watch.next().await.unwrap()?;
+            assert_eq!((new.latest, new.start), (ids[1], 2));
+            assert_eq!(new.entries, [U64(2), U64(3)]);
+
+            assert!(watch.next().await.unwrap().is_err());
+
+            // The entries of the link which was merged before failing are yielded with the
+            // next ones.
+            let new = watch.next().await.unwrap()?;
+            assert_eq!((new.latest, new.start), (ids[3], 4));
+            assert_eq!(new.entries, [U64(4), U64(5), U64(6), U64(7)]);
+
+            assert!(watch.next().await.is_none());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn mixed_versions() -> Result<()> {
+        block_on(async {
+            let storage = memory().with_legacy_format();
+            let links = legacy_chain(&storage, &[2, 1]).await?;
+
+            let mut reader = Reader::<U64>::open(links[1], storage.clone()).await?;
+            assert_eq!(reader.format_versions(), BTreeSet::from([0]));
+
+            let mut writer = Writer::<U64>::create(Some(links[1]), storage.clone()).await?;
+            writer.with_snapshot_from(&reader).await?;
+            writer.write_unique(U64(3)).await?;
+            let snapshot = writer.finish().await?;
+
+            let mut writer = Writer::<U64>::create(Some(snapshot), storage.clone()).await?;
+            writer.write_unique(U64(4)).await?;
+            let latest = writer.finish().await?;
+
+            // Each file is decoded according to its own version.
+
+            reader.reload(latest).await?;
+            assert_eq!(reader.format_versions(), BTreeSet::from([0, 1]));
+
+            let entries = reader.iter().map(|(_, entry)| entry.0).collect::<Vec<_>>();
+            assert_eq!(entries, [0, 1, 2, 3, 4]);
+
+            let reader = Reader::<U64>::open(latest, storage).await?;
+            assert_eq!(reader.format_versions(), BTreeSet::from([1]));
+            assert_eq!(reader.len(), 5);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn locate() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..4]).await?;
+
+            let previous = Reader::<U64>::open(links[1], storage.clone()).await?;
+            let mut writer = Writer::<U64>::create(Some(links[1]), storage.clone()).await?;
+            writer.with_snapshot_from(&previous).await?;
+            writer.write_unique(U64(4)).await?;
+            let snapshot = writer.finish().await?;
+
+            let mut writer = Writer::<U64>::create(Some(snapshot), storage.clone()).await?;
+            writer.write_unique(U64(5)).await?;
+            writer.write_unique(U64(6)).await?;
+            let latest = writer.finish().await?;
+
+            let mut reader = Reader::<U64>::open(links[0], storage).await?;
+            reader.reload(links[1]).await?;
+            assert_eq!(reader.locate(&U64(1)), Some((1, links[0])));
+            assert_eq!(reader.locate(&U64(2)), Some((2, links[1])));
+            assert_eq!(reader.locate(&U64(4)), None);
+
+            // The entries loaded from a snapshot are attributed to the snapshot's link.
+            reader.reload_with_snapshots(latest).await?;
+            assert_eq!(reader.locate(&U64(1)), Some((1, snapshot)));
+            assert_eq!(reader.locate(&U64(4)), Some((4, snapshot)));
+            assert_eq!(reader.locate(&U64(6)), Some((6, latest)));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn diff_set() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..4]).await?;
+            let reader = Reader::<U64>::open(links[1], storage).await?;
+
+            assert!(
+                reader
+                    .diff_set(&[U64(3), U64(1), U64(2), U64(0)])
+                    .is_empty()
+            );
+
+            let diff = reader.diff_set(&[U64(5), U64(0), U64(2), U64(4)]);
+            assert!(!diff.is_empty());
+            assert_eq!(diff.missing, [&U64(5), &U64(4)]);
+            assert_eq!(diff.extra, [(1, &U64(1)), (3, &U64(3))]);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn partition_present() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, Some(0..3)).await?;
+            let reader = Reader::<U64>::open(links[0], storage).await?;
+
+            let candidates = [U64(4), U64(2), U64(0), U64(3)];
+            let (present, absent) = reader.partition_present(&candidates);
+            assert_eq!(present, [&U64(2), &U64(0)]);
+            assert_eq!(absent, [&U64(4), &U64(3)]);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn export() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..3]).await?;
+            let reader = Reader::<U64>::open(links[1], storage).await?;
+
+            let mut exported = Vec::new();
+            let render = |index, entry: &U64| format!("{index}\t{}", entry.0 * 10);
+            reader.export(&mut exported, render).await?;
+            assert_eq!(exported, b"0\t0\n1\t10\n2\t20\n");
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn reload_with_snapshots() -> Result<()> {
+        block_on(async {
+            let (storage, probe) = probed();
+            let links = chain(&storage, [0..2, 2..3]).await?;
+            let snapshot = snapshot(&storage, links[1], 3..4).await?;
+            let latest = extend(&storage, Some(snapshot), Some(4..6)).await?[0];
+
+            // The links preceding the snapshot aren't read at all.
+            probe.fail_reads(Some((links[1], Delta)));
+
+            let mut reader = Reader::<U64>::open(links[0], storage.clone()).await?;
+            assert!(reader.reload(latest).await.is_err());
+
+            let mut reader = Reader::<U64>::open(links[0], storage).await?;
+
+            reader.reload_with_snapshots(latest).await?;
+            assert_eq!(reader.latest(), latest);
+            assert_eq!(reader.len(), 6);
+            assert_eq!(reader.get_index_of(&U64(2)), Some(2));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn open_chunked() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..3, 3..5]).await?;
+            let mut reader = Reader::<U64>::open_chunked(links[0], storage, 2).await?;
+
+            // Reloading keeps storing the entries in chunks.
+            reader.reload(links[1]).await?;
+            assert_eq!(reader.as_slice(), None);
+            assert_eq!(reader.get_at(4), Some(&U64(4)));
+            assert_eq!(reader.get_index_of(&U64(3)), Some(3));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn reload_or_reopen() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..4]).await?;
+            let compacted = chain(&storage, [0..4, 4..6]).await?;
+
+            let mut reader = Reader::<U64>::open(links[0], storage).await?;
+            reader.reload_or_reopen(links[1]).await?;
+            assert_eq!((reader.latest(), reader.len()), (links[1], 4));
+
+            // The compacted chain doesn't extend the latest link which was loaded.
+            let error = reader.reload(compacted[1]).await.unwrap_err();
+            assert!(matches!(error, Error::Disconnected { .. }));
+            assert_eq!(reader.latest(), links[1]);
+
+            reader.reload_or_reopen(compacted[1]).await?;
+            assert_eq!((reader.latest(), reader.len()), (compacted[1], 6));
+            assert_eq!(reader.root_id(), Some(compacted[0]));
+            assert_eq!(reader.get_index_of(&U64(5)), Some(5));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn snapshot_entry_count_mismatch() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+
+            // The footers say that the snapshots contain more or less entries than they do.
+            for (count, stored) in [(3, 2), (1, 2)] {
+                let footer = SFooter {
+                    previous: None,
+                    index: ChainIndex::default(),
+                    count,
+                    base: None,
+                    base_count: 0,
+                    sorted: false,
+                    checksum_block: 0,
+                    prefix: 0,
+                    prefix_count: 0,
+                    version: storage::VERSION,
+                };
+
+                let mut content = vec![0; stored * U64::SIZE];
+                content.extend(footer.to_bytes()?);
+
+                let id = LinkId::random();
+                write_file(&storage, id, Snapshot, &content).await?;
+
+                let result = Reader::<U64>::open(id, storage.clone()).await;
+                assert!(matches!(
+                    result,
+                    Err(Error::Inconsistent {
+                        reason: "snapshot entry count mismatch",
+                        ..
+                    })
+                ));
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn open_from_slice() -> Result<()> {
+        block_on(async {
+            assert_eq!(U64::read_from_slice(&5u64.to_be_bytes())?, U64(5));
+            assert!(matches!(
+                U64::read_from_slice(&[0; 4]),
+                Err(Error::EntrySize {
+                    expected: 8,
+                    got: 4
+                })
+            ));
+
+            let storage = memory();
+            let footer = DFooter {
+                previous: None,
+                index: ChainIndex::default(),
+                total: 2,
+                count: 2,
+                version: storage::VERSION,
+            };
+
+            let mut content = [0u64, 1].map(u64::to_be_bytes).concat();
+            content.extend(footer.to_bytes()?);
+
+            let first = LinkId::random();
+            write_file(&storage, first, Delta, &content).await?;
+            let latest = extend(&storage, Some(first), Some(2..3)).await?[0];
+
+            let mut reader =
+                Reader::<U64>::open_from_slice(first, Delta, &content, storage.clone())?;
+            assert_eq!(reader.get_index_of(&U64(1)), Some(1));
+            assert_eq!(reader.bytes_read(), 0);
+
+            // The links extending the file's link are loaded from the storage.
+            reader.reload(latest).await?;
+            assert_eq!(reader.get_at(2), Some(&U64(2)));
+
+            let result = Reader::<U64>::open_from_slice(latest, Values, &content, storage);
+            assert!(matches!(result, Err(Error::Unsupported { .. })));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn root_and_head_previous() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..1, 1..2, 2..3]).await?;
+
+            let mut reader = Reader::<U64>::open(links[0], storage.clone()).await?;
+            assert_eq!(
+                (reader.root_id(), reader.head_previous()),
+                (Some(links[0]), None)
+            );
+
+            reader.reload(links[2]).await?;
+            assert_eq!(reader.root_id(), Some(links[0]));
+            assert_eq!(reader.head_previous(), Some(links[1]));
+
+            // The first link isn't known when loading the snapshot of another link.
+            let snapshot = snapshot(&storage, links[2], 3..4).await?;
+            let reader = Reader::<U64>::open(snapshot, storage).await?;
+            assert_eq!(
+                (reader.root_id(), reader.head_previous()),
+                (None, Some(links[2]))
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    #[allow(deprecated)] // `index()` is still supported until it is removed
+    fn current_index() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..5]).await?;
+
+            let reader = Reader::<U64>::open(links[1], storage).await?;
+            assert_eq!(reader.current_index(), ChainIndex::new(1));
+            assert_eq!(reader.index(), reader.current_index());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn into_parts() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..5]).await?;
+
+            let reader = Reader::<U64>::open(links[1], storage).await?;
+            let (latest, entries, storage) = reader.into_parts();
+            assert_eq!(latest, links[1]);
+            assert_eq!(entries.len(), 5);
+            assert_eq!(entries.get_index_of(&U64(3)), Some(3));
+
+            // The storage can be used to open the chain again.
+            let reader = Reader::<U64>::open(links[0], storage).await?;
+            assert_eq!(reader.len(), 2);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn cancelled_reload_keeps_fully_merged_links() -> Result<()> {
+        block_on(async {
+            let (storage, probe) = probed();
+            let ids = chain(&storage, [0..2, 2..4, 4..6, 6..8]).await?;
+            let mut reader = Reader::<U64>::open(ids[0], storage).await?;
+
+            // Reading the entries of the second of the three links to merge never
+            // completes, and the reload is cancelled while waiting for them.
+            probe.stall_reads(Some((ids[2], Delta)));
+            {
+                let reload = pin!(reader.reload(ids[3]));
+                let timeout = Delay::new(Duration::from_millis(10));
+                assert!(matches!(select(reload, timeout).await, Either::Right(_)));
+            }
+
+            assert_eq!(reader.latest(), ids[1]);
+            assert_eq!(reader.len(), 4);
+
+            // Only the remaining links are merged when reloading again.
+            probe.stall_reads(None);
+            reader.reload(ids[3]).await?;
+            assert_eq!(reader.latest(), ids[3]);
+            assert_eq!(reader.get_index_of(&U64(7)), Some(7));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn open_delta_only() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, Some(0..2)).await?;
+            let snapshot = snapshot(&storage, links[0], 2..3).await?;
+            let latest = extend(&storage, Some(snapshot), Some(3..4)).await?[0];
+
+            // The snapshot can't be read anymore, but the deltas are enough.
+            write_file(&storage, snapshot, Snapshot, b"garbage").await?;
+            assert!(Reader::<U64>::open(latest, storage.clone()).await.is_err());
+
+            let reader = Reader::<U64>::open_delta_only(links[0], latest, storage.clone()).await?;
+            assert_eq!(reader.len(), 4);
+            assert_eq!(reader.root_id(), Some(links[0]));
+            assert_eq!(reader.get_index_of(&U64(3)), Some(3));
+
+            assert!(matches!(
+                Reader::<U64>::open_delta_only(snapshot, latest, storage).await,
+                Err(Error::Disconnected { expected, got, .. })
+                    if expected == snapshot && got == links[0]
+            ));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn reserve() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let mut links = Vec::new();
+            for entries in [0..1, 1..50, 50..60] {
+                let mut writer =
+                    Writer::<U64>::create(links.last().copied(), storage.clone()).await?;
+                for entry in entries {
+                    writer
+                        .write_unique_with_value(U64(entry), &U64(entry * 10))
+                        .await?;
+                }
+
+                links.push(writer.finish().await?);
+            }
+
+            let mut reader = Reader::<U64, RandomState, U64>::open(links[0], storage).await?;
+            reader.reserve(100);
+            let capacity = (reader.entries.capacity(), reader.values.capacity());
+            assert!(capacity.0 >= 101 && capacity.1 >= 101);
+
+            // The reserved capacity is enough to merge the new links without reallocating
+            // the entries or the values.
+            reader.reload(links[2]).await?;
+            assert_eq!(reader.get_index_of(&U64(59)), Some(59));
+            assert_eq!(reader.get_value_at(59), Some(&U64(590)));
+            assert_eq!(
+                (reader.entries.capacity(), reader.values.capacity()),
+                capacity
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn deltas_since_snapshot() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..1, 1..2]).await?;
+            let snapshot = snapshot(&storage, links[1], 2..3).await?;
+            let latest = extend(&storage, Some(snapshot), [3..4, 4..5]).await?;
+
+            let mut reader = Reader::<U64>::open(links[1], storage.clone()).await?;
+            assert!(!reader.loaded_from_snapshot());
+            assert_eq!(reader.deltas_since_snapshot(), 2);
+
+            reader.reload_with_snapshots(latest[0]).await?;
+            assert!(reader.loaded_from_snapshot());
+            assert_eq!(reader.deltas_since_snapshot(), 1);
+
+            reader.reload(latest[1]).await?;
+            assert_eq!(reader.deltas_since_snapshot(), 2);
+
+            let reader = Reader::<U64>::open(snapshot, storage).await?;
+            assert!(reader.loaded_from_snapshot());
+            assert_eq!(reader.deltas_since_snapshot(), 0);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn open_suffix() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..4]).await?;
+            let snapshot = snapshot(&storage, links[1], 4..6).await?;
+            let latest = extend(&storage, Some(snapshot), Some(6..8)).await?[0];
+
+            // Only the deltas are loaded, even if the snapshot could have been used.
+            let reader = Reader::<U64>::open_suffix(latest, 2, storage.clone()).await?;
+            assert!(!reader.loaded_from_snapshot());
+            assert_eq!(reader.first_loaded(), 4);
+            assert_eq!(reader.get_at(3), None);
+            assert_eq!(reader.get_at(4), Some(&U64(4)));
+            assert_eq!(reader.get_index_of(&U64(7)), Some(7));
+            assert_eq!(reader.get_index_of(&U64(0)), None);
+
+            let reader = Reader::<U64>::open_suffix(latest, 0, storage).await?;
+            assert_eq!(reader.first_loaded(), 6);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn get_or_load_at() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..4, 4..6]).await?;
+
+            let mut reader = Reader::<U64>::open_suffix(links[2], 1, storage).await?;
+            assert_eq!(reader.get_or_load_at(5).await?, Some(&U64(5)));
+            assert_eq!(reader.get_or_load_at(6).await?, None);
+            assert_eq!(reader.first_loaded(), 4);
+
+            // Only the links up to the one containing the entry are loaded.
+            assert_eq!(reader.get_or_load_at(3).await?, Some(&U64(3)));
+            assert_eq!(reader.first_loaded(), 2);
+            assert_eq!(reader.get_or_load_at(0).await?, Some(&U64(0)));
+            assert_eq!(reader.first_loaded(), 0);
+            assert!(reader.iter().map(|(_, entry)| entry.0).eq(0..6));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn prefetch() -> Result<()> {
+        block_on(async {
+            let (storage, probe) = probed();
+            let links = chain(&storage, [0..2, 2..4]).await?;
+            let reader = Reader::<U64>::open(links[1], storage).await?;
+            let bytes_read = reader.bytes_read();
+
+            // All of the entries are already loaded, so nothing is read from the storage.
+            probe.fail_reads(Some((links[1], Delta)));
+            reader.prefetch(1..3)?;
+            reader.prefetch(10..20)?;
+            assert_eq!(reader.get_at(2), Some(&U64(2)));
+            assert_eq!(reader.bytes_read(), bytes_read);
+
+            Ok(())
+        })
+    }
+
+    /// Writes a link extending `previous` whose footer claims that the chain contains
+    /// less entries than the link itself, returning its ID.
+    async fn inconsistent_link(storage: &Storage, previous: LinkId) -> Result<LinkId> {
+        let mut content = Vec::new();
+        for entry in 2..4u64 {
+            content.extend_from_slice(&entry.to_be_bytes());
+        }
+
+        let footer = DFooter {
+            previous: Some(previous),
+            index: ChainIndex::new(1),
+            total: 1,
+            count: 2,
+            version: storage::VERSION,
+        };
+
+        content.extend_from_slice(&footer.to_bytes()?);
+
+        let id = LinkId::random();
+        write_file(storage, id, Delta, &content).await?;
+
+        Ok(id)
+    }
+
+    #[test]
+    fn open_suffix_with_inconsistent_total() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, Some(0..2)).await?;
+            let corrupt = inconsistent_link(&storage, links[0]).await?;
+
+            assert!(matches!(
+                Reader::<U64>::open_suffix(corrupt, 1, storage).await,
+                Err(Error::Inconsistent { link, .. }) if link == corrupt
+            ));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn get_index_of_fetch_with_inconsistent_total() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, Some(0..2)).await?;
+            let corrupt = inconsistent_link(&storage, links[0]).await?;
+            let latest = extend(&storage, Some(corrupt), Some(4..5)).await?[0];
+
+            let mut reader = Reader::<U64>::open_suffix(latest, 1, storage).await?;
+            assert!(matches!(
+                reader.get_index_of_fetch(&U64(3)).await,
+                Err(Error::Inconsistent { link, .. }) if link == corrupt
+            ));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn try_get_at() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..4, 4..6]).await?;
+
+            // Entries which weren't loaded are told apart from out-of-range ones.
+            let reader = Reader::<U64>::open_suffix(links[2], 1, storage.clone()).await?;
+            assert_eq!(reader.get_at(1), None);
+            assert!(matches!(
+                reader.try_get_at(1),
+                Err(Error::Unsupported { .. })
+            ));
+            assert_eq!(reader.try_get_at(4)?, Some(&U64(4)));
+            assert_eq!(reader.try_get_at(6)?, None);
+
+            let reader = Reader::<U64>::open(links[2], storage).await?;
+            assert_eq!(reader.try_get_at(0)?, Some(&U64(0)));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn load_entries_into() -> Result<()> {
+        block_on(async {
+            let (storage, probe) = probed();
+            let links = chain(&storage, [0..2, 2..4]).await?;
+            let snapshot = snapshot(&storage, links[1], 4..6).await?;
+            let latest = extend(&storage, Some(snapshot), Some(6..8)).await?[0];
+
+            let mut out = vec![U64(100)];
+            super::load_entries_into(latest, &storage, &mut out).await?;
+            assert!(
+                out.iter()
+                    .map(|entry| entry.0)
+                    .eq([100].into_iter().chain(0..8))
+            );
+
+            // Nothing is appended if reading any of the files fails.
+            probe.fail_reads(Some((snapshot, Snapshot)));
+            let result = super::load_entries_into(latest, &storage, &mut out).await;
+            assert!(matches!(result, Err(Error::Storage(_))));
+            assert_eq!(out.len(), 9);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn open_into() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..4]).await?;
+
+            // Any entry already present is removed, but the configuration is kept.
+            let mut entries = Entries::with_chunk_size(3).with_index_headroom(0.5);
+            entries.insert_unique(U64(100));
+            let reader = Reader::<U64>::open_into(links[1], storage, entries).await?;
+            assert_eq!(reader.len(), 4);
+            assert_eq!(reader.get_index_of(&U64(100)), None);
+            assert_eq!(reader.get_index_of(&U64(3)), Some(3));
+
+            let (_, entries, _) = reader.into_parts();
+            assert!(entries.is_chunked());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn get_index_of_fetch() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..4]).await?;
+            let reader = Reader::<U64>::open(links[1], storage.clone()).await?;
+
+            let mut writer = Writer::<U64>::create(Some(links[1]), storage.clone()).await?;
+            writer.with_snapshot_from(&reader).await?;
+            writer.with_snapshot_only();
+            for entry in 4..6 {
+                writer.write_unique(U64(entry)).await?;
+            }
+
+            let snapshot_only = writer.finish().await?;
+            let latest = extend(&storage, Some(snapshot_only), [6..8, 8..10]).await?;
+
+            let mut reader = Reader::<U64>::open_suffix(latest[1], 1, storage).await?;
+            assert_eq!(reader.get_index_of_fetch(&U64(9)).await?, Some(9));
+            assert_eq!(reader.get_index_of_fetch(&U64(7)).await?, Some(7));
+
+            // The link without a delta is searched through its snapshot.
+            assert_eq!(reader.get_index_of_fetch(&U64(1)).await?, Some(1));
+            assert_eq!(reader.get_index_of_fetch(&U64(100)).await?, None);
+
+            // The links which were searched aren't loaded.
+            assert_eq!(reader.get_index_of(&U64(7)), None);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn difference_and_intersection() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let first = chain(&storage, [0..3, 3..6]).await?;
+            let second = chain(&storage, [4..6, 6..8]).await?;
+
+            let first = Reader::<U64>::open(first[1], storage.clone()).await?;
+            let second = Reader::<U64>::open(second[1], storage).await?;
+
+            // The `u32`s are the ones assigned by the reader the entries are taken from.
+            let difference = first
+                .difference(&second)
+                .map(|(index, entry)| (index, entry.0));
+            assert!(difference.eq((0..4).map(|entry| (entry as u32, entry))));
+            let intersection = first
+                .intersection(&second)
+                .map(|(index, entry)| (index, entry.0));
+            assert!(intersection.eq([(4, 4), (5, 5)]));
+
+            let difference = second
+                .difference(&first)
+                .map(|(index, entry)| (index, entry.0));
+            assert!(difference.eq([(2, 6), (3, 7)]));
+            let intersection = second
+                .intersection(&first)
+                .map(|(index, entry)| (index, entry.0));
+            assert!(intersection.eq([(0, 4), (1, 5)]));
+
+            Ok(())
+        })
     }
 }
@@ -0,0 +1,190 @@
+use std::hash::{BuildHasher, RandomState};
+
+use hashbrown::HashTable;
+
+use crate::{ChainIndex, Error, LinkId, Result, Storage};
+
+use super::RawReader;
+
+/// A view over the encoded bytes of an entry, which borrows them instead of decoding
+/// them into an owned value, as returned by [`ArenaReader`].
+pub trait FromBytes<'a>: Sized {
+    /// Creates a view over the given bytes.
+    ///
+    /// `bytes` always contains exactly as many bytes as each of the entries of the
+    /// chain being read (i.e. their [`Entry::SIZE`][1]).
+    ///
+    /// [1]: crate::Entry::SIZE
+    fn from_bytes(bytes: &'a [u8]) -> Result<Self>;
+}
+
+/// A reader which keeps the encoded entries of a chain in a single contiguous buffer,
+/// and returns views borrowing from it instead of decoding the entries.
+///
+/// Contrarily to [`Reader`][1], which stores decoded entries (requiring an allocation
+/// per entry for entries owning heap memory), this only requires a single allocation
+/// for all of the entries, and a hash table to allow [`get_index_of()`][2] to work.
+/// This is most useful for chains of many small entries.
+///
+/// Entries are compared and hashed using their encoded bytes.
+///
+/// [1]: crate::Reader
+/// [2]: Self::get_index_of()
+pub struct ArenaReader<S = RandomState> {
+    /// The reader owning the encoded entries.
+    raw: RawReader,
+
+    /// Maps the hashes of the encoded entries to the `u32` assigned to them.
+    indexes: HashTable<u32>,
+
+    /// The hasher used to determine where the entries' index should be stored in
+    /// `indexes`.
+    hasher: S,
+}
+
+impl<S: BuildHasher + Default> ArenaReader<S> {
+    /// Creates a new reader from the given storage, loading the necessary links' files,
+    /// for a chain whose entries are encoded with `size` bytes each (i.e. whose
+    /// [`Entry::SIZE`][1] is `size`).
+    ///
+    /// [1]: crate::Entry::SIZE
+    pub async fn open(latest: LinkId, storage: Storage, size: usize) -> Result<Self> {
+        let raw = RawReader::open(latest, storage, size).await?;
+
+        let hasher = S::default();
+        let mut indexes = HashTable::with_capacity(raw.len() as usize);
+
+        for (index, bytes) in raw.iter() {
+            let hash = hasher.hash_one(bytes);
+
+            // `indexes` has enough capacity for all of the entries, so it never needs to
+            // rehash the existing ones.
+            let rehash = |index: &u32| match raw.get_at(*index) {
+                Some(bytes) => hasher.hash_one(bytes),
+                None => 0,
+            };
+
+            indexes.insert_unique(hash, index, rehash);
+        }
+
+        Ok(Self {
+            raw,
+            indexes,
+            hasher,
+        })
+    }
+}
+
+impl<S: BuildHasher> ArenaReader<S> {
+    /// Returns the ID of the last link in the chain which has been loaded by this
+    /// reader.
+    #[inline]
+    pub fn latest(&self) -> LinkId {
+        self.raw.latest()
+    }
+
+    /// Returns the index of the last link in the chain which has been loaded by this
+    /// reader.
+    #[inline]
+    pub fn current_index(&self) -> ChainIndex {
+        self.raw.current_index()
+    }
+
+    /// Returns the number of entries present.
+    #[inline]
+    #[allow(clippy::len_without_is_empty)] // `is_empty` would otherwise always return `false`
+    pub fn len(&self) -> u32 {
+        self.raw.len()
+    }
+
+    /// Returns a view over the entry represented by the given `u32`, if there is one.
+    #[inline]
+    pub fn get_at<'a, V: FromBytes<'a>>(&'a self, index: u32) -> Result<Option<V>> {
+        self.raw.get_at(index).map(V::from_bytes).transpose()
+    }
+
+    /// Returns the `u32` assigned to the entry encoded as `bytes`, if it is present.
+    pub fn get_index_of(&self, bytes: &[u8]) -> Option<u32> {
+        let hash = self.hasher.hash_one(bytes);
+        let eq = |index: &u32| self.raw.get_at(*index) == Some(bytes);
+
+        self.indexes.find(hash, eq).copied()
+    }
+
+    /// Iterates over views over the entries, ordered by the `u32` which represent them.
+    #[inline]
+    pub fn iter<'a, V: FromBytes<'a>>(
+        &'a self,
+    ) -> impl ExactSizeIterator<Item = Result<(u32, V)>> + 'a {
+        self.raw
+            .iter()
+            .map(|(index, bytes)| Ok((index, V::from_bytes(bytes)?)))
+    }
+}
+
+impl<'a> FromBytes<'a> for &'a [u8] {
+    #[inline]
+    fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+        Ok(bytes)
+    }
+}
+
+impl<'a, const N: usize> FromBytes<'a> for &'a [u8; N] {
+    #[inline]
+    fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+        bytes.try_into().map_err(|_| Error::EntrySize {
+            expected: N,
+            got: bytes.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Entry,
+        tests::{U64, block_on, chain, memory},
+    };
+
+    use super::*;
+
+    #[test]
+    fn views_over_entries() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..5]).await?;
+
+            let reader = ArenaReader::<RandomState>::open(links[1], storage, U64::SIZE).await?;
+            assert_eq!(reader.latest(), links[1]);
+            assert_eq!(reader.current_index(), ChainIndex::new(1));
+            assert_eq!(reader.len(), 5);
+
+            let slice: Option<&[u8]> = reader.get_at(3)?;
+            assert_eq!(slice, Some(&3u64.to_be_bytes()[..]));
+            let array: Option<&[u8; 8]> = reader.get_at(4)?;
+            assert_eq!(array, Some(&4u64.to_be_bytes()));
+            assert_eq!(reader.get_at::<&[u8]>(5)?, None);
+
+            // Views of the wrong size can't be created.
+            assert!(matches!(
+                reader.get_at::<&[u8; 4]>(0),
+                Err(Error::EntrySize {
+                    expected: 4,
+                    got: 8
+                })
+            ));
+
+            assert_eq!(reader.get_index_of(&2u64.to_be_bytes()), Some(2));
+            assert_eq!(reader.get_index_of(&5u64.to_be_bytes()), None);
+            assert_eq!(reader.get_index_of(&[0; 4]), None);
+
+            let entries = reader.iter::<&[u8; 8]>().collect::<Result<Vec<_>>>()?;
+            let entries = entries
+                .into_iter()
+                .map(|(index, bytes)| (index, u64::from_be_bytes(*bytes)));
+            assert!(entries.eq((0..5).map(|entry| (entry as u32, entry))));
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    hash::{BuildHasher, Hash, RandomState},
+    marker::PhantomData,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{Entries, Entry, Error, LinkId, Result, Storage, entries::Key, storage};
+
+use super::{CappedReader, Reader};
+
+/// A builder combining the options used to open a [`Reader`], as returned by
+/// [`Reader::builder()`].
+///
+/// There is no option to reserve capacity for the entries, since enough of it is always
+/// reserved for all of the entries of the chain before reading any of them.
+// TODO(MLB): support progress callbacks
+pub struct ReaderBuilder<T: Entry, S = RandomState, V = ()> {
+    /// The maximum number of entries stored in each allocation, if they are stored in
+    /// chunks.
+    chunk_size: Option<u32>,
+
+    /// The fraction of additional capacity reserved in the hash table used to find the
+    /// entries.
+    headroom: f32,
+
+    /// The hasher used to find the entries.
+    hasher: S,
+
+    /// Hashes the key of the entries, if they are found by key.
+    key: Option<Key<T, S>>,
+
+    /// The maximum number of links whose delta is loaded, if only the latest ones are.
+    max_deltas: Option<usize>,
+
+    /// The maximum duration of the whole opening, if any.
+    deadline: Option<Duration>,
+
+    /// The maximum number of entries kept in memory, if the reader is capped.
+    max_resident: Option<usize>,
+
+    _marker: PhantomData<fn() -> (T, V)>,
+}
+
+impl<T: Entry, S: BuildHasher + Default, V: Entry> Reader<T, S, V> {
+    /// Returns a builder allowing to combine the options used to open a reader.
+    ///
+    /// Opening a reader with the default options is the same as calling
+    /// [`open()`][1].
+    ///
+    /// [1]: Self::open()
+    #[inline]
+    pub fn builder() -> ReaderBuilder<T, S, V> {
+        ReaderBuilder {
+            chunk_size: None,
+            headroom: 0.0,
+            hasher: S::default(),
+            key: None,
+            max_deltas: None,
+            deadline: None,
+            max_resident: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Entry, S: BuildHasher + Default, V: Entry> ReaderBuilder<T, S, V> {
+    /// Stores the entries in chunks of at most `chunk_size` entries (see
+    /// [`Reader::open_chunked()`]).
+    #[inline]
+    pub fn chunk_size(mut self, chunk_size: u32) -> Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    /// Reserves more capacity to find the entries (see
+    /// [`Entries::with_index_headroom()`]).
+    #[inline]
+    pub fn index_headroom(mut self, headroom: f32) -> Self {
+        self.headroom = headroom;
+        self
+    }
+
+    /// Uses the given hasher to find the entries (see [`Entries::with_hasher()`]).
+    #[inline]
+    pub fn hasher(mut self, hasher: S) -> Self {
+        self.hasher = hasher;
+        self
+    }
+
+    /// Finds the entries by the key returned by `project`, allowing to call
+    /// [`Reader::get_index_of_by()`] with the same projection (see
+    /// [`Entries::with_key()`]).
+    #[inline]
+    pub fn key<K: Hash>(mut self, project: impl Fn(&T) -> K + Send + Sync + 'static) -> Self {
+        self.key = Some(Arc::new(move |hasher: &S, entry: &T| {
+            hasher.hash_one(project(entry))
+        }));
+
+        self
+    }
+
+    /// Only loads the deltas of at most `max_deltas` of the latest links (see
+    /// [`Reader::open_suffix()`]).
+    #[inline]
+    pub fn max_deltas(mut self, max_deltas: usize) -> Self {
+        self.max_deltas = Some(max_deltas);
+        self
+    }
+
+    /// Fails with [`Error::Timeout`][1] if opening the reader takes longer than
+    /// `deadline`, whatever the timeout of each of the operations (see
+    /// [`Storage::with_operation_timeout()`]).
+    ///
+    /// [1]: crate::Error::Timeout
+    #[inline]
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Keeps at most `max_resident_entries` entries in memory, reading the other ones
+    /// from the storage when they are accessed, which requires opening the reader with
+    /// [`open_capped()`][1] (see [`CappedReader`]).
+    ///
+    /// [1]: Self::open_capped()
+    #[inline]
+    pub fn max_resident_entries(mut self, max_resident_entries: usize) -> Self {
+        self.max_resident = Some(max_resident_entries);
+        self
+    }
+
+    /// Creates a new reader from the given storage, loading the necessary links' files
+    /// according to the options of the builder.
+    ///
+    /// Fails with [`Error::Unsupported`] if the number of entries kept in memory is
+    /// capped (see [`max_resident_entries()`][1]).
+    ///
+    /// [1]: Self::max_resident_entries()
+    pub async fn open(self, latest: LinkId, storage: Storage) -> Result<Reader<T, S, V>> {
+        if self.max_resident.is_some() {
+            return Err(Error::Unsupported {
+                operation: "open a capped reader with `open()`",
+            });
+        }
+
+        let entries = match self.chunk_size {
+            Some(chunk_size) => Entries::with_chunk_size(chunk_size),
+            None => Entries::default(),
+        };
+
+        let mut entries = entries
+            .with_hasher(self.hasher)
+            .with_index_headroom(self.headroom);
+
+        if let Some(key) = self.key {
+            entries = entries.with_key_hash(key);
+        }
+
+        let (snapshots, budget) = match self.max_deltas {
+            Some(max_deltas) => (false, Some(max_deltas.max(1))),
+            None => (true, None),
+        };
+
+        let open = Reader::open_with(latest, storage, entries, snapshots, None, budget);
+        storage::timed(self.deadline, "open", open).await
+    }
+}
+
+impl<T: Entry, S: BuildHasher + Default> ReaderBuilder<T, S> {
+    /// Creates a new reader from the given storage which keeps at most the number of
+    /// entries set with [`max_resident_entries()`][1] in memory (or all of them if it
+    /// wasn't set), reading the other ones from the storage when they are accessed.
+    ///
+    /// Only the hasher and the deadline apply to capped readers. Fails with
+    /// [`Error::Unsupported`] if only the deltas of the latest links should be loaded,
+    /// or if the entries are found by key.
+    ///
+    /// [1]: Self::max_resident_entries()
+    pub async fn open_capped(self, latest: LinkId, storage: Storage) -> Result<CappedReader<T, S>> {
+        if self.max_deltas.is_some() || self.key.is_some() {
+            return Err(Error::Unsupported {
+                operation: "open a capped reader loading only the latest deltas or finding \
+                            entries by key",
+            });
+        }
+
+        let max_resident = self.max_resident.unwrap_or(usize::MAX);
+        let open = CappedReader::open(latest, storage, self.hasher, max_resident);
+        storage::timed(self.deadline, "open", open).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        storage::Kind::*,
+        tests::{U64, block_on, chain, probed},
+    };
+
+    use super::*;
+
+    #[test]
+    fn combines_options() -> Result<()> {
+        block_on(async {
+            let (storage, probe) = probed();
+            let links = chain(&storage, [0..2, 2..4, 4..6]).await?;
+
+            let reader = Reader::<U64>::builder()
+                .chunk_size(2)
+                .key(|entry: &U64| entry.0 * 10)
+                .max_deltas(2)
+                .open(links[2], storage.clone())
+                .await?;
+
+            assert_eq!(reader.first_loaded(), 2);
+            assert_eq!(reader.get_index_of_by(&30, |entry| entry.0 * 10), Some(3));
+
+            // Only the hasher and the deadline apply to capped readers.
+            let builder = Reader::<U64>::builder().max_resident_entries(2);
+            let result = builder.open(links[2], storage.clone()).await;
+            assert!(matches!(result, Err(Error::Unsupported { .. })));
+
+            let builder = Reader::<U64>::builder().max_deltas(1);
+            let result = builder.open_capped(links[2], storage.clone()).await;
+            assert!(matches!(result, Err(Error::Unsupported { .. })));
+
+            let builder = Reader::<U64>::builder().max_resident_entries(2);
+            let mut reader = builder.open_capped(links[2], storage.clone()).await?;
+            assert_eq!(reader.get_at(0).await?, Some(&U64(0)));
+
+            probe.stall_reads(Some((links[0], Delta)));
+            let builder = Reader::<U64>::builder().deadline(Duration::from_millis(10));
+            let result = builder.open(links[2], storage).await;
+            assert!(matches!(result, Err(Error::Timeout { operation: "open" })));
+
+            Ok(())
+        })
+    }
+}
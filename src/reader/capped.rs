@@ -0,0 +1,562 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{BuildHasher, RandomState},
+    io::SeekFrom,
+};
+
+use hashbrown::HashTable;
+
+use crate::{
+    ChainIndex, DFooter, Entry, Error, LinkContext, LinkId, Result, SFooter, Storage,
+    storage::{self, Kind::*, Retries},
+};
+
+use super::{read_entry, read_older};
+
+/// A reader which keeps at most a given number of the entries of a chain in memory,
+/// reading the other ones from the storage when they are accessed.
+///
+/// Contrarily to [`Reader`][1], which keeps all of the entries it loaded in memory,
+/// this only keeps the entries which were accessed most recently: whenever
+/// [`get_at()`][2] reads an entry which isn't resident while the maximum number of
+/// entries are, the least recently accessed one is evicted. The file of each link
+/// stays open and all of the entries stored in a file have the same size, so reading
+/// an entry only requires a single read request.
+///
+/// This is a separate type rather than a mode of [`Reader`][1] because accessing an
+/// entry might require reading it, which [`Reader`][1]'s synchronous, shared
+/// accessors (which return references to entries which stay in memory for as long as
+/// the reader does) can't do.
+///
+/// To allow [`get_index_of()`][3] to work, opening the reader reads all of the entries
+/// once to keep their hashes (but not the entries themselves) in a hash table, which
+/// requires 4 bytes and a control byte per entry. Finding an entry then reads the
+/// entries with the same hash which aren't resident.
+///
+/// The entries are read from the links' delta files, or from the snapshot of the first
+/// link whose delta file is missing (along with its base or the snapshot it was
+/// appended to, if any). Sorted snapshots additionally require keeping where each of
+/// their entries is stored in memory (4 bytes per entry), and encrypted files are
+/// decrypted all at once when they are opened, so their content stays in memory.
+///
+/// [1]: crate::Reader
+/// [2]: Self::get_at()
+/// [3]: Self::get_index_of()
+pub struct CappedReader<T: Entry, S = RandomState> {
+    /// The ID of the latest link which has been loaded.
+    latest: LinkId,
+
+    /// The index in the chain of the latest link which has been loaded.
+    index: ChainIndex,
+
+    /// The number of entries in the chain.
+    len: u32,
+
+    /// The files from which the entries are read, ordered by the `u32` assigned to the
+    /// first entry each of them stores.
+    files: Vec<File>,
+
+    /// Maps the hashes of all of the entries to the `u32` assigned to them.
+    indexes: HashTable<u32>,
+
+    /// The hasher used to determine where the entries' index should be stored in
+    /// `indexes`.
+    hasher: S,
+
+    /// How reading an entry should be retried if it fails transiently.
+    retries: Option<Retries>,
+
+    /// The entries which are kept in memory.
+    resident: Resident<T>,
+}
+
+/// The entries of a [`CappedReader`] which are kept in memory, evicting the least
+/// recently accessed one when a new one is kept while the maximum number of entries
+/// already are.
+struct Resident<T> {
+    /// The maximum number of entries kept in `entries`.
+    max: usize,
+
+    /// The entries, keyed by the `u32` assigned to them, along with the last time they
+    /// were accessed.
+    entries: HashMap<u32, (T, u64)>,
+
+    /// The `u32`s assigned to the entries in `entries`, keyed by the last time they
+    /// were accessed.
+    accesses: BTreeMap<u64, u32>,
+
+    /// The number of times entries have been accessed, used as a clock to order the
+    /// accesses.
+    clock: u64,
+}
+
+/// A file from which entries are read.
+struct File {
+    /// The `u32` assigned to the first entry stored in the file.
+    start: u32,
+
+    /// The number of entries stored in the file.
+    count: u32,
+
+    /// The offset at which the entries stored in the file start (e.g. after the
+    /// previous link's snapshot stored at the start of appended snapshots).
+    offset: u64,
+
+    /// Where each of the entries stored in the file is stored relatively to the
+    /// others, ordered by the `u32` assigned to them.
+    ///
+    /// This is empty unless the file is a sorted snapshot.
+    positions: Vec<u32>,
+
+    /// The reader for the file, which acts as-if its footer did not exist.
+    reader: storage::Reader,
+
+    /// The context in which the entries stored in the file are decoded.
+    context: LinkContext,
+}
+
+impl<T: Entry, S: BuildHasher> CappedReader<T, S> {
+    /// Creates a new reader from the given storage, reading all of the entries of the
+    /// chain up to `latest` once, and keeping at most `max_resident` of them (but at
+    /// least one) in memory.
+    pub(super) async fn open(
+        latest: LinkId,
+        storage: Storage,
+        hasher: S,
+        max_resident: usize,
+    ) -> Result<Self> {
+        let mut files = Vec::new();
+        let mut totals = Vec::new();
+        let mut index = None;
+        let mut next = Some(latest);
+
+        while let Some(link) = next {
+            if let Some(mut reader) = storage.open_maybe(link, Delta).await? {
+                let footer = DFooter::read(&mut reader).await?;
+                footer.check_size(link, &reader, T::SIZE)?;
+                index.get_or_insert(footer.index);
+
+                // The number of entries in the chain stored by legacy deltas can't be
+                // trusted (see `Storage::legacy_total()`), but isn't needed either.
+                totals.push((footer.version != storage::LEGACY_VERSION).then_some(footer.total));
+
+                next = footer.previous;
+                files.push(File {
+                    start: 0,
+                    count: footer.count,
+                    offset: 0,
+                    positions: Vec::new(),
+                    reader,
+                    context: LinkContext::delta(link, &footer),
+                });
+
+                continue;
+            }
+
+            // Snapshots contain all of the entries of the previous links as well, some of
+            // which might be stored in their base or at their start.
+            let mut reader = storage.open(link, Snapshot).await?;
+            let footer = SFooter::read(&mut reader).await?;
+            index.get_or_insert(footer.index);
+
+            let mut snapshot = Some((link, reader, footer));
+            while let Some((link, reader, footer)) = snapshot {
+                snapshot = read_older(&storage, link, &reader, &footer).await?;
+                totals.push(Some(footer.count));
+                files.push(File::snapshot::<T>(link, reader, &footer).await?);
+            }
+
+            break;
+        }
+
+        files.reverse();
+        totals.reverse();
+
+        // Each link's entries must directly follow the entries of its previous link.
+        let mut len = 0u32;
+        for (file, total) in files.iter_mut().zip(totals) {
+            file.start = len;
+            len = len.saturating_add(file.count);
+
+            if total.is_some_and(|total| total != len) {
+                return Err(Error::Inconsistent {
+                    link: file.context.link,
+                    reason: "link entry count doesn't follow its previous link's",
+                });
+            }
+        }
+
+        let mut this = Self {
+            latest,
+            index: index.unwrap_or_default(),
+            len,
+            files,
+            indexes: HashTable::with_capacity(len as usize),
+            hasher,
+            retries: storage.retries(),
+            resident: Resident::new(max_resident),
+        };
+
+        this.load().await?;
+
+        Ok(this)
+    }
+
+    /// Reads all of the entries to store their hashes, keeping the latest ones in
+    /// memory.
+    async fn load(&mut self) -> Result<()> {
+        for position in 0..self.files.len() {
+            let File {
+                start,
+                count,
+                offset,
+                positions,
+                reader,
+                context,
+            } = &mut self.files[position];
+
+            // The entries of sorted snapshots aren't stored in the order of the `u32`s
+            // assigned to them.
+            let mut assigned = vec![0; positions.len()];
+            for (index, &slot) in positions.iter().enumerate() {
+                assigned[slot as usize] = index as u32;
+            }
+
+            // The entries are read one after the other, which can reuse a single request.
+            reader.seek(SeekFrom::Start(*offset))?;
+            reader.read_sequentially();
+
+            for slot in 0..*count {
+                let index = *start + assigned.get(slot as usize).copied().unwrap_or(slot);
+                let entry: T = read_entry(reader, context, self.retries).await?;
+                let hash = self.hasher.hash_one(&entry);
+
+                // `indexes` has enough capacity for all of the entries, so it never needs to
+                // rehash the existing ones.
+                self.indexes.insert_unique(hash, index, |_| unreachable!());
+
+                self.resident.insert(index, entry);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the ID of the last link in the chain which has been loaded by this
+    /// reader.
+    #[inline]
+    pub fn latest(&self) -> LinkId {
+        self.latest
+    }
+
+    /// Returns the index of the last link in the chain which has been loaded by this
+    /// reader.
+    #[inline]
+    pub fn current_index(&self) -> ChainIndex {
+        self.index
+    }
+
+    /// Returns the number of entries present, whether or not they are resident.
+    #[inline]
+    #[allow(clippy::len_without_is_empty)] // `is_empty` would otherwise always return `false`
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Returns the maximum number of entries kept in memory.
+    #[inline]
+    pub fn max_resident(&self) -> usize {
+        self.resident.max
+    }
+
+    /// Returns the number of entries currently kept in memory.
+    #[inline]
+    pub fn resident_len(&self) -> usize {
+        self.resident.entries.len()
+    }
+
+    /// Returns `true` if the entry represented by the given `u32` is currently kept in
+    /// memory (meaning that [`get_at()`][1] doesn't need to read it).
+    ///
+    /// [1]: Self::get_at()
+    #[inline]
+    pub fn is_resident(&self, index: u32) -> bool {
+        self.resident.entries.contains_key(&index)
+    }
+
+    /// Returns the number of bytes which have been read from the storage so far,
+    /// including when opening the reader.
+    pub fn bytes_read(&self) -> u64 {
+        self.files.iter().map(|file| file.reader.bytes_read()).sum()
+    }
+
+    /// Returns the entry represented by the given `u32`, if there is one.
+    ///
+    /// If the entry isn't resident, this reads it from the storage and keeps it in
+    /// memory, evicting the least recently accessed entry if the maximum number of
+    /// entries are already resident.
+    pub async fn get_at(&mut self, index: u32) -> Result<Option<&T>> {
+        if index >= self.len {
+            return Ok(None);
+        }
+
+        if !self.resident.touch(index) {
+            let entry = self.read(index).await?;
+            self.resident.insert(index, entry);
+        }
+
+        Ok(self.resident.get(index))
+    }
+
+    /// Returns the `u32` assigned to the given `entry`, if it is present.
+    ///
+    /// This compares `entry` with all of the entries with the same hash, reading the
+    /// ones which aren't resident (see [`get_at()`][1]).
+    ///
+    /// [1]: Self::get_at()
+    pub async fn get_index_of(&mut self, entry: &T) -> Result<Option<u32>> {
+        let hash = self.hasher.hash_one(entry);
+        let candidates = self.indexes.iter_hash(hash).copied().collect::<Vec<_>>();
+
+        for index in candidates {
+            if self.get_at(index).await? == Some(entry) {
+                return Ok(Some(index));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Reads the entry represented by the given `u32`, which must be present, from the
+    /// file storing it.
+    async fn read(&mut self, index: u32) -> Result<T> {
+        let position = self.files.partition_point(|file| file.start <= index) - 1;
+        let File {
+            start,
+            offset,
+            positions,
+            reader,
+            context,
+            ..
+        } = &mut self.files[position];
+
+        let slot = index - *start;
+        let slot = positions.get(slot as usize).copied().unwrap_or(slot);
+        reader.seek(SeekFrom::Start(*offset + slot as u64 * T::SIZE as u64))?;
+
+        read_entry(reader, context, self.retries).await
+    }
+}
+
+impl File {
+    /// Creates a new file from which the entries stored in the snapshot of `link` being
+    /// read by `reader` are read (i.e. excluding the ones stored in its base or at its
+    /// start), reading where each of them is stored if the snapshot is sorted.
+    ///
+    /// Fails with [`Error::Inconsistent`] if the snapshot doesn't contain exactly the
+    /// number of entries stored in its footer.
+    async fn snapshot<T: Entry>(
+        link: LinkId,
+        mut reader: storage::Reader,
+        footer: &SFooter,
+    ) -> Result<Self> {
+        let count = footer
+            .count
+            .saturating_sub(footer.base_count)
+            .saturating_sub(footer.prefix_count);
+
+        // Sorted snapshots store the position of each of their entries after them.
+        let size = if footer.sorted { T::SIZE + 4 } else { T::SIZE };
+        if reader.file_size() != footer.prefix as usize + count as usize * size {
+            return Err(Error::Inconsistent {
+                link,
+                reason: "snapshot entry count mismatch",
+            });
+        }
+
+        let mut positions = Vec::new();
+        if footer.sorted {
+            let mut stored = vec![false; count as usize];
+            positions.reserve_exact(count as usize);
+
+            reader.seek(SeekFrom::Start(
+                footer.prefix + count as u64 * T::SIZE as u64,
+            ))?;
+            reader.read_sequentially();
+
+            for _ in 0..count {
+                let position = reader.read_u32().await?;
+                match stored.get_mut(position as usize) {
+                    Some(stored @ false) => *stored = true,
+                    _ => return Err(Error::InvalidPermutation { link }),
+                }
+
+                positions.push(position);
+            }
+        }
+
+        Ok(Self {
+            start: 0,
+            count,
+            offset: footer.prefix,
+            positions,
+            reader,
+            context: LinkContext::snapshot(link, footer),
+        })
+    }
+}
+
+impl<T> Resident<T> {
+    /// Creates a new set of resident entries which keeps at most `max` entries (but at
+    /// least one).
+    #[inline]
+    fn new(max: usize) -> Self {
+        Self {
+            max: max.max(1),
+            entries: HashMap::new(),
+            accesses: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// Returns the entry represented by the given `u32`, if it is resident.
+    #[inline]
+    fn get(&self, index: u32) -> Option<&T> {
+        self.entries.get(&index).map(|(entry, _)| entry)
+    }
+
+    /// Marks the entry represented by the given `u32` as just accessed, returning
+    /// `false` if it isn't resident.
+    fn touch(&mut self, index: u32) -> bool {
+        let Some((_, accessed)) = self.entries.get_mut(&index) else {
+            return false;
+        };
+
+        self.clock += 1;
+        self.accesses.remove(accessed);
+        self.accesses.insert(self.clock, index);
+        *accessed = self.clock;
+
+        true
+    }
+
+    /// Keeps the given entry, represented by `index`, in memory as just accessed,
+    /// evicting the least recently accessed entry if the maximum number of entries are
+    /// already resident.
+    fn insert(&mut self, index: u32, entry: T) {
+        if self.entries.len() >= self.max
+            && let Some((_, coldest)) = self.accesses.pop_first()
+        {
+            self.entries.remove(&coldest);
+        }
+
+        self.clock += 1;
+        self.entries.insert(index, (entry, self.clock));
+        self.accesses.insert(self.clock, index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Reader, Writer,
+        tests::{U64, block_on, chain, memory},
+    };
+
+    use super::*;
+
+    /// Opens a reader for the chain up to `latest` keeping at most `max` entries in
+    /// memory.
+    async fn open(latest: LinkId, storage: &Storage, max: usize) -> Result<CappedReader<U64>> {
+        Reader::<U64>::builder()
+            .max_resident_entries(max)
+            .open_capped(latest, storage.clone())
+            .await
+    }
+
+    #[test]
+    fn evicts_and_reloads() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let ids = chain(&storage, [0..4, 4..8]).await?;
+            let mut reader = open(ids[1], &storage, 2).await?;
+
+            assert_eq!(reader.len(), 8);
+            assert_eq!(reader.resident_len(), 2);
+            assert!(reader.is_resident(6) && reader.is_resident(7));
+
+            // Resident entries are returned without reading anything.
+            let bytes_read = reader.bytes_read();
+            assert_eq!(reader.get_at(6).await?, Some(&U64(6)));
+            assert_eq!(reader.bytes_read(), bytes_read);
+
+            // Other entries are read, evicting the least recently accessed one.
+            assert_eq!(reader.get_at(1).await?, Some(&U64(1)));
+            assert_eq!(reader.bytes_read(), bytes_read + U64::SIZE as u64);
+            assert_eq!(reader.resident_len(), 2);
+            assert!(reader.is_resident(1) && reader.is_resident(6));
+            assert!(!reader.is_resident(7));
+
+            assert_eq!(reader.get_at(7).await?, Some(&U64(7)));
+            assert!(!reader.is_resident(6));
+
+            // Finding an entry reads the evicted candidates.
+            assert_eq!(reader.get_index_of(&U64(5)).await?, Some(5));
+            assert!(reader.is_resident(5));
+            assert_eq!(reader.get_index_of(&U64(8)).await?, None);
+            assert_eq!(reader.get_at(8).await?, None);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn reads_snapshots() -> Result<()> {
+        block_on(async {
+            for kind in ["full", "incremental", "appended", "sorted"] {
+                let storage = memory();
+
+                // The snapshots of the links after the first are incremental, appended to
+                // each other or sorted (by ascending entries) depending on `kind`.
+                let mut previous = None;
+                for entries in [0..3, 3..6, 6..9] {
+                    let mut writer = Writer::<U64>::create(previous, storage.clone()).await?;
+                    match kind {
+                        "incremental" => writer.with_incremental_snapshot().await?,
+                        "appended" => writer.with_appended_snapshot().await?,
+                        "sorted" => writer.with_sorted_snapshot().await?,
+                        _ => writer.with_snapshot().await?,
+                    }
+
+                    writer.with_snapshot_only();
+                    for entry in entries.rev() {
+                        writer.write_unique(U64(entry)).await?;
+                    }
+
+                    previous = Some(writer.finish().await?);
+                }
+
+                let mut reader = open(previous.unwrap(), &storage, 1).await?;
+                assert_eq!(reader.len(), 9, "{kind}");
+
+                for (index, entry) in (0..3)
+                    .rev()
+                    .chain((3..6).rev())
+                    .chain((6..9).rev())
+                    .enumerate()
+                {
+                    let (index, entry) = (index as u32, U64(entry));
+                    assert_eq!(reader.get_at(index).await?, Some(&entry), "{kind}");
+                    assert_eq!(reader.resident_len(), 1, "{kind}");
+                }
+
+                for index in (0..9).rev() {
+                    let entry = *reader.get_at(index).await?.unwrap();
+                    assert_eq!(reader.get_index_of(&entry).await?, Some(index), "{kind}");
+                }
+            }
+
+            Ok(())
+        })
+    }
+}
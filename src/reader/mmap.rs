@@ -0,0 +1,314 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    hash::{BuildHasher, RandomState},
+    marker::PhantomData,
+    ops::Range,
+};
+
+use hashbrown::HashTable;
+use memmap2::Mmap;
+
+use crate::{
+    ChainIndex, Entry, Error, GetEntry, LinkId, Result, SFooter, Storage, storage::Kind::*,
+};
+
+/// An [`Entry`] which can be decoded synchronously from the bytes it was encoded
+/// into, as required by [`MmapReader`].
+pub trait DecodeEntry: Entry {
+    /// Decodes an entry from the given bytes.
+    ///
+    /// `bytes` always contains exactly `SIZE` bytes.
+    fn decode(bytes: &[u8]) -> Result<Self>;
+}
+
+/// A reader which memory-maps the snapshot file of the latest link of a chain stored
+/// on the local file system, decoding entries when they are accessed instead of
+/// keeping all of them in memory.
+///
+/// Only the `u32`s assigned to the entries are kept in memory, to allow
+/// [`get_index_of()`][1] to work.
+///
+/// [1]: Self::get_index_of()
+pub struct MmapReader<T: DecodeEntry, S = RandomState> {
+    /// The ID of the link whose snapshot file is mapped.
+    latest: LinkId,
+
+    /// The index in the chain of the link whose snapshot file is mapped.
+    index: ChainIndex,
+
+    /// The number of entries present in the mapped snapshot file.
+    count: u32,
+
+    /// The mapped snapshot file.
+    map: Mmap,
+
+    /// Maps the hashes of the entries to the `u32` assigned to them.
+    indexes: HashTable<u32>,
+
+    /// The hasher used to determine where the entries' index should be stored in
+    /// `indexes`.
+    hasher: S,
+
+    _t: PhantomData<T>,
+}
+
+impl<T: DecodeEntry, S: BuildHasher + Default> MmapReader<T, S> {
+    /// Creates a new reader from the given storage, mapping the snapshot file of the
+    /// `latest` link.
+    ///
+    /// Fails if the storage isn't backed by the local file system, or if no snapshot
+    /// file exists for `latest`, or if it is an incremental, appended, sorted or
+    /// encrypted snapshot.
+    pub async fn open(latest: LinkId, storage: Storage) -> Result<Self> {
+        let Some(path) = storage.local_path(latest, Snapshot) else {
+            return Err(Error::Unsupported { operation: "mmap" });
+        };
+
+        let mut reader = storage.open(latest, Snapshot).await?;
+        let footer = SFooter::read(&mut reader).await?;
+
+        // TODO(MLB): map both the base's snapshot and the incremental one
+        if footer.base.is_some() {
+            return Err(Error::Unsupported {
+                operation: "mmap incremental snapshot",
+            });
+        }
+
+        if reader.is_in_memory() {
+            return Err(Error::Unsupported {
+                operation: "mmap encrypted snapshot",
+            });
+        }
+
+        // TODO(MLB): map each of the parts of appended snapshots
+        if footer.prefix > 0 {
+            return Err(Error::Unsupported {
+                operation: "mmap appended snapshot",
+            });
+        }
+
+        // TODO(MLB): read the positions of the entries of sorted snapshots
+        if footer.sorted {
+            return Err(Error::Unsupported {
+                operation: "mmap sorted snapshot",
+            });
+        }
+
+        let expected = footer.count as usize * T::SIZE;
+        if reader.file_size() < expected {
+            return Err(Error::FileSize {
+                expected,
+                got: reader.file_size(),
+            });
+        }
+
+        let file = File::open(path)?;
+
+        // SAFETY: files are never modified once they have been written, so the mapped
+        //         memory cannot change while it is being read.
+        let map = unsafe { Mmap::map(&file)? };
+
+        let hasher = S::default();
+        let mut indexes = HashTable::with_capacity(footer.count as usize);
+
+        for index in 0..footer.count {
+            let entry = T::decode(entry_bytes::<T>(&map, index))?;
+            let hash = hasher.hash_one(&entry);
+
+            // `indexes` has enough capacity for all of the entries, so it never needs to
+            // rehash the existing ones.
+            let rehash = |index: &u32| match T::decode(entry_bytes::<T>(&map, *index)) {
+                Ok(entry) => hasher.hash_one(&entry),
+                Err(_) => 0,
+            };
+
+            indexes.insert_unique(hash, index, rehash);
+        }
+
+        Ok(Self {
+            latest,
+            index: footer.index,
+            count: footer.count,
+
+            map,
+            indexes,
+            hasher,
+
+            _t: PhantomData,
+        })
+    }
+}
+
+impl<T: DecodeEntry, S: BuildHasher> MmapReader<T, S> {
+    /// Returns the ID of the link whose snapshot file is mapped.
+    #[inline]
+    pub fn latest(&self) -> LinkId {
+        self.latest
+    }
+
+    /// Returns the index of the link whose snapshot file is mapped.
+    #[inline]
+    pub fn current_index(&self) -> ChainIndex {
+        self.index
+    }
+
+    /// Returns the number of entries present.
+    #[inline]
+    #[allow(clippy::len_without_is_empty)] // `is_empty` would otherwise always return `false`
+    pub fn len(&self) -> u32 {
+        self.count
+    }
+
+    /// Decodes and returns the entry represented by the given `u32`, if there is one.
+    pub fn get_at(&self, index: u32) -> Result<Option<T>> {
+        if index >= self.count {
+            return Ok(None);
+        }
+
+        T::decode(entry_bytes::<T>(&self.map, index)).map(Some)
+    }
+
+    /// Asks the operating system to read the entries represented by the `u32`s in
+    /// `range` from the mapped snapshot file ahead of time, so that accessing them later
+    /// doesn't have to wait for it.
+    ///
+    /// This returns without waiting for the entries to be read, and does nothing on
+    /// platforms other than Unix ones.
+    pub fn prefetch(&self, range: Range<u32>) -> Result<()> {
+        let start = range.start.min(self.count) as usize * T::SIZE;
+        let end = range.end.min(self.count) as usize * T::SIZE;
+
+        #[cfg(unix)]
+        if start < end {
+            self.map
+                .advise_range(memmap2::Advice::WillNeed, start, end - start)?;
+        }
+
+        #[cfg(not(unix))]
+        let _ = (start, end);
+
+        Ok(())
+    }
+
+    /// Returns the `u32` assigned to the given `entry`, if it is present.
+    pub fn get_index_of(&self, entry: &T) -> Option<u32> {
+        let hash = self.hasher.hash_one(entry);
+        let eq = |index: &u32| {
+            T::decode(entry_bytes::<T>(&self.map, *index)).is_ok_and(|other| &other == entry)
+        };
+
+        self.indexes.find(hash, eq).copied()
+    }
+}
+
+impl<T: DecodeEntry + Clone, S: BuildHasher> GetEntry<T> for MmapReader<T, S> {
+    #[inline]
+    fn get_cow_at(&self, index: u32) -> Result<Option<Cow<'_, T>>> {
+        Ok(self.get_at(index)?.map(Cow::Owned))
+    }
+}
+
+/// Returns the encoded bytes of the entry represented by the given `u32`.
+#[inline]
+fn entry_bytes<T: Entry>(map: &[u8], index: u32) -> &[u8] {
+    let start = index as usize * T::SIZE;
+    &map[start..start + T::SIZE]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Reader,
+        tests::{U64, block_on, chain, local, snapshot},
+    };
+
+    use super::*;
+
+    impl DecodeEntry for U64 {
+        fn decode(bytes: &[u8]) -> Result<Self> {
+            Ok(Self(u64::from_be_bytes(bytes.try_into().unwrap())))
+        }
+    }
+
+    #[test]
+    fn same_entries_as_reader() -> Result<()> {
+        block_on(async {
+            let (storage, _dir) = local();
+            let links = chain(&storage, [0..2, 2..4]).await?;
+            let latest = snapshot(&storage, links[1], 4..6).await?;
+
+            // Entries are decoded when accessed rather than when loading the chain, but
+            // the same ones are returned.
+            let lazy = MmapReader::<U64>::open(latest, storage.clone()).await?;
+            let eager = Reader::<U64>::open(latest, storage).await?;
+            assert_eq!(
+                (lazy.latest(), lazy.current_index()),
+                (latest, eager.current_index())
+            );
+            assert_eq!(lazy.len(), eager.len());
+
+            for index in 0..7 {
+                assert_eq!(lazy.get_at(index)?, eager.get_at(index).copied());
+                assert_eq!(
+                    lazy.get_index_of(&U64(index as u64)),
+                    eager.get_index_of(&U64(index as u64))
+                );
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn get_cow_at() -> Result<()> {
+        /// Returns the sum of the entries represented by the `u32`s in `range`.
+        fn sum(reader: &impl GetEntry<U64>, range: Range<u32>) -> Result<u64> {
+            let mut sum = 0;
+            for index in range {
+                sum += reader.get_cow_at(index)?.map_or(0, |entry| entry.0);
+            }
+
+            Ok(sum)
+        }
+
+        block_on(async {
+            let (storage, _dir) = local();
+            let links = chain(&storage, [0..2, 2..4]).await?;
+            let latest = snapshot(&storage, links[1], 4..6).await?;
+
+            // The mapped reader returns owned entries, and the eager one borrowed ones.
+            let lazy = MmapReader::<U64>::open(latest, storage.clone()).await?;
+            let eager = Reader::<U64>::open(latest, storage).await?;
+            assert!(matches!(lazy.get_cow_at(1)?, Some(Cow::Owned(U64(1)))));
+            assert!(matches!(eager.get_cow_at(1)?, Some(Cow::Borrowed(U64(1)))));
+
+            assert_eq!(sum(&lazy, 0..10)?, 15);
+            assert_eq!(sum(&eager, 0..10)?, 15);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn prefetch() -> Result<()> {
+        block_on(async {
+            let (storage, _dir) = local();
+            let links = chain(&storage, Some(0..1)).await?;
+            let latest = snapshot(&storage, links[0], 1..1000).await?;
+
+            // Ranges are clamped to the entries present. Whether accessing the entries
+            // then reads the file can't be observed through the map, as the operating
+            // system reads its pages on its own.
+            let reader = MmapReader::<U64>::open(latest, storage).await?;
+            reader.prefetch(10..500)?;
+            reader.prefetch(900..2000)?;
+            reader.prefetch(2000..3000)?;
+            reader.prefetch(5..5)?;
+
+            assert_eq!(reader.get_at(999)?, Some(U64(999)));
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,190 @@
+use std::ops::Range;
+
+use crate::{
+    ChainIndex, DFooter, LinkContext, LinkId, Result, SFooter, Storage,
+    storage::{self, Kind::*},
+};
+
+use super::{Decode, read_snapshot_with};
+
+/// A reader which loads the entries of a chain as raw bytes, without decoding them.
+///
+/// Contrarily to [`Reader`][1], this doesn't require a concrete [`Entry`][2] type, but
+/// only the size of the encoded entries, which allows writing tools which inspect or
+/// copy chains whatever the type of their entries. Since entries aren't decoded,
+/// finding the `u32` assigned to an entry isn't supported.
+///
+/// [1]: crate::Reader
+/// [2]: crate::Entry
+pub struct RawReader {
+    /// The ID of the latest link which has been loaded.
+    latest: LinkId,
+
+    /// The index in the chain of the latest link which has been loaded.
+    index: ChainIndex,
+
+    /// The size of each of the encoded entries.
+    size: usize,
+
+    /// The number of entries which have been loaded.
+    len: u32,
+
+    /// The encoded entries which have been loaded, one after the other and ordered by
+    /// the `u32` which represent them.
+    bytes: Vec<u8>,
+}
+
+/// Decodes entries as their raw bytes.
+struct Raw(usize);
+
+impl RawReader {
+    /// Creates a new reader from the given storage, loading the necessary links' files,
+    /// for a chain whose entries are encoded with `size` bytes each (i.e. whose
+    /// [`Entry::SIZE`][1] is `size`).
+    ///
+    /// [1]: crate::Entry::SIZE
+    pub async fn open(latest: LinkId, storage: Storage, size: usize) -> Result<Self> {
+        let mut deltas = Vec::new();
+        let mut snapshot = Vec::new();
+        let mut index = None;
+        let mut len = 0;
+        let mut next = latest;
+
+        loop {
+            if let Some(mut reader) = storage.open_maybe(next, Snapshot).await? {
+                let footer = SFooter::read(&mut reader).await?;
+                index.get_or_insert(footer.index);
+                len += footer.count;
+
+                snapshot.reserve(footer.count as usize * size);
+                let insert = |entry: Vec<u8>| snapshot.extend_from_slice(&entry);
+                read_snapshot_with(&storage, next, reader, &footer, &Raw(size), insert).await?;
+                break;
+            }
+
+            let mut reader = storage.open(next, Delta).await?;
+            let footer = DFooter::read(&mut reader).await?;
+            footer.check_size(next, &reader, size)?;
+            index.get_or_insert(footer.index);
+            len += footer.count;
+
+            deltas.push(reader.read_vec(footer.count as usize * size).await?);
+
+            let Some(previous) = footer.previous else {
+                break;
+            };
+
+            next = previous;
+        }
+
+        let mut bytes = snapshot;
+        for delta in deltas.into_iter().rev() {
+            bytes.extend_from_slice(&delta);
+        }
+
+        Ok(Self {
+            latest,
+            // The loop always reads the footer of `latest` before ending.
+            index: index.unwrap_or_default(),
+            size,
+            len,
+            bytes,
+        })
+    }
+
+    /// Returns the ID of the last link in the chain which has been loaded by this
+    /// reader.
+    #[inline]
+    pub fn latest(&self) -> LinkId {
+        self.latest
+    }
+
+    /// Returns the index of the last link in the chain which has been loaded by this
+    /// reader.
+    #[inline]
+    pub fn current_index(&self) -> ChainIndex {
+        self.index
+    }
+
+    /// Returns the size of each of the encoded entries.
+    #[inline]
+    pub fn entry_size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the number of entries present.
+    #[inline]
+    #[allow(clippy::len_without_is_empty)] // `is_empty` would otherwise always return `false`
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Returns the encoded entry represented by the given `u32`, if there is one.
+    #[inline]
+    pub fn get_at(&self, index: u32) -> Option<&[u8]> {
+        if index >= self.len {
+            return None;
+        }
+
+        Some(&self.bytes[self.range(index)])
+    }
+
+    /// Returns the range of `bytes` containing the encoded entry represented by the
+    /// given `u32`.
+    #[inline]
+    fn range(&self, index: u32) -> Range<usize> {
+        let start = index as usize * self.size;
+        start..start + self.size
+    }
+
+    /// Iterates over the encoded entries ordered by the `u32` which represent them.
+    #[inline]
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (u32, &[u8])> {
+        (0..self.len).map(|index| (index, &self.bytes[self.range(index)]))
+    }
+}
+
+impl Decode for Raw {
+    type Entry = Vec<u8>;
+
+    #[inline]
+    fn decode(
+        &self,
+        reader: &mut storage::Reader,
+        _: &LinkContext,
+    ) -> impl Future<Output = Result<Vec<u8>>> + Send {
+        reader.read_vec(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Entry,
+        tests::{U64, block_on, chain, extend, memory, snapshot},
+    };
+
+    use super::*;
+
+    #[test]
+    fn reads_raw_entries() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..3]).await?;
+            let snapshot = snapshot(&storage, links[1], 3..5).await?;
+            let latest = extend(&storage, Some(snapshot), Some(5..6)).await?[0];
+
+            let reader = RawReader::open(latest, storage, U64::SIZE).await?;
+            assert_eq!(reader.latest(), latest);
+            assert_eq!(reader.current_index(), ChainIndex::new(3));
+            assert_eq!((reader.len(), reader.entry_size()), (6, 8));
+            assert_eq!(reader.get_at(4), Some(&4u64.to_be_bytes()[..]));
+            assert_eq!(reader.get_at(6), None);
+
+            let entries = reader.iter().map(|(_, bytes)| bytes.try_into().unwrap());
+            assert!(entries.map(u64::from_be_bytes).eq(0..6));
+
+            Ok(())
+        })
+    }
+}
@@ -1,6 +1,8 @@
+use std::io::SeekFrom;
+
 use crate::{
-    Error, LinkId, Result,
-    storage::{self, Reader, Writer},
+    ChainIndex, Error, LinkId, Result,
+    storage::{self, Encryption, Reader, Writer},
 };
 
 /// The footer of a snapshot file, containing information about it.
@@ -16,12 +18,31 @@ use crate::{
 ///    `None`, and the `u128` otherwise represents a UUID.
 /// 2. `index`, encoded in big-endian order.
 /// 3. `count`, encoded in big-endian order.
-/// 4. `VERSION`, encoded in big-endian order.
+/// 4. `base`, encoded like `previous`.
+/// 5. `base_count`, encoded in big-endian order.
+/// 6. `sorted`, encoded as a single byte which is `1` if `sorted` is `true` and `0`
+///    otherwise.
+/// 7. `checksum_block`, encoded in big-endian order.
+/// 8. `prefix`, encoded in big-endian order.
+/// 9. `prefix_count`, encoded in big-endian order.
+/// 10. how the rest of the file is encrypted, as the ID of the encryption scheme
+///     encoded as a single byte (where `0` represents none), followed by the nonce
+///     used to encrypt it (or 12 zeros).
+/// 11. `MAGIC`, stored as-is.
+/// 12. `VERSION`, encoded in big-endian order.
+///
+/// Version `0` of the storage format only stored `previous`, `index`, `count` and
+/// `VERSION` (i.e. all snapshots were full, unsorted and unencrypted snapshots without
+/// checksums, which weren't appended to the previous link's snapshot).
 ///
-/// `VERSION` is stored last to make sure that if we add more fields in later
-/// versions of the storage format, the version that was used to encode a file is
-/// stored at the same offset from the end of the file, to make sure that we detect
-/// any incompabilities when trying to decode a snapshot.
+/// `MAGIC` allows detecting when a file which isn't a snapshot file (e.g. a delta
+/// file or an object which wasn't written by this crate) is being read as one.
+///
+/// `MAGIC` and `VERSION` are stored last to make sure that if we add more fields in
+/// later versions of the storage format, they are stored at the same offset from
+/// the end of the file, to make sure that we detect any incompabilities when trying
+/// to decode a snapshot.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Footer {
     /// The ID of the previous link which this link extends.
     pub previous: Option<LinkId>,
@@ -29,44 +50,175 @@ pub struct Footer {
     /// The index of this link in the chain of links.
     ///
     /// This is equal to the number of previous links in the chain.
-    pub index: u32,
+    pub index: ChainIndex,
 
     /// The number of entries present in the link's snapshot.
     ///
     /// This is equal to the number of entries present in this link's delta as well as
     /// all of the previous links'.
     pub count: u32,
+
+    /// The ID of the link whose (full) snapshot contains the first `base_count` entries
+    /// of this snapshot, if this is an incremental snapshot.
+    ///
+    /// Incremental snapshots only store the entries which aren't present in their base,
+    /// and their base is always a full snapshot, so that loading an incremental
+    /// snapshot never requires reading more than two snapshot files.
+    pub base: Option<LinkId>,
+
+    /// The number of entries which are stored in the snapshot of `base` rather than in
+    /// this one.
+    ///
+    /// This is `0` for full snapshots.
+    pub base_count: u32,
+
+    /// Whether the entries are stored sorted rather than ordered by the `u32` which
+    /// represent them.
+    ///
+    /// Sorted snapshots are always full snapshots, and store, between their entries
+    /// and their footer, the position at which each entry is stored (as a `u32` encoded
+    /// in big-endian order), ordered by the `u32` which represent them.
+    pub sorted: bool,
+
+    /// The number of entries covered by each of the checksums stored in the snapshot,
+    /// or `0` if it doesn't store any.
+    ///
+    /// Snapshots storing checksums store, between their entries and their footer, the
+    /// CRC-32 of the encoded entries of each block of `checksum_block` entries (the
+    /// last block possibly containing less entries), as `u32`s encoded in big-endian
+    /// order. This allows localizing corrupted entries when verifying the snapshot.
+    pub checksum_block: u32,
+
+    /// The size of the previous link's snapshot file, if this is an appended snapshot,
+    /// or `0` otherwise.
+    ///
+    /// Appended snapshots start with the previous link's snapshot file stored as-is
+    /// (including its footer, which itself might be the footer of an appended
+    /// snapshot), followed by the entries which were inserted since. This allows
+    /// writing them without decoding or reordering anything, and even without copying
+    /// anything if the storage can copy files and append to them. `prefix` is thus
+    /// the offset at which the entries stored in this snapshot start, and the offset
+    /// at which the footer of the previous link's snapshot ends.
+    pub prefix: u64,
+
+    /// The number of entries which are stored in the previous link's snapshot at the
+    /// start of the file, rather than after it.
+    ///
+    /// This is `0` unless this is an appended snapshot.
+    pub prefix_count: u32,
+
+    /// The version of the storage format which was used to encode the file.
+    pub version: u16,
 }
 
 impl Footer {
     /// The expected size of the footer of a snapshot file.
     ///
     /// Future storage formats might have a bigger footer than this value.
-    pub const SIZE: usize = 26; // 16 + 2 * 4 + 2
+    pub const SIZE: usize = 80; // 16 + 2 * 4 + 16 + 4 + 1 + 4 + 8 + 4 + 13 + 4 + 2
+
+    /// The magic bytes stored in the footer of a snapshot file.
+    pub const MAGIC: [u8; 4] = *b"CDS\0";
+
+    /// Returns the size of the footer of a file encoded with the given version of the
+    /// storage format.
+    #[inline]
+    const fn size(version: u16) -> usize {
+        match version {
+            0 => 26, // 16 + 2 * 4 + 2
+            _ => Self::SIZE,
+        }
+    }
 
     /// Reads the [`Footer`] supposedly stored at the end of the file being read by
     /// `reader`.
     ///
-    /// This updates the `reader` so that it will act as-if the footer did not exist.
+    /// This supports all of the versions of the storage format since
+    /// `storage::MIN_VERSION` (or `storage::LEGACY_VERSION` if the storage opted into
+    /// it), decoding the footer according to the version the file was encoded with.
+    ///
+    /// This updates the `reader` so that it will act as-if the footer (and the
+    /// checksums stored before it, if any) did not exist.
     pub async fn read(reader: &mut Reader) -> Result<Self> {
-        if reader.file_size() < Self::SIZE {
+        let (footer, encryption) = Self::decode(reader).await?;
+
+        let end = reader.file_size() - Self::size(footer.version);
+        reader.set_file_size(end);
+        reader.seek(SeekFrom::Start(0))?;
+        reader.unseal(encryption).await?;
+
+        // The checksums are part of the (possibly encrypted) content of the file.
+        let checksums_size = footer.checksums() * 4;
+        if reader.file_size() < checksums_size {
+            return Err(Error::FileSize {
+                expected: checksums_size,
+                got: reader.file_size(),
+            });
+        }
+
+        reader.set_file_size(reader.file_size() - checksums_size);
+
+        // The entries stored in appended snapshots start after the previous link's
+        // snapshot.
+        if footer.prefix > 0 {
+            reader.seek(SeekFrom::Start(footer.prefix))?;
+        }
+
+        Ok(footer)
+    }
+
+    /// Decodes the [`Footer`] supposedly stored at the end of the file being read by
+    /// `reader`, along with how the rest of the file is encrypted, without updating the
+    /// `reader` to act as-if the footer did not exist (see [`read()`][1]).
+    ///
+    /// This only reads the end of the file, which means that `reader` can also read
+    /// only the last bytes of the file.
+    ///
+    /// [1]: Self::read()
+    pub(crate) async fn decode(reader: &mut Reader) -> Result<(Self, Option<Encryption>)> {
+        // Files smaller than the smallest footer can't be decoded, whatever their version.
+        let min_size = Self::size(storage::LEGACY_VERSION);
+        if reader.file_size() < min_size {
             return Err(Error::FileSize {
-                expected: Self::SIZE,
+                expected: min_size,
                 got: reader.file_size(),
             });
         }
 
-        reader.goto(-2)?;
+        // The version is always stored last, whatever the version of the storage format,
+        // which allows us to decode each file according to its own version.
+        reader.seek(SeekFrom::End(-2))?;
         let version = reader.read_u16().await?;
 
-        if version != storage::VERSION {
+        if !(reader.min_version()..=storage::VERSION).contains(&version) {
             return Err(Error::Version {
                 expected: storage::VERSION,
                 got: version,
             });
         }
 
-        reader.goto(-(Self::SIZE as isize))?;
+        let size = Self::size(version);
+        if reader.file_size() < size {
+            return Err(Error::FileSize {
+                expected: size,
+                got: reader.file_size(),
+            });
+        }
+
+        // Version `0` of the storage format didn't store any magic bytes.
+        if version >= 1 {
+            reader.seek(SeekFrom::End(-6))?;
+            let magic = reader.read_bytes().await?;
+
+            if magic != Self::MAGIC {
+                return Err(Error::BadMagic {
+                    expected: Self::MAGIC,
+                    got: magic,
+                });
+            }
+        }
+
+        reader.seek(SeekFrom::End(-(size as i64)))?;
 
         let previous = reader.read_u128().await?;
         let previous = if previous == 0 {
@@ -75,34 +227,222 @@ impl Footer {
             Some(LinkId::from_u128(previous))
         };
 
-        let index = reader.read_u32().await?;
+        let index = ChainIndex::new(reader.read_u32().await?);
         let count = reader.read_u32().await?;
 
-        let end = reader.file_size() - Self::SIZE;
-        reader.set_file_size(end);
-
-        Ok(Self {
+        let mut footer = Self {
             previous,
             index,
             count,
-        })
+            base: None,
+            base_count: 0,
+            sorted: false,
+            checksum_block: 0,
+            prefix: 0,
+            prefix_count: 0,
+            version,
+        };
+
+        // Version `0` of the storage format didn't store any of the other fields.
+        if version == 0 {
+            return Ok((footer, None));
+        }
+
+        let base = reader.read_u128().await?;
+        if base != 0 {
+            footer.base = Some(LinkId::from_u128(base));
+        }
+
+        footer.base_count = reader.read_u32().await?;
+
+        let [sorted] = reader.read_bytes().await?;
+        footer.sorted = sorted != 0;
+
+        footer.checksum_block = reader.read_u32().await?;
+        footer.prefix = reader.read_u64().await?;
+        footer.prefix_count = reader.read_u32().await?;
+
+        let encryption = Encryption::read(reader).await?;
+
+        Ok((footer, encryption))
     }
 
-    /// Writes the [`Footer`] to the writer.
+    /// Returns the number of checksums stored in the snapshot.
+    #[inline]
+    pub fn checksums(&self) -> usize {
+        if self.checksum_block == 0 {
+            return 0;
+        }
+
+        let stored = self
+            .count
+            .saturating_sub(self.base_count)
+            .saturating_sub(self.prefix_count);
+        stored.div_ceil(self.checksum_block) as usize
+    }
+
+    /// Writes the [`Footer`] to the writer, encoding it according to its `version`.
+    ///
+    /// If the file is encrypted, this first encrypts all of the content written to it.
+    ///
+    /// Fails if the snapshot uses features which `version` doesn't support (e.g. if it
+    /// is an incremental snapshot but `version` is `0`).
     pub async fn write(&self, writer: &mut Writer) -> Result<()> {
+        let encryption = writer.seal().await?;
+        let bytes = self.encode(encryption)?;
+
+        writer.write_slice(&bytes).await
+    }
+
+    /// Returns the bytes stored at the end of an unencrypted snapshot file with this
+    /// [`Footer`], which only depend on its fields.
+    ///
+    /// Fails like [`write()`][1] does.
+    ///
+    /// [1]: Self::write()
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        self.encode(None)
+    }
+
+    /// Encodes the [`Footer`] according to its `version`, for a file whose content was
+    /// encrypted as described by `encryption`.
+    fn encode(&self, encryption: Option<Encryption>) -> Result<Vec<u8>> {
         let Self {
             previous,
             index,
             count,
+            base,
+            base_count,
+            sorted,
+            checksum_block,
+            prefix,
+            prefix_count,
+            version,
         } = self;
 
         let previous = previous.as_ref().map(LinkId::as_u128).unwrap_or_default();
+        let base = base.as_ref().map(LinkId::as_u128).unwrap_or_default();
+
+        // The fields which older versions of the storage format didn't store must have
+        // the value they implied.
+        if *version < 1 && (base != 0 || *sorted || *checksum_block != 0 || *prefix != 0) {
+            return Err(Error::Unsupported {
+                operation: "snapshot feature with this version of the storage format",
+            });
+        }
+
+        if *version < 1 && encryption.is_some() {
+            return Err(Error::Unsupported {
+                operation: "encryption with this version of the storage format",
+            });
+        }
+
+        let mut bytes = Vec::with_capacity(Self::size(*version));
+
+        bytes.extend_from_slice(&previous.to_be_bytes());
+        bytes.extend_from_slice(&index.get().to_be_bytes());
+        bytes.extend_from_slice(&count.to_be_bytes());
+
+        if *version >= 1 {
+            bytes.extend_from_slice(&base.to_be_bytes());
+            bytes.extend_from_slice(&base_count.to_be_bytes());
+            bytes.push(*sorted as u8);
+            bytes.extend_from_slice(&checksum_block.to_be_bytes());
+            bytes.extend_from_slice(&prefix.to_be_bytes());
+            bytes.extend_from_slice(&prefix_count.to_be_bytes());
+            Encryption::encode(encryption, &mut bytes);
+            bytes.extend_from_slice(&Self::MAGIC);
+        }
+
+        bytes.extend_from_slice(&version.to_be_bytes());
+
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{block_on, memory};
+
+    use super::*;
+
+    fn footer(version: u16) -> Footer {
+        Footer {
+            previous: Some(LinkId::from_u128(42)),
+            index: ChainIndex::new(3),
+            count: 10,
+            base: None,
+            base_count: 0,
+            sorted: false,
+            checksum_block: 0,
+            prefix: 0,
+            prefix_count: 0,
+            version,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let full = Footer {
+            base: Some(LinkId::from_u128(7)),
+            base_count: 6,
+            sorted: true,
+            checksum_block: 2,
+            prefix: 123,
+            prefix_count: 1,
+            ..footer(storage::VERSION)
+        };
+
+        let storage = memory().with_legacy_format();
+        for footer in [
+            footer(storage::LEGACY_VERSION),
+            footer(storage::VERSION),
+            full,
+        ] {
+            let bytes = footer.to_bytes().unwrap();
+            assert_eq!(bytes.len(), Footer::size(footer.version));
+
+            let mut reader = storage.open_slice(&bytes);
+            assert_eq!(block_on(Footer::decode(&mut reader)).unwrap().0, footer);
+        }
+    }
+
+    #[test]
+    fn version_0_only_supports_full_snapshots() {
+        let incremental = Footer {
+            base: Some(LinkId::from_u128(7)),
+            base_count: 6,
+            ..footer(storage::LEGACY_VERSION)
+        };
+
+        let error = incremental.to_bytes().unwrap_err();
+        assert!(matches!(error, Error::Unsupported { .. }));
+    }
+
+    #[test]
+    fn legacy_version_requires_opt_in() {
+        let bytes = footer(storage::LEGACY_VERSION).to_bytes().unwrap();
+
+        let mut reader = memory().open_slice(&bytes);
+        let error = block_on(Footer::read(&mut reader)).unwrap_err();
+        assert!(error.is_version_mismatch());
+    }
+
+    #[test]
+    fn delta_read_as_snapshot() {
+        let delta = crate::DFooter {
+            previous: None,
+            index: ChainIndex::new(0),
+            total: 1,
+            count: 1,
+            version: storage::VERSION,
+        };
 
-        writer.write_u128(previous).await?;
-        writer.write_u32(*index).await?;
-        writer.write_u32(*count).await?;
-        writer.write_u16(storage::VERSION).await?;
+        let mut bytes = vec![0; Footer::SIZE];
+        bytes.extend(delta.to_bytes().unwrap());
 
-        Ok(())
+        let mut reader = Reader::from_slice(&bytes);
+        let error = block_on(Footer::read(&mut reader)).unwrap_err();
+        assert!(matches!(error, Error::BadMagic { .. }));
     }
 }
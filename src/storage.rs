@@ -1,23 +1,179 @@
 use std::{
     fmt::{self, Display, Formatter},
+    io::SeekFrom,
     ops::Range,
+    pin::pin,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime},
 };
 
-use futures::prelude::*;
-use opendal::{ErrorKind, Operator};
+use futures::{
+    future::{Either, select},
+    prelude::*,
+};
+use futures_timer::Delay;
+use opendal::{Buffer, BufferStream, Capability, ErrorKind, Metadata, Operator};
+use uuid::Uuid;
+
+use crate::{
+    ChainIndex, DFooter, Entry, Error, LinkContext, LinkId, Result, SFooter,
+    reader::{read_entry, read_snapshot},
+};
 
-use crate::{Error, LinkId, Result};
+#[cfg(feature = "encryption")]
+use self::encryption::Cipher;
+
+#[cfg(feature = "encryption")]
+mod encryption;
 
 #[derive(Clone, Copy, Debug)]
 pub enum Kind {
     Delta,
     Snapshot,
+    Values,
+}
+
+/// Metadata about the file of a link, as returned by [`Storage::stat_link()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinkStat {
+    /// The size of the file, in bytes.
+    pub content_length: u64,
+
+    /// When the file was last modified, if the storage reports it.
+    pub last_modified: Option<SystemTime>,
+
+    /// The entity tag of the file, if the storage reports it.
+    pub etag: Option<String>,
+}
+
+/// The information stored in the footer of a link's file, whatever its kind, as
+/// returned by [`Storage::read_footer()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FooterInfo {
+    /// The ID of the previous link which the link extends.
+    pub previous: Option<LinkId>,
+
+    /// The index of the link in the chain.
+    pub index: ChainIndex,
+
+    /// The number of entries in the chain up to the link (included).
+    pub total: u32,
+
+    /// The number of entries (or values) stored in the file itself.
+    ///
+    /// For delta and values files, this is the number of entries introduced by the
+    /// link. For snapshot files, this excludes the entries stored in the snapshot of
+    /// the base of incremental snapshots.
+    pub count: u32,
+
+    /// The version of the storage format which was used to encode the file.
+    pub version: u16,
+}
+
+/// The capabilities of a storage which some of the features of the crate depend on, as
+/// returned by [`Storage::capabilities()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Whether files can be renamed, which two-phase commits rely on (see
+    /// [`Writer::create_two_phase()`][1]).
+    ///
+    /// [1]: crate::Writer::create_two_phase()
+    pub rename: bool,
+
+    /// Whether files can be copied and appended to, which allows appended snapshots to
+    /// not read the previous link's snapshot (see
+    /// [`Writer::with_appended_snapshot()`][1]).
+    ///
+    /// [1]: crate::Writer::with_appended_snapshot()
+    pub append: bool,
+
+    /// Whether files can be deleted, which discarding the links of a [`Batch`][1] relies
+    /// on.
+    ///
+    /// [1]: crate::Batch
+    pub delete: bool,
+
+    /// Whether the links' files can be listed (see [`Storage::list_links()`]).
+    pub list: bool,
+
+    /// Whether reading files can be presigned, i.e. whether URLs allowing to read them
+    /// without credentials can be generated.
+    pub presign: bool,
+
+    /// Whether reading files can be conditional on their entity tag (see
+    /// [`LinkStat::etag`]).
+    pub conditional_read: bool,
 }
 
+/// A function returning the path (relative to the base path) of the file of the given
+/// kind for the link with the given ID.
+type PathFn = Arc<dyn Fn(LinkId, Kind) -> String + Send + Sync>;
+
+/// A function decompressing the whole content of a file which was compressed as a
+/// whole (see [`Storage::with_decompressor()`]).
+type Decompress = Arc<dyn Fn(&[u8]) -> std::io::Result<Vec<u8>> + Send + Sync>;
+
 #[derive(Clone)]
 pub struct Storage {
     base: Option<String>,
     operator: Operator,
+
+    /// The function used to get the path of the links' files, if the default layout
+    /// (i.e. `{id}.{kind}`) isn't used.
+    path_fn: Option<PathFn>,
+
+    /// The storage in which the links' files which don't exist in this one are looked
+    /// up, if any.
+    fallback: Option<Arc<Storage>>,
+
+    /// How long each operation issued to the storage can take before failing with
+    /// [`Error::Timeout`], if it can't take forever.
+    timeout: Option<Duration>,
+
+    /// How reading an entry is retried when it fails transiently, if it is.
+    retries: Option<Retries>,
+
+    /// The maximum number of chunks read concurrently, along with the size of those
+    /// chunks, if large reads are split into chunks.
+    read_concurrency: Option<(usize, usize)>,
+
+    /// The functions used to decompress the files which were compressed as a whole,
+    /// along with the name of the compression they decompress.
+    decompressors: Vec<(String, Decompress)>,
+
+    /// Whether files encoded with [`LEGACY_VERSION`] of the storage format can be read.
+    legacy: bool,
+
+    /// The cipher used to encrypt the content of the files written and decrypt the
+    /// content of the encrypted files read, if any.
+    #[cfg(feature = "encryption")]
+    cipher: Option<Arc<Cipher>>,
+}
+
+/// How reading an entry is retried when it fails transiently (see
+/// [`Storage::with_entry_retries()`]).
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Retries {
+    /// The maximum number of times reading an entry is retried.
+    pub(crate) max: u32,
+
+    /// How long to wait before retrying for the first time, which is doubled before
+    /// each subsequent retry.
+    pub(crate) backoff: Duration,
+}
+
+/// How the content of a file (i.e. everything but its footer) was encrypted.
+///
+/// This is stored in footers as follows:
+/// 1. the ID of the encryption scheme, encoded as a single byte, where `0` represents
+///    `None`; and
+/// 2. the nonce used to encrypt the content, stored as-is (or as zeros if the content
+///    isn't encrypted).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Encryption {
+    /// The content was encrypted with ChaCha20-Poly1305 using the given nonce, and is
+    /// followed by its authentication tag.
+    ChaCha20Poly1305 { nonce: [u8; 12] },
 }
 
 /// A reader for a file which exists in some storage.
@@ -31,8 +187,59 @@ pub struct Reader {
     /// to simplify reading all of a file's content but its footer).
     file_size: usize,
 
-    /// The raw reader this is reading from.
-    reader: opendal::Reader,
+    /// The number of bytes which have been read from the file so far.
+    bytes_read: u64,
+
+    /// Where the content of the file is read from.
+    source: Source,
+
+    /// How long each read can take before failing, if it can't take forever.
+    timeout: Option<Duration>,
+
+    /// The content of the file being streamed from the storage, if it is being read
+    /// sequentially (see [`read_sequentially()`][1]).
+    ///
+    /// [1]: Self::read_sequentially()
+    sequential: Option<Sequential>,
+
+    /// Whether the file can be encoded with [`LEGACY_VERSION`] of the storage format.
+    legacy: bool,
+
+    /// The cipher used to decrypt the content of the file, if any.
+    #[cfg(feature = "encryption")]
+    cipher: Option<Arc<Cipher>>,
+}
+
+/// The content of a file being read sequentially by a [`Reader`], which is streamed
+/// from the storage.
+struct Sequential {
+    /// The stream returning the content of the file which follows `buffer`, once the
+    /// first read started it.
+    ///
+    /// The stream isn't [`Sync`], which would make readers (and the futures using them)
+    /// not [`Sync`] either. It is only ever accessed mutably, which never locks.
+    stream: Option<Mutex<BufferStream>>,
+
+    /// The content which was returned by `stream` but which hasn't been read yet.
+    buffer: Buffer,
+
+    /// The position inside of the file at which `buffer` starts.
+    offset: usize,
+
+    /// The position inside of the file at which the stream ends.
+    end: usize,
+}
+
+/// Where the content of a file being read by a [`Reader`] comes from.
+enum Source {
+    /// The content is read from the storage using the given raw reader.
+    Storage(opendal::Reader),
+
+    /// The content is kept in memory.
+    ///
+    /// This is the case for files read from a slice, and for encrypted files, which are
+    /// decrypted all at once when their footer is read.
+    Memory(Vec<u8>),
 }
 
 /// A writer for a file which was created in some storage.
@@ -42,13 +249,57 @@ pub struct Writer {
 
     /// The number of bytes which have been written to the file so far..
     file_size: usize,
+
+    /// How long each write can take before failing, if it can't take forever.
+    timeout: Option<Duration>,
+
+    /// The content written to the file so far, if it is encrypted, along with the
+    /// cipher used to encrypt it once it is [sealed][1].
+    ///
+    /// [1]: Self::seal()
+    #[cfg(feature = "encryption")]
+    encrypted: Option<(Arc<Cipher>, Vec<u8>)>,
+
+    /// The checksums of the blocks written to the file so far, if checksums are
+    /// computed for it.
+    checksums: Option<Checksums>,
+}
+
+/// The CRC-32 checksums of consecutive fixed-size blocks of the content written to a
+/// file.
+struct Checksums {
+    /// The number of bytes in each block.
+    block_size: usize,
+
+    /// The hasher for the block currently being written.
+    hasher: crc32fast::Hasher,
+
+    /// The number of bytes written to the block currently being written.
+    len: usize,
+
+    /// The checksums of all of the blocks which were fully written.
+    checksums: Vec<u32>,
 }
 
 /// The (currently) latest version of the storage format.
 ///
 /// This is used to make the storage format backward compatible at best, or to
 /// fail on incompatibilities at worst.
-pub(crate) const VERSION: u16 = 0;
+pub const VERSION: u16 = 1;
+
+/// The oldest version of the storage format which can still be read.
+///
+/// Each file is decoded according to the version it was encoded with, meaning that a
+/// chain can contain links encoded with different versions of the storage format.
+pub const MIN_VERSION: u16 = 1;
+
+/// The version of the storage format which predates [`MIN_VERSION`], and which can
+/// only be read by storages which opted into it (see
+/// [`Storage::with_legacy_format()`]).
+///
+/// Files encoded with it don't store any magic bytes, meaning that any object ending
+/// with two zero bytes would otherwise be decoded as a link's file.
+pub const LEGACY_VERSION: u16 = 0;
 
 impl Storage {
     /// Creates a new [`Storage`] from the given [`Operator`].
@@ -56,6 +307,16 @@ impl Storage {
         Self {
             base: None,
             operator,
+            path_fn: None,
+            fallback: None,
+            timeout: None,
+            retries: None,
+            read_concurrency: None,
+            decompressors: Vec::new(),
+            legacy: false,
+
+            #[cfg(feature = "encryption")]
+            cipher: None,
         }
     }
 
@@ -65,9 +326,172 @@ impl Storage {
         Self {
             base: Some(base.into()),
             operator,
+            path_fn: None,
+            fallback: None,
+            timeout: None,
+            retries: None,
+            read_concurrency: None,
+            decompressors: Vec::new(),
+            legacy: false,
+
+            #[cfg(feature = "encryption")]
+            cipher: None,
         }
     }
 
+    /// Uses the given function to get the path of the file of the given kind for the
+    /// link with the given ID, relative to the base path, instead of `{id}.{kind}`.
+    ///
+    /// This allows using custom layouts, for instance sharding the files by the first
+    /// characters of their link's ID to avoid hot prefixes:
+    ///
+    /// ```
+    /// # use chaindict::storage::Storage;
+    /// # use opendal::{Operator, services::Memory};
+    /// # let operator = Operator::new(Memory::default()).unwrap().finish();
+    /// let storage = Storage::new_in("chains", operator).with_path_fn(|id, kind| {
+    ///     let id = id.to_string();
+    ///     format!("{}/{id}.{kind}", &id[..2])
+    /// });
+    /// ```
+    ///
+    /// The name of each file must still be `{id}.{kind}` for [`list_links()`][1] to
+    /// find it.
+    ///
+    /// [1]: Self::list_links()
+    pub fn with_path_fn<F>(mut self, path_fn: F) -> Self
+    where
+        F: Fn(LinkId, Kind) -> String + Send + Sync + 'static,
+    {
+        self.path_fn = Some(Arc::new(path_fn));
+        self
+    }
+
+    /// Looks up the links' files which don't exist in this storage in `fallback` (and
+    /// then in its own fallback, if any).
+    ///
+    /// This allows creating links which extend links stored under a different base
+    /// path (or even in a different storage backend): since link IDs are random UUIDs,
+    /// a link can be found in any storage without any risk of confusing it with
+    /// another one. Files are only ever written to this storage.
+    ///
+    /// Note that this makes looking up files which don't exist (e.g. optional snapshot
+    /// files) require one more request per fallback.
+    pub fn with_fallback(mut self, fallback: Storage) -> Self {
+        self.fallback = Some(Arc::new(fallback));
+        self
+    }
+
+    /// Fails with [`Error::Timeout`] whenever an operation issued to the storage (e.g.
+    /// opening, reading, writing or listing files) takes longer than `timeout`, instead
+    /// of possibly waiting forever (e.g. for a stuck request).
+    ///
+    /// Since files are never modified once they have been written, operations which
+    /// timed out can be retried safely.
+    pub fn with_operation_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Retries reading an entry up to `max_retries` times when it fails transiently (e.g.
+    /// because of a network blip or a timeout), waiting for `backoff` before the first
+    /// retry and doubling it before each subsequent one.
+    ///
+    /// Only the read of the failed entry is retried, rather than the whole operation,
+    /// so that a single blip doesn't require reading all of the entries which were
+    /// already read again. Reading footers isn't retried. If reading the entry still
+    /// fails after all of the retries, this fails with [`Error::EntryRead`].
+    pub fn with_entry_retries(mut self, max_retries: u32, backoff: Duration) -> Self {
+        self.retries = Some(Retries {
+            max: max_retries,
+            backoff,
+        });
+
+        self
+    }
+
+    /// Splits reads of more than `chunk_size` bytes into chunks of `chunk_size` bytes,
+    /// reading up to `concurrency` of them concurrently.
+    ///
+    /// This mostly speeds up copying the content of large files (e.g. the snapshot of
+    /// the previous link, when writing a snapshot with [`Writer::with_snapshot()`][1])
+    /// on storages where a single request can't saturate the available bandwidth.
+    ///
+    /// The chunks are read by tasks spawned on the operator's executor (which is Tokio's
+    /// unless another one was configured), so reading files panics outside of its
+    /// runtime.
+    ///
+    /// [1]: crate::Writer::with_snapshot()
+    pub fn with_read_concurrency(mut self, concurrency: usize, chunk_size: usize) -> Self {
+        self.read_concurrency = Some((concurrency.max(1), chunk_size.max(1)));
+        self
+    }
+
+    /// Decompresses the files which were compressed as a whole (including their
+    /// footer, e.g. by the tool which uploaded them) with the compression named `name`
+    /// using `decompress`, which is given the whole compressed content of a file.
+    ///
+    /// Such files are read when their content is encoded with `name` (i.e. their
+    /// `Content-Encoding` is `name`), or when the file of a link doesn't exist but the
+    /// same path with the `.{name}` suffix does (e.g. `{id}.delta.zst` when `name` is
+    /// `zst`). This crate doesn't depend on any compression library, so `decompress`
+    /// needs to be provided, e.g. with the `zstd` crate:
+    ///
+    /// ```
+    /// # use chaindict::storage::Storage;
+    /// # use opendal::{Operator, services::Memory};
+    /// # mod zstd {
+    /// #     pub fn decode_all(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    /// #         Ok(bytes.to_vec())
+    /// #     }
+    /// # }
+    /// # let operator = Operator::new(Memory::default()).unwrap().finish();
+    /// let storage = Storage::new(operator).with_decompressor("zst", |bytes| zstd::decode_all(bytes));
+    /// ```
+    ///
+    /// Compressed files are read and decompressed all at once when they are opened,
+    /// which means that their whole content is kept in memory while they are read.
+    /// Looking up files which don't exist (e.g. optional snapshot files) requires one
+    /// more request for each decompressor. Files are never written compressed.
+    pub fn with_decompressor<F>(mut self, name: impl Into<String>, decompress: F) -> Self
+    where
+        F: Fn(&[u8]) -> std::io::Result<Vec<u8>> + Send + Sync + 'static,
+    {
+        self.decompressors.push((name.into(), Arc::new(decompress)));
+        self
+    }
+
+    /// Returns how reading an entry is retried when it fails transiently, if it is.
+    #[inline]
+    pub(crate) fn retries(&self) -> Option<Retries> {
+        self.retries
+    }
+
+    /// Encrypts the content of all of the files written using ChaCha20-Poly1305 with
+    /// the given key, and decrypts the content of the encrypted files read.
+    ///
+    /// Only the entries are encrypted – footers are stored in plaintext, along with the
+    /// nonce used to encrypt each file. Files are encrypted and decrypted all at once,
+    /// which means that their whole content is kept in memory while they are written or
+    /// read.
+    ///
+    /// Files which aren't encrypted can still be read.
+    #[cfg(feature = "encryption")]
+    pub fn with_encryption(mut self, key: [u8; 32]) -> Self {
+        self.cipher = Some(Arc::new(Cipher::new(key)));
+        self
+    }
+
+    /// Allows reading files encoded with [`LEGACY_VERSION`] of the storage format,
+    /// which are otherwise rejected with [`Error::Version`].
+    ///
+    /// Those files don't store any magic bytes, so this should only be used for
+    /// storages which only contain links' files.
+    pub fn with_legacy_format(mut self) -> Self {
+        self.legacy = true;
+        self
+    }
+
     /// Opens the file of the given kind for the link with the given ID, returning a
     /// reader for it.
     ///
@@ -79,59 +503,809 @@ impl Storage {
     }
 
     /// Opens the file of the given kind for the link with the given ID, returning a
-    /// reader for it, if it exists.
+    /// reader for it, if it exists either in this storage or in one of its fallbacks.
     ///
     /// Returns `None` if the file does not exist.
     pub(crate) async fn open_maybe(&self, id: LinkId, kind: Kind) -> Result<Option<Reader>> {
-        let path = self.path(id, kind);
+        let mut storage = self;
 
-        let metadata = match self.operator.stat(&path).await {
-            Ok(metadata) => metadata,
-            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
-            Err(error) => return Err(error.into()),
+        loop {
+            if let Some(reader) = storage.open_here(id, kind).await? {
+                return Ok(Some(reader));
+            }
+
+            let Some(fallback) = &storage.fallback else {
+                return Ok(None);
+            };
+
+            storage = fallback;
+        }
+    }
+
+    /// Opens the file of the given kind for the link with the given ID, returning a
+    /// reader for it, if it exists in this storage (ignoring its fallbacks).
+    async fn open_here(&self, id: LinkId, kind: Kind) -> Result<Option<Reader>> {
+        self.require("read", |capability| capability.read)?;
+
+        let path = self.path(id, kind);
+        let Some(metadata) = self.stat_here(id, kind).await? else {
+            return self.open_compressed(&path).await;
         };
 
+        let encoding = metadata.content_encoding();
+        let decompressor = self
+            .decompressors
+            .iter()
+            .find(|(name, _)| Some(&**name) == encoding);
+        if let Some((_, decompress)) = decompressor {
+            return self.open_decompressed(&path, decompress).await.map(Some);
+        }
+
         let file_size = metadata.content_length() as usize;
-        // TODO(MLB): configure the reader?
-        let reader = self.operator.reader(&path).await?;
+
+        let mut reader = self.operator.reader_with(&path);
+        if let Some((concurrency, chunk_size)) = self.read_concurrency {
+            reader = reader.concurrent(concurrency).chunk(chunk_size);
+        }
+
+        let reader = timed(self.timeout, "open", reader.into_future()).await?;
 
         Ok(Some(Reader {
             offset: 0,
             file_size,
-            reader,
+            bytes_read: 0,
+            source: Source::Storage(reader),
+            timeout: self.timeout,
+            sequential: None,
+            legacy: self.legacy,
+
+            #[cfg(feature = "encryption")]
+            cipher: self.cipher.clone(),
         }))
     }
 
+    /// Opens the file at `path` with the suffix of one of the compressions for which a
+    /// decompressor was provided, returning a reader for its decompressed content, if
+    /// such a file exists.
+    async fn open_compressed(&self, path: &str) -> Result<Option<Reader>> {
+        for (name, decompress) in &self.decompressors {
+            let path = format!("{path}.{name}");
+            match timed(self.timeout, "stat", self.operator.stat(&path)).await {
+                Ok(_) => return self.open_decompressed(&path, decompress).await.map(Some),
+                Err(Error::Storage(error)) if error.kind() == ErrorKind::NotFound => {}
+                Err(error) => return Err(error),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Reads the whole content of the file at `path` and decompresses it with
+    /// `decompress`, returning a reader for the decompressed content.
+    async fn open_decompressed(&self, path: &str, decompress: &Decompress) -> Result<Reader> {
+        let compressed = timed(self.timeout, "read", self.operator.read(path)).await?;
+        let content = decompress(&compressed.to_vec())?;
+
+        Ok(Reader {
+            offset: 0,
+            file_size: content.len(),
+            bytes_read: compressed.len() as u64,
+            source: Source::Memory(content),
+            timeout: self.timeout,
+            sequential: None,
+            legacy: self.legacy,
+
+            #[cfg(feature = "encryption")]
+            cipher: self.cipher.clone(),
+        })
+    }
+
+    /// Returns metadata about the file of the given kind for the link with the given ID
+    /// (e.g. to find old links), without opening it.
+    ///
+    /// If the file doesn't exist in this storage but exists in one of its fallbacks,
+    /// this returns metadata about it in that fallback instead.
+    ///
+    /// Fails with [`Error::DoesNotExist`] if the file does not exist.
+    pub async fn stat_link(&self, id: LinkId, kind: Kind) -> Result<LinkStat> {
+        let mut storage = self;
+
+        let metadata = loop {
+            if let Some(metadata) = storage.stat_here(id, kind).await? {
+                break metadata;
+            }
+
+            let Some(fallback) = &storage.fallback else {
+                return Err(Error::DoesNotExist { link: id, kind });
+            };
+
+            storage = fallback;
+        };
+
+        Ok(LinkStat {
+            content_length: metadata.content_length(),
+            last_modified: metadata.last_modified().map(SystemTime::from),
+            etag: metadata.etag().map(str::to_owned),
+        })
+    }
+
+    /// Reads the footer of the file of the given kind for the link with the given ID,
+    /// without reading any of its entries.
+    ///
+    /// This only issues a single read of the end of the file (once it has been opened),
+    /// and doesn't decrypt the file, since footers are stored in plaintext.
+    ///
+    /// Fails with [`Error::DoesNotExist`] if the file does not exist.
+    pub async fn read_footer(&self, id: LinkId, kind: Kind) -> Result<FooterInfo> {
+        let mut reader = self.open(id, kind).await?;
+
+        let len = reader.file_size().min(DFooter::SIZE.max(SFooter::SIZE));
+        reader.seek(SeekFrom::End(-(len as i64)))?;
+        let mut tail = self.open_slice(&reader.read_vec(len).await?);
+
+        let info = match kind {
+            Kind::Delta | Kind::Values => {
+                let magic = match kind {
+                    Kind::Values => DFooter::VALUES_MAGIC,
+                    _ => DFooter::MAGIC,
+                };
+
+                let (footer, _) = DFooter::decode(&mut tail, magic).await?;
+                FooterInfo {
+                    previous: footer.previous,
+                    index: footer.index,
+                    total: footer.total,
+                    count: footer.count,
+                    version: footer.version,
+                }
+            }
+
+            Kind::Snapshot => {
+                let (footer, _) = SFooter::decode(&mut tail).await?;
+                FooterInfo {
+                    previous: footer.previous,
+                    index: footer.index,
+                    total: footer.count,
+                    count: footer.count.saturating_sub(footer.base_count),
+                    version: footer.version,
+                }
+            }
+        };
+
+        Ok(info)
+    }
+
+    /// Returns metadata about the file of the given kind for the link with the given
+    /// ID, if it exists in this storage (ignoring its fallbacks).
+    async fn stat_here(&self, id: LinkId, kind: Kind) -> Result<Option<Metadata>> {
+        self.require("stat", |capability| capability.stat)?;
+
+        let path = self.path(id, kind);
+        match timed(self.timeout, "stat", self.operator.stat(&path)).await {
+            Ok(metadata) => Ok(Some(metadata)),
+            Err(Error::Storage(error)) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Returns a reader for a file of this storage whose content is the given bytes
+    /// (e.g. because it was already loaded in memory).
+    pub(crate) fn open_slice(&self, bytes: &[u8]) -> Reader {
+        let mut reader = Reader::from_slice(bytes);
+        reader.legacy = self.legacy;
+
+        #[cfg(feature = "encryption")]
+        {
+            reader.cipher = self.cipher.clone();
+        }
+
+        reader
+    }
+
     /// Creates a file of the given kind for the link with the given ID, returning a
     /// writer for it.
+    #[inline]
     pub(crate) async fn create(&self, id: LinkId, kind: Kind) -> Result<Writer> {
-        let path = self.path(id, kind);
+        self.create_at(self.path(id, kind)).await
+    }
+
+    /// Creates a temporary file of the given kind for the link with the given ID,
+    /// returning a writer for it.
+    ///
+    /// Once written, the file must be moved to its final path with [`commit()`][1].
+    ///
+    /// [1]: Self::commit()
+    #[inline]
+    pub(crate) async fn create_temporary(&self, id: LinkId, kind: Kind) -> Result<Writer> {
+        self.create_at(self.temporary_path(id, kind)).await
+    }
+
+    /// Moves the temporary file of the given kind for the link with the given ID to
+    /// its final path.
+    pub(crate) async fn commit(&self, id: LinkId, kind: Kind) -> Result<()> {
+        self.require("rename", |capability| capability.rename)?;
+
+        let from = self.temporary_path(id, kind);
+        let to = self.path(id, kind);
+
+        timed(self.timeout, "rename", self.operator.rename(&from, &to)).await
+    }
+
+    /// Deletes the file of the given kind for the link with the given ID (at its
+    /// temporary path if `temporary` is `true`), if it exists.
+    pub(crate) async fn delete(&self, id: LinkId, kind: Kind, temporary: bool) -> Result<()> {
+        self.require("delete", |capability| capability.delete)?;
+
+        let path = if temporary {
+            self.temporary_path(id, kind)
+        } else {
+            self.path(id, kind)
+        };
+
+        timed(self.timeout, "delete", self.operator.delete(&path)).await
+    }
+
+    /// Records that the link with the given ID extends `previous`, as an empty file in a
+    /// directory next to the delta file of `previous`, so that the links extending it
+    /// can be listed with [`list_children()`][1] without listing all of the links.
+    ///
+    /// [1]: Self::list_children()
+    pub(crate) async fn add_child(&self, previous: LinkId, id: LinkId) -> Result<()> {
+        self.require("write", |capability| capability.write)?;
+
+        let path = format!("{}{id}", self.children_path(previous));
+        timed(
+            self.timeout,
+            "write",
+            self.operator.write(&path, Buffer::new()),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Removes the record that the link with the given ID extends `previous` (see
+    /// [`add_child()`][1]), if it exists.
+    ///
+    /// [1]: Self::add_child()
+    pub(crate) async fn remove_child(&self, previous: LinkId, id: LinkId) -> Result<()> {
+        self.require("delete", |capability| capability.delete)?;
+
+        let path = format!("{}{id}", self.children_path(previous));
+        timed(self.timeout, "delete", self.operator.delete(&path)).await
+    }
+
+    /// Returns a stream of the IDs of the links which were recorded as extending
+    /// `previous` (see [`add_child()`][1]).
+    ///
+    /// This only lists the records of `previous`, whatever the number of links in the
+    /// storage.
+    ///
+    /// [1]: Self::add_child()
+    pub(crate) fn list_children(
+        &self,
+        previous: LinkId,
+    ) -> impl Stream<Item = Result<LinkId>> + Send + '_ {
+        let lister = async move {
+            self.require("list", |capability| capability.list)?;
+
+            let path = self.children_path(previous);
+            timed(self.timeout, "list", self.operator.lister(&path)).await
+        };
+
+        let entries = stream::once(lister)
+            .map_ok(move |lister| {
+                stream::try_unfold(lister, move |mut lister| async move {
+                    let entry = timed(self.timeout, "list", lister.try_next()).await?;
+                    Ok(entry.map(|entry| (entry, lister)))
+                })
+            })
+            .try_flatten();
+
+        entries.try_filter_map(|entry| async move {
+            let id = Uuid::try_parse(entry.name()).ok().map(|id| id.as_u128());
+            Ok(id.filter(|id| *id != 0).map(LinkId::from_u128))
+        })
+    }
+
+    /// Returns the path of the directory in which the links extending `previous` are
+    /// recorded (see [`add_child()`][1]).
+    ///
+    /// [1]: Self::add_child()
+    #[inline]
+    fn children_path(&self, previous: LinkId) -> String {
+        format!("{}.children/", self.path(previous, Kind::Delta))
+    }
+
+    /// Returns `true` if the storage supports renaming files, which is required by
+    /// [`create_temporary()`][1] and [`commit()`][2].
+    ///
+    /// [1]: Self::create_temporary()
+    /// [2]: Self::commit()
+    #[inline]
+    pub(crate) fn supports_rename(&self) -> bool {
+        self.capabilities().rename
+    }
+
+    /// Returns the capabilities of the storage which some of the features of the crate
+    /// depend on, which allows checking them upfront rather than handling the errors
+    /// returned when they are missing.
+    ///
+    /// These are the capabilities of the storage itself, regardless of its fallbacks
+    /// (see [`with_fallback()`][1]).
+    ///
+    /// [1]: Self::with_fallback()
+    pub fn capabilities(&self) -> Capabilities {
+        let capability = self.operator.info().full_capability();
+
+        Capabilities {
+            rename: capability.rename,
+            append: capability.copy && capability.write_can_append && capability.stat,
+            delete: capability.delete,
+            list: capability.list && (self.path_fn.is_none() || capability.list_with_recursive),
+            presign: capability.presign && capability.presign_read,
+            conditional_read: capability.read_with_if_match,
+        }
+    }
+
+    /// Returns `true` if the content of the files written to the storage is encrypted.
+    #[inline]
+    pub(crate) fn is_encrypted(&self) -> bool {
+        #[cfg(feature = "encryption")]
+        {
+            self.cipher.is_some()
+        }
+
+        #[cfg(not(feature = "encryption"))]
+        {
+            false
+        }
+    }
+
+    /// Creates a file of the given kind for the link with the given ID (at a temporary
+    /// path if `temporary` is `true`) which starts with the content of the file of the
+    /// same kind of the link `from`, returning a writer appending to it.
+    ///
+    /// This lets the storage copy the file, which avoids reading it. Returns `None` if
+    /// the storage can't copy files or append to them, if the file of `from` only
+    /// exists in one of the storage's fallbacks, or if the storage is encrypted (since
+    /// files are encrypted all at once).
+    pub(crate) async fn create_appended(
+        &self,
+        id: LinkId,
+        kind: Kind,
+        from: LinkId,
+        temporary: bool,
+    ) -> Result<Option<Writer>> {
+        if !self.capabilities().append || self.is_encrypted() {
+            return Ok(None);
+        }
+
+        let from = self.path(from, kind);
+        let path = if temporary {
+            self.temporary_path(id, kind)
+        } else {
+            self.path(id, kind)
+        };
+
+        match timed(self.timeout, "copy", self.operator.copy(&from, &path)).await {
+            Ok(()) => {}
+            Err(Error::Storage(error)) if error.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error),
+        }
+
+        let metadata = timed(self.timeout, "stat", self.operator.stat(&path)).await?;
+        let writer = self.operator.writer_with(&path).append(true).into_future();
+        let writer = timed(self.timeout, "create", writer).await?;
+
+        Ok(Some(Writer {
+            writer,
+            file_size: metadata.content_length() as usize,
+            timeout: self.timeout,
+
+            #[cfg(feature = "encryption")]
+            encrypted: None,
+
+            checksums: None,
+        }))
+    }
+
+    /// Fails with [`Error::Unsupported`] if the storage doesn't support `operation`,
+    /// according to `supported`.
+    ///
+    /// This allows failing early with a clear error rather than with whatever error the
+    /// storage would return when attempting the operation.
+    fn require(
+        &self,
+        operation: &'static str,
+        supported: impl FnOnce(Capability) -> bool,
+    ) -> Result<()> {
+        if supported(self.operator.info().full_capability()) {
+            Ok(())
+        } else {
+            Err(Error::Unsupported { operation })
+        }
+    }
+
+    /// Returns a stream of all of the links' files which exist in the storage, along
+    /// with the ID of the link they belong to.
+    ///
+    /// The files are listed as the stream is polled, one page of results at a time, so
+    /// that only a single page is ever kept in memory, and dropping the stream stops
+    /// listing them (e.g. once the file which was being looked for was found).
+    ///
+    /// This does not check whether the links are reachable from any chain, and skips
+    /// all of the files whose name isn't the one of a link's file (e.g. temporary
+    /// files or files which weren't written by this crate).
+    ///
+    /// If a custom path function is used (see [`with_path_fn()`][1]), this lists all of
+    /// the files under the base path recursively, and skips the ones which aren't
+    /// stored at the path returned by the function.
+    ///
+    /// [1]: Self::with_path_fn()
+    pub fn list_links(&self) -> impl Stream<Item = Result<(LinkId, Kind)>> + Send + '_ {
+        let recursive = self.path_fn.is_some();
+
+        let lister = async move {
+            self.require("list", |capability| capability.list)?;
+            if recursive {
+                self.require("recursive list", |capability| {
+                    capability.list_with_recursive
+                })?;
+            }
+
+            let directory = match &self.base {
+                Some(base) => format!("{base}/"),
+                None => String::from("/"),
+            };
+
+            let lister = self.operator.lister_with(&directory).recursive(recursive);
+            timed(self.timeout, "list", lister.into_future()).await
+        };
+
+        let entries = stream::once(lister)
+            .map_ok(move |lister| {
+                stream::try_unfold(lister, move |mut lister| async move {
+                    let entry = timed(self.timeout, "list", lister.try_next()).await?;
+                    Ok(entry.map(|entry| (entry, lister)))
+                })
+            })
+            .try_flatten();
+
+        entries.try_filter_map(move |entry| async move {
+            let Some((id, kind)) = parse_name(entry.name()) else {
+                return Ok(None);
+            };
+
+            if recursive && entry.path() != self.path(id, kind) {
+                return Ok(None);
+            }
+
+            Ok(Some((id, kind)))
+        })
+    }
+
+    /// Returns the number of entries present in the link with the given ID, along with
+    /// its index in the chain and the ID of its previous link.
+    ///
+    /// This reads the footer of the link's delta file, or the one of its snapshot file
+    /// if it has no delta file (and the footers of the previous links if the delta file
+    /// was encoded with [`LEGACY_VERSION`], see [`legacy_total()`][1]).
+    ///
+    /// [1]: Self::legacy_total()
+    pub(crate) async fn read_total(&self, id: LinkId) -> Result<(u32, ChainIndex, Option<LinkId>)> {
+        let footer = match self.read_footer(id, Kind::Delta).await {
+            Err(Error::DoesNotExist { .. }) => match self.read_footer(id, Kind::Snapshot).await {
+                Err(Error::DoesNotExist { .. }) => {
+                    return Err(Error::DoesNotExist {
+                        link: id,
+                        kind: Kind::Delta,
+                    });
+                }
+
+                footer => footer?,
+            },
+
+            Ok(footer) if footer.version == LEGACY_VERSION => {
+                let total = self.legacy_total(footer.previous, footer.count).await?;
+                return Ok((total, footer.index, footer.previous));
+            }
+
+            footer => footer?,
+        };
+
+        Ok((footer.total, footer.index, footer.previous))
+    }
+
+    /// Returns the number of entries in the chain up to a link whose delta file was
+    /// encoded with [`LEGACY_VERSION`], given its previous link and the number of
+    /// entries in its delta.
+    ///
+    /// Writers of that version of the storage format started assigning `u32`s after the
+    /// number of entries in the previous link's delta rather than in the chain, so the
+    /// `total` stored in those delta files is wrong from the third link of a chain
+    /// onwards. It is instead recomputed by reading the footers of the previous links,
+    /// until reaching one whose number of entries in the chain can be trusted (i.e. a
+    /// link whose delta was encoded with a later version, a link with only a snapshot,
+    /// or the first link of the chain).
+    pub(crate) async fn legacy_total(&self, previous: Option<LinkId>, count: u32) -> Result<u32> {
+        let mut total = count;
+        let mut next = previous;
+
+        while let Some(link) = next {
+            let footer = match self.read_footer(link, Kind::Delta).await {
+                // Snapshots always stored the right number of entries.
+                Err(Error::DoesNotExist { .. }) => self.read_footer(link, Kind::Snapshot).await?,
+                footer => footer?,
+            };
+
+            if footer.version != LEGACY_VERSION || footer.count == footer.total {
+                return Ok(total.saturating_add(footer.total));
+            }
+
+            total = total.saturating_add(footer.count);
+            next = footer.previous;
+        }
+
+        Ok(total)
+    }
+
+    /// Recreates the delta file of the link with the given ID from its snapshot file, if
+    /// it is missing (e.g. if it was lost).
+    ///
+    /// Readers can load a link whose delta file is missing from its snapshot, but the
+    /// link's delta is still needed when reading the chain without using its snapshot
+    /// (e.g. when reloading a reader). The recreated delta contains the entries of the
+    /// snapshot which aren't present in the previous link, which is why the previous
+    /// link must have either a delta or a snapshot file.
+    ///
+    /// Does nothing if the link already has a delta file.
+    pub async fn repair_delta<T: Entry>(&self, id: LinkId) -> Result<()> {
+        if self.open_maybe(id, Kind::Delta).await?.is_some() {
+            return Ok(());
+        }
+
+        let mut reader = self.open(id, Kind::Snapshot).await?;
+        let footer = SFooter::read(&mut reader).await?;
+
+        let previous_total = match footer.previous {
+            Some(previous) => self.read_total(previous).await?.0,
+            None => 0,
+        };
+
+        let Some(count) = footer.count.checked_sub(previous_total) else {
+            return Err(Error::Inconsistent {
+                link: id,
+                reason: "snapshot contains less entries than its previous link",
+            });
+        };
+
+        // Only the entries which weren't present in the previous link are kept.
+        let mut entries = Vec::with_capacity(count as usize);
+        let mut position = 0;
+        let insert = |entry| {
+            if position >= previous_total {
+                entries.push(entry);
+            }
+
+            position += 1;
+        };
+
+        read_snapshot::<T>(self, id, reader, &footer, insert).await?;
+
+        // The delta is written to a temporary path if possible, so that a delta file is
+        // never left half-written.
+        let temporary = self.supports_rename();
+        let mut writer = if temporary {
+            self.create_temporary(id, Kind::Delta).await?
+        } else {
+            self.create(id, Kind::Delta).await?
+        };
+
+        for entry in &entries {
+            writer.write_entry(entry).await?;
+        }
+
+        let dfooter = DFooter {
+            previous: footer.previous,
+            index: footer.index,
+            total: footer.count,
+            count,
+            version: VERSION,
+        };
+
+        dfooter.write(&mut writer).await?;
+        writer.finish().await?;
+
+        if temporary {
+            self.commit(id, Kind::Delta).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the entries stored in the delta file of the link with the given ID, even if
+    /// the writer of the link was interrupted before it could finish it (e.g. because
+    /// its process was killed), in which case the file has no footer.
+    ///
+    /// If the file doesn't end with a valid footer, its content is read as consecutive
+    /// entries of `T::SIZE` bytes, and any trailing bytes which don't form a whole entry
+    /// (i.e. an entry which was only partly written) are discarded. If it does, the
+    /// entries it contains are returned as usual.
+    ///
+    /// Note that some storages only make files visible once they have been completely
+    /// written, and that encrypted files are only written once finished, in which case
+    /// there is nothing to salvage. Without a footer, the index and the previous link
+    /// of the link are unknown, so the entries are decoded with a context (see
+    /// [`Entry::read_with_context()`]) where they are zero and `None`.
+    ///
+    /// Fails if the delta file doesn't exist, or if `T` is zero-sized.
+    pub async fn salvage<T: Entry>(&self, id: LinkId) -> Result<Vec<T>> {
+        if T::SIZE == 0 {
+            return Err(Error::Unsupported {
+                operation: "salvaging zero-sized entries",
+            });
+        }
+
+        let mut reader = self.open(id, Kind::Delta).await?;
+        let file_size = reader.file_size();
+
+        // The content of a footerless file could be mistaken for a footer, which is why
+        // the footer is only trusted if it matches the size of the file.
+        let footer = match DFooter::read(&mut reader).await {
+            Ok(footer) if footer.count as usize * T::SIZE == reader.file_size() => Some(footer),
+
+            // Reading the footer may have decrypted the content of the file into memory,
+            // which then doesn't include the footer, so the file is opened again.
+            Ok(_) => {
+                reader = self.open(id, Kind::Delta).await?;
+                None
+            }
+
+            Err(Error::BadMagic { .. } | Error::FileSize { .. } | Error::Version { .. }) => {
+                reader.set_file_size(file_size);
+                None
+            }
+
+            Err(error) => return Err(error),
+        };
+
+        let (count, context) = match footer {
+            Some(footer) => (footer.count as usize, LinkContext::delta(id, &footer)),
+            None => {
+                let count = file_size / T::SIZE;
+                let context = LinkContext {
+                    link: id,
+                    index: ChainIndex::default(),
+                    previous: None,
+                    total: count as u32,
+                    version: VERSION,
+                };
+
+                (count, context)
+            }
+        };
+
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_sequentially();
+
+        let retries = self.retries();
+        let mut entries = Vec::with_capacity(count);
+        for _ in 0..count {
+            entries.push(read_entry(&mut reader, &context, retries).await?);
+        }
+
+        Ok(entries)
+    }
+
+    /// Creates a file at the given path, returning a writer for it.
+    async fn create_at(&self, path: String) -> Result<Writer> {
+        self.require("write", |capability| capability.write)?;
 
         // TODO(MLB): configure the writer?
-        let writer = self.operator.writer(&path).await?;
+        let writer = timed(self.timeout, "create", self.operator.writer(&path)).await?;
 
         Ok(Writer {
             writer,
             file_size: 0,
+            timeout: self.timeout,
+
+            #[cfg(feature = "encryption")]
+            encrypted: self.cipher.clone().map(|cipher| (cipher, Vec::new())),
+
+            checksums: None,
         })
     }
 
+    /// Returns the path of the file of the given kind for the link with the given ID on
+    /// the local file system, if the storage is backed by it.
+    ///
+    /// If the file doesn't exist in this storage but exists in one of its fallbacks,
+    /// this returns its path in that fallback instead.
+    #[cfg(feature = "mmap")]
+    pub(crate) fn local_path(&self, id: LinkId, kind: Kind) -> Option<std::path::PathBuf> {
+        let path = self.local_path_here(id, kind);
+
+        if let Some(fallback) = &self.fallback
+            && !path.as_ref().is_some_and(|path| path.exists())
+            && let Some(path) = fallback.local_path(id, kind)
+            && path.exists()
+        {
+            return Some(path);
+        }
+
+        path
+    }
+
+    /// Returns the path of the file of the given kind for the link with the given ID on
+    /// the local file system, if this storage (ignoring its fallbacks) is backed by it.
+    #[cfg(feature = "mmap")]
+    fn local_path_here(&self, id: LinkId, kind: Kind) -> Option<std::path::PathBuf> {
+        let info = self.operator.info();
+        if info.scheme() != "fs" {
+            return None;
+        }
+
+        Some(std::path::PathBuf::from(info.root()).join(self.path(id, kind)))
+    }
+
     /// Returns the path at which the file of the given kind for the link with the given
     /// ID should exist or be created.
     #[inline]
     fn path(&self, id: LinkId, kind: Kind) -> String {
+        let path = match &self.path_fn {
+            Some(path_fn) => path_fn(id, kind),
+            None => format!("{id}.{kind}"),
+        };
+
         if let Some(base) = &self.base {
-            format!("{base}/{id}.{kind}")
+            format!("{base}/{path}")
         } else {
-            format!("{id}.{kind}")
+            path
+        }
+    }
+
+    /// Returns the path at which the temporary file of the given kind for the link
+    /// with the given ID should be created.
+    #[inline]
+    fn temporary_path(&self, id: LinkId, kind: Kind) -> String {
+        format!("{}.tmp", self.path(id, kind))
+    }
+}
+
+impl Reader {
+    /// Creates a new reader for a file whose content is the given bytes.
+    pub(crate) fn from_slice(bytes: &[u8]) -> Self {
+        Self {
+            offset: 0,
+            file_size: bytes.len(),
+            bytes_read: 0,
+            source: Source::Memory(bytes.to_vec()),
+            timeout: None,
+            sequential: None,
+            legacy: false,
+
+            #[cfg(feature = "encryption")]
+            cipher: None,
         }
     }
-}
 
-impl Reader {
     #[inline]
-    pub(crate) fn file_size(&self) -> usize {
-        self.file_size
+    pub(crate) fn file_size(&self) -> usize {
+        self.file_size
+    }
+
+    /// Returns the number of bytes which remain to be read from the current position
+    /// until the end of the file.
+    #[inline]
+    pub(crate) fn remaining(&self) -> usize {
+        self.file_size - self.offset
+    }
+
+    /// Returns the number of bytes which have been read from the file so far.
+    #[inline]
+    pub(crate) fn bytes_read(&self) -> u64 {
+        self.bytes_read
     }
 
     /// Reads a `u16` from the reader.
@@ -177,14 +1351,124 @@ impl Reader {
         let mut bytes = [0u8; N];
         let range = self.range(N)?;
 
-        // TODO(MLB): do some buffering?
-        self.reader
-            .read_into(&mut bytes.as_mut_slice(), range)
-            .await?;
+        // Some backends treat empty ranges as "the whole file" – zero-sized reads (e.g. for
+        // entries whose `SIZE` is `0`) must not issue any request.
+        if N == 0 {
+            return Ok(bytes);
+        }
+
+        if self.read_streamed(&mut bytes).await? {
+            self.bytes_read += N as u64;
+            self.offset += N;
+
+            return Ok(bytes);
+        }
+
+        match &self.source {
+            Source::Storage(reader) => {
+                let mut buffer = bytes.as_mut_slice();
+                let read = reader.read_into(&mut buffer, range);
+                let read = timed(self.timeout, "read", read).await?;
+                check_read(N, read)?;
+                self.bytes_read += N as u64;
+            }
+
+            Source::Memory(content) => {
+                bytes.copy_from_slice(&content[self.offset..self.offset + N]);
+            }
+        }
+
+        self.offset += N;
+
+        Ok(bytes)
+    }
+
+    /// Reads `len` bytes from the reader.
+    ///
+    /// This also updates the reader's current position accordingly.
+    pub(crate) async fn read_vec(&mut self, len: usize) -> Result<Vec<u8>> {
+        let range = self.range(len)?;
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut bytes = Vec::with_capacity(len);
+        if self.sequential.is_some() {
+            bytes.resize(len, 0);
+            if self.read_streamed(&mut bytes).await? {
+                self.bytes_read += len as u64;
+                self.offset += len;
+
+                return Ok(bytes);
+            }
+
+            bytes.clear();
+        }
+
+        match &self.source {
+            Source::Storage(reader) => {
+                let read = timed(self.timeout, "read", reader.read_into(&mut bytes, range)).await?;
+                check_read(len, read)?;
+                self.bytes_read += len as u64;
+            }
+
+            Source::Memory(content) => {
+                bytes.extend_from_slice(&content[self.offset..self.offset + len]);
+            }
+        }
+
+        self.offset += len;
 
         Ok(bytes)
     }
 
+    /// Streams the content of the file from the current position until its end, so that
+    /// reading it in order (e.g. entry after entry) doesn't require issuing a request
+    /// for each read, but reuses a single one.
+    ///
+    /// The stream is only started by the next read, so that failing to start it fails
+    /// that read. Reads which don't start where the previous one ended (e.g. after
+    /// seeking, or after a read failed) stop the stream and fall back to issuing a
+    /// request for each read. This does nothing if the content of the file is kept in
+    /// memory.
+    pub(crate) fn read_sequentially(&mut self) {
+        if matches!(self.source, Source::Memory(_)) || self.offset == self.file_size {
+            return;
+        }
+
+        self.sequential = Some(Sequential {
+            stream: None,
+            buffer: Buffer::new(),
+            offset: self.offset,
+            end: self.file_size,
+        });
+    }
+
+    /// Fills `bytes` with the content of the file at the current position if it is being
+    /// streamed from there, returning `false` if it isn't.
+    ///
+    /// This doesn't update the reader's current position.
+    async fn read_streamed(&mut self, bytes: &mut [u8]) -> Result<bool> {
+        let (Some(sequential), Source::Storage(reader)) = (&mut self.sequential, &self.source)
+        else {
+            return Ok(false);
+        };
+
+        if sequential.offset != self.offset {
+            self.sequential = None;
+            return Ok(false);
+        }
+
+        // The stream is stopped on failure, since part of its content might have been
+        // consumed already.
+        if let Err(error) = sequential.read(reader, bytes, self.timeout).await {
+            self.sequential = None;
+            return Err(error);
+        }
+
+        Ok(true)
+    }
+
     /// Returns the range that should be used to read `len` bytes at the current
     /// position.
     ///
@@ -203,29 +1487,157 @@ impl Reader {
         Ok((self.offset as u64)..(self.offset + len) as u64)
     }
 
-    /// Updates the current reader position based on `offset`.
+    /// Moves the current reader position to the given position, returning the new
+    /// position from the start of the file.
     ///
-    /// A positive `offset` value represents a value from the start of the file, whereas
-    /// a negative one represents a value from the end of it (i.e. if `file_size = 10`
-    /// and `offset = -1`, then the new position will be `9`).
-    pub(crate) fn goto(&mut self, offset: isize) -> Result<()> {
-        if offset.is_positive() {
-            self.offset = offset as usize;
-        } else {
-            let Some(offset) = self.file_size.checked_add_signed(offset) else {
-                // TODO(MLB): handle...
-                todo!()
-            };
+    /// Fails if the new position would be before the start of the file or after its
+    /// end, which excludes the parts of the file which were already interpreted (e.g.
+    /// the footer, once it has been read).
+    pub fn seek(&mut self, pos: SeekFrom) -> Result<usize> {
+        let (base, delta) = match pos {
+            SeekFrom::Start(offset) => (0, offset as i64),
+            SeekFrom::End(delta) => (self.file_size as i64, delta),
+            SeekFrom::Current(delta) => (self.offset as i64, delta),
+        };
 
-            self.offset = offset;
+        let position = base.saturating_add(delta);
+        if position < 0 || position > self.file_size as i64 {
+            return Err(Error::InvalidSeek {
+                position,
+                file_size: self.file_size,
+            });
         }
 
-        Ok(())
+        self.offset = position as usize;
+
+        Ok(self.offset)
+    }
+
+    /// Returns the oldest version of the storage format the file can be encoded with.
+    #[inline]
+    pub(crate) fn min_version(&self) -> u16 {
+        if self.legacy {
+            LEGACY_VERSION
+        } else {
+            MIN_VERSION
+        }
     }
 
     pub(crate) fn set_file_size(&mut self, file_size: usize) {
         self.file_size = file_size;
     }
+
+    /// Returns a new reader for the first `file_size` bytes of the file being read
+    /// (e.g. to read the previous link's snapshot stored at the start of an appended
+    /// snapshot).
+    pub(crate) fn prefix(&self, file_size: usize) -> Self {
+        let source = match &self.source {
+            Source::Storage(reader) => Source::Storage(reader.clone()),
+            Source::Memory(content) => Source::Memory(content[..file_size].to_vec()),
+        };
+
+        Self {
+            offset: 0,
+            file_size,
+            bytes_read: 0,
+            source,
+            timeout: self.timeout,
+            sequential: None,
+            legacy: self.legacy,
+
+            #[cfg(feature = "encryption")]
+            cipher: self.cipher.clone(),
+        }
+    }
+
+    /// Returns `true` if the content of the file is kept in memory rather than read
+    /// from the storage.
+    #[cfg(feature = "mmap")]
+    #[inline]
+    pub(crate) fn is_in_memory(&self) -> bool {
+        matches!(self.source, Source::Memory(_))
+    }
+
+    /// Reads the [`Encryption`] stored in a footer, and if the file was encrypted,
+    /// decrypts all of its content at once.
+    ///
+    /// This must be called once the footer has been read (i.e. once the reader acts
+    /// as-if the footer did not exist).
+    pub(crate) async fn unseal(&mut self, encryption: Option<Encryption>) -> Result<()> {
+        let Some(encryption) = encryption else {
+            return Ok(());
+        };
+
+        #[cfg(feature = "encryption")]
+        if let Some(cipher) = self.cipher.clone() {
+            let mut content = Vec::with_capacity(self.file_size);
+            match &self.source {
+                Source::Storage(reader) => {
+                    let range = 0..self.file_size as u64;
+                    if !range.is_empty() {
+                        let read = reader.read_into(&mut content, range);
+                        let read = timed(self.timeout, "read", read).await?;
+                        check_read(self.file_size, read)?;
+                    }
+
+                    self.bytes_read += content.len() as u64;
+                }
+
+                Source::Memory(memory) => content.extend_from_slice(&memory[..self.file_size]),
+            }
+
+            cipher.decrypt(encryption, &mut content)?;
+
+            self.offset = 0;
+            self.file_size = content.len();
+            self.source = Source::Memory(content);
+
+            return Ok(());
+        }
+
+        let _ = encryption;
+        Err(Error::Encrypted)
+    }
+}
+
+impl Sequential {
+    /// Fills `bytes` with the content of the file which follows what was read until now,
+    /// starting the stream with `reader` if it hasn't been yet.
+    async fn read(
+        &mut self,
+        reader: &opendal::Reader,
+        bytes: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<()> {
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => {
+                let range = (self.offset as u64)..(self.end as u64);
+                let stream = timed(timeout, "read", reader.clone().into_stream(range)).await?;
+                self.stream.insert(Mutex::new(stream))
+            }
+        };
+
+        let stream = stream.get_mut().unwrap_or_else(PoisonError::into_inner);
+
+        let mut filled = 0;
+        while filled < bytes.len() {
+            if self.buffer.is_empty() {
+                match timed(timeout, "read", stream.try_next()).await? {
+                    Some(buffer) => self.buffer = buffer,
+                    None => return check_read(bytes.len(), filled),
+                }
+            }
+
+            let len = self.buffer.len().min(bytes.len() - filled);
+            std::io::Read::read_exact(&mut self.buffer, &mut bytes[filled..filled + len])?;
+            filled += len;
+        }
+
+        self.offset += bytes.len();
+
+        Ok(())
+    }
 }
 
 impl Writer {
@@ -235,19 +1647,84 @@ impl Writer {
         self.file_size
     }
 
+    /// Computes the checksum of each block of `block_size` bytes written to the file
+    /// from now on, until [`take_checksums()`][1] is called.
+    ///
+    /// [1]: Self::take_checksums()
+    #[inline]
+    pub(crate) fn with_checksums(&mut self, block_size: usize) {
+        self.checksums = Some(Checksums {
+            block_size,
+            hasher: crc32fast::Hasher::new(),
+            len: 0,
+            checksums: Vec::new(),
+        });
+    }
+
+    /// Stops computing checksums, returning the checksums of all of the blocks written
+    /// since [`with_checksums()`][1] was called (the last one possibly being smaller
+    /// than the others), if it was.
+    ///
+    /// [1]: Self::with_checksums()
+    pub(crate) fn take_checksums(&mut self) -> Option<Vec<u32>> {
+        let Checksums {
+            hasher,
+            len,
+            mut checksums,
+            ..
+        } = self.checksums.take()?;
+
+        if len > 0 {
+            checksums.push(hasher.finalize());
+        }
+
+        Some(checksums)
+    }
+
     /// Reads everything from `reader` and writes it to the writer as-is.
     pub(crate) async fn copy_from(&mut self, reader: Reader) -> Result<()> {
+        let raw = match &reader.source {
+            Source::Storage(raw) => raw,
+            Source::Memory(content) => {
+                return self
+                    .write_slice(&content[reader.offset..reader.file_size])
+                    .await;
+            }
+        };
+
         let range = (reader.offset as u64)..(reader.file_size as u64);
-        let mut stream = reader.reader.into_stream(range).await?;
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        let expected = (range.end - range.start) as usize;
+        let mut copied = 0;
 
-        while let Some(buffer) = stream.try_next().await? {
+        let stream = raw.clone().into_stream(range);
+        let mut stream = timed(reader.timeout, "read", stream).await?;
+
+        while let Some(buffer) = timed(reader.timeout, "read", stream.try_next()).await? {
             let num_bytes = buffer.len();
+            copied += num_bytes;
+
+            if let Some(checksums) = &mut self.checksums {
+                for chunk in buffer.clone() {
+                    checksums.update(&chunk);
+                }
+            }
 
-            self.writer.write(buffer).await?;
+            #[cfg(feature = "encryption")]
+            if let Some((_, content)) = &mut self.encrypted {
+                content.extend_from_slice(&buffer.to_bytes());
+                self.file_size += num_bytes;
+                continue;
+            }
+
+            timed(self.timeout, "write", self.writer.write(buffer)).await?;
             self.file_size += num_bytes;
         }
 
-        Ok(())
+        check_read(expected, copied)
     }
 
     /// Writes a `u16` into the writer.
@@ -280,28 +1757,548 @@ impl Writer {
 
     /// Writes the given bytes into the writer.
     pub async fn write_bytes<const N: usize>(&mut self, bytes: [u8; N]) -> Result<()> {
+        if N == 0 {
+            return Ok(());
+        }
+
+        self.write_slice(&bytes).await
+    }
+
+    /// Writes the given bytes into the writer.
+    ///
+    /// Contrarily to [`write_bytes()`][1], the number of bytes doesn't need to be known
+    /// at compile time. This allows entries to write their encoding in several pieces
+    /// (e.g. to avoid building the whole encoding of large entries in memory or on the
+    /// stack), as long as they write exactly `SIZE` bytes in total.
+    ///
+    /// [1]: Self::write_bytes()
+    pub async fn write_slice(&mut self, bytes: &[u8]) -> Result<()> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(checksums) = &mut self.checksums {
+            checksums.update(bytes);
+        }
+
+        #[cfg(feature = "encryption")]
+        if let Some((_, content)) = &mut self.encrypted {
+            content.extend_from_slice(bytes);
+            self.file_size += bytes.len();
+
+            return Ok(());
+        }
+
         // TODO(MLB): do some buffering?
-        self.writer.write_from(bytes.as_slice()).await?;
-        self.file_size += N;
+        timed(self.timeout, "write", self.writer.write_from(bytes)).await?;
+        self.file_size += bytes.len();
+
+        Ok(())
+    }
+
+    /// Writes the given entry into the writer.
+    ///
+    /// Fails with [`Error::EntrySize`] if the entry didn't write exactly `SIZE` bytes.
+    pub(crate) async fn write_entry<T: Entry>(&mut self, entry: &T) -> Result<()> {
+        let start = self.file_size;
+        entry.write(self).await?;
+
+        let written = self.file_size - start;
+        if written != T::SIZE {
+            return Err(Error::EntrySize {
+                expected: T::SIZE,
+                got: written,
+            });
+        }
 
         Ok(())
     }
 
+    /// Encrypts all of the content written so far and writes it to the file, if it is
+    /// encrypted, returning the [`Encryption`] which should be stored in its footer.
+    ///
+    /// This must be called right before writing the footer, which is never encrypted.
+    pub(crate) async fn seal(&mut self) -> Result<Option<Encryption>> {
+        #[cfg(feature = "encryption")]
+        if let Some((cipher, mut content)) = self.encrypted.take() {
+            let encryption = cipher.encrypt(&mut content)?;
+
+            // The authentication tag is part of the file's content.
+            self.file_size = content.len();
+            let write = self.writer.write_from(content.as_slice());
+            timed(self.timeout, "write", write).await?;
+
+            return Ok(Some(encryption));
+        }
+
+        Ok(None)
+    }
+
     /// Finishes writing, flushing all remaining bytes to the file.
     #[inline]
     pub(crate) async fn finish(mut self) -> Result<()> {
-        self.writer.close().await?;
+        timed(self.timeout, "close", self.writer.close()).await?;
+
+        Ok(())
+    }
+
+    /// Abandons the file, discarding what was written to it.
+    pub(crate) async fn abort(mut self) -> Result<()> {
+        timed(self.timeout, "abort", self.writer.abort()).await?;
 
         Ok(())
     }
 }
 
+impl Checksums {
+    /// Updates the checksums with the given bytes, which were written right after the
+    /// previous ones.
+    fn update(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            let len = bytes.len().min(self.block_size - self.len);
+            self.hasher.update(&bytes[..len]);
+            self.len += len;
+            bytes = &bytes[len..];
+
+            if self.len == self.block_size {
+                let hasher = std::mem::take(&mut self.hasher);
+                self.checksums.push(hasher.finalize());
+                self.len = 0;
+            }
+        }
+    }
+}
+
+impl Encryption {
+    /// Reads an [`Encryption`] (or its absence) stored in a footer.
+    pub(crate) async fn read(reader: &mut Reader) -> Result<Option<Self>> {
+        let [scheme] = reader.read_bytes().await?;
+        let nonce = reader.read_bytes().await?;
+
+        match scheme {
+            0 => Ok(None),
+            1 => Ok(Some(Self::ChaCha20Poly1305 { nonce })),
+            _ => Err(Error::Unsupported {
+                operation: "unknown encryption scheme",
+            }),
+        }
+    }
+
+    /// Appends an [`Encryption`] (or its absence) to the encoding of a footer.
+    pub(crate) fn encode(encryption: Option<Self>, bytes: &mut Vec<u8>) {
+        let (scheme, nonce) = match encryption {
+            None => (0, [0; 12]),
+            Some(Self::ChaCha20Poly1305 { nonce }) => (1, nonce),
+        };
+
+        bytes.push(scheme);
+        bytes.extend_from_slice(&nonce);
+    }
+}
+
+/// Completes a future which only reads from readers whose content is kept in memory,
+/// without waiting.
+///
+/// Fails if the future needs to wait.
+pub(crate) fn complete_now<T>(future: impl Future<Output = Result<T>>) -> Result<T> {
+    future.now_or_never().unwrap_or(Err(Error::Unsupported {
+        operation: "waiting while reading from memory",
+    }))
+}
+
+/// Fails with [`Error::ShortRead`] if the storage returned less bytes than the
+/// `expected` ones.
+#[inline]
+fn check_read(expected: usize, got: usize) -> Result<()> {
+    if got != expected {
+        return Err(Error::ShortRead { expected, got });
+    }
+
+    Ok(())
+}
+
+/// Waits for the given storage operation to complete, failing with [`Error::Timeout`]
+/// if it takes longer than `timeout` (if any).
+pub(crate) async fn timed<T, E>(
+    timeout: Option<Duration>,
+    operation: &'static str,
+    future: impl Future<Output = std::result::Result<T, E>>,
+) -> Result<T>
+where
+    Error: From<E>,
+{
+    let Some(timeout) = timeout else {
+        return Ok(future.await?);
+    };
+
+    match select(pin!(future), Delay::new(timeout)).await {
+        Either::Left((result, _)) => Ok(result?),
+        Either::Right(_) => Err(Error::Timeout { operation }),
+    }
+}
+
+/// Parses the name of a link's file (i.e. `{id}.{kind}`), returning the ID of the link
+/// along with the kind of the file.
+///
+/// Returns `None` if the name isn't the one of a link's file.
+fn parse_name(name: &str) -> Option<(LinkId, Kind)> {
+    let (id, kind) = name.split_once('.')?;
+
+    let kind = match kind {
+        "delta" => Kind::Delta,
+        "snapshot" => Kind::Snapshot,
+        "values" => Kind::Values,
+        _ => return None,
+    };
+
+    // A zero ID is used to represent the lack of a link in footers, and is thus never
+    // assigned to one.
+    let id = Uuid::try_parse(id).ok()?.as_u128();
+    if id == 0 {
+        return None;
+    }
+
+    Some((LinkId::from_u128(id), kind))
+}
+
 impl Display for Kind {
     #[inline]
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
             Self::Delta => write!(f, "delta"),
             Self::Snapshot => write!(f, "snapshot"),
+            Self::Values => write!(f, "values"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{
+        Batch, Reader, Writer,
+        tests::{U64, block_on, chain, extend, local, memory, probed, snapshot, write_file},
+    };
+
+    use super::*;
+
+    #[test]
+    fn list_links() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..4, 4..5]).await?;
+
+            let temporary = storage.create_temporary(links[0], Kind::Snapshot).await?;
+            temporary.finish().await?;
+            storage.operator.write("/notes.txt", "not a link").await?;
+            storage.operator.write("/nil.delta", "not a link").await?;
+
+            let listed = storage
+                .list_links()
+                .map_ok(|(id, kind)| (id, kind.to_string()))
+                .try_collect::<HashSet<_>>()
+                .await?;
+
+            let expected = links.iter().map(|id| (*id, String::from("delta")));
+            assert_eq!(listed, expected.collect());
+
+            // The stream can be dropped before the listing ends.
+            let mut listed = pin!(storage.list_links());
+            assert!(links.contains(&listed.try_next().await?.unwrap().0));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn list_links_streams_the_listing() -> Result<()> {
+        block_on(async {
+            let (storage, probe) = probed();
+            for _ in 0..1000 {
+                let path = storage.path(LinkId::random(), Kind::Delta);
+                storage.operator.write(&path, "").await?;
+            }
+
+            // Stopping after a few links only pulls a few entries from the storage.
+            let first = storage.list_links().take(3).try_collect::<Vec<_>>().await?;
+            assert_eq!(first.len(), 3);
+            assert!(probe.listed() < 10, "listed {} entries", probe.listed());
+
+            // The links can be counted without collecting them.
+            let before = probe.listed();
+            let count = storage
+                .list_links()
+                .try_fold(0, |count, _| future::ready(Ok(count + 1)))
+                .await?;
+            assert_eq!(count, 1000);
+            assert!(probe.listed() - before >= 1000);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn fallback() -> Result<()> {
+        block_on(async {
+            let operator = memory().operator;
+            let first = Storage::new_in("first", operator.clone());
+            let second = Storage::new_in("second", operator).with_fallback(first.clone());
+
+            let links = chain(&first, Some(0..2)).await?;
+            let mut writer = Writer::<U64>::create(Some(links[0]), second.clone()).await?;
+            writer.write_unique(U64(2)).await?;
+            let latest = writer.finish().await?;
+
+            // The links of the other storage are found, but files are only written to
+            // this storage.
+            let reader = Reader::<U64>::open(latest, second.clone()).await?;
+            assert_eq!(reader.len(), 3);
+            assert!(first.open_maybe(latest, Kind::Delta).await?.is_none());
+
+            let listed = second.list_links().try_collect::<Vec<_>>().await?;
+            assert_eq!(listed.len(), 1);
+            assert_eq!(listed[0].0, latest);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn operation_timeout() -> Result<()> {
+        block_on(async {
+            let (storage, probe) = probed();
+            let storage = storage.with_operation_timeout(Duration::from_millis(10));
+            let links = chain(&storage, [0..2, 2..4]).await?;
+
+            probe.stall_reads(Some((links[1], Kind::Delta)));
+            let error = Reader::<U64>::open(links[1], storage.clone()).await.err();
+            assert!(matches!(error, Some(Error::Timeout { .. })));
+            assert!(error.unwrap().is_retryable());
+
+            // Operations which timed out can simply be retried.
+            probe.stall_reads(None);
+            let reader = Reader::<U64>::open(links[1], storage).await?;
+            assert_eq!(reader.len(), 4);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn repair_delta() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..3]).await?;
+            let snapshot = snapshot(&storage, links[1], 3..5).await?;
+            let latest = extend(&storage, Some(snapshot), Some(5..6)).await?[0];
+
+            let mut delta = storage.open(snapshot, Kind::Delta).await?;
+            let delta = delta.read_vec(delta.file_size()).await?;
+            storage.delete(snapshot, Kind::Delta, false).await?;
+
+            // The chain can still be read using the snapshot, but not from its deltas.
+            Reader::<U64>::open(latest, storage.clone()).await?;
+            assert!(matches!(
+                Reader::<U64>::open_delta_only(links[0], latest, storage.clone()).await,
+                Err(Error::DoesNotExist { link, kind: Kind::Delta }) if link == snapshot
+            ));
+
+            storage.repair_delta::<U64>(snapshot).await?;
+            let mut repaired = storage.open(snapshot, Kind::Delta).await?;
+            assert_eq!(repaired.read_vec(repaired.file_size()).await?, delta);
+
+            let reader = Reader::<U64>::open_delta_only(links[0], latest, storage).await?;
+            assert!(reader.iter().map(|(_, entry)| entry.0).eq(0..6));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn entry_retries() -> Result<()> {
+        block_on(async {
+            let (storage, probe) = probed();
+            let storage = storage.with_entry_retries(2, Duration::from_millis(1));
+            let links = chain(&storage, [0..2, 2..4]).await?;
+
+            probe.fail_reads_temporarily(links[1], Kind::Delta, 2);
+            let reader = Reader::<U64>::open(links[1], storage.clone()).await?;
+            assert_eq!(reader.get_index_of(&U64(2)), Some(2));
+
+            // Reading the entry fails once all of the retries have been used.
+            probe.fail_reads_temporarily(links[1], Kind::Delta, 3);
+            assert!(matches!(
+                Reader::<U64>::open(links[1], storage).await,
+                Err(Error::EntryRead { link, offset: 0, attempts: 3, .. }) if link == links[1]
+            ));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn short_reads() -> Result<()> {
+        block_on(async {
+            let (storage, probe) = probed();
+            let links = chain(&storage, [0..2, 2..4]).await?;
+
+            probe.truncate_reads(Some((links[1], Kind::Delta)));
+            assert!(matches!(
+                Reader::<U64>::open(links[1], storage.clone()).await,
+                Err(Error::ShortRead {
+                    expected: 8,
+                    got: 7
+                })
+            ));
+
+            probe.truncate_reads(None);
+            Reader::<U64>::open(links[1], storage).await?;
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn salvage() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..5]).await?;
+            let entries = storage.salvage::<U64>(links[1]).await?;
+            assert_eq!(entries, [U64(2), U64(3), U64(4)]);
+
+            // The content of an interrupted writer has no footer, and its last entry might
+            // only have been partly written.
+            let mut content = Vec::new();
+            for entry in 5..8u64 {
+                content.extend_from_slice(&entry.to_be_bytes());
+            }
+
+            content.extend_from_slice(&[0; 5]);
+
+            let interrupted = LinkId::random();
+            write_file(&storage, interrupted, Kind::Delta, &content).await?;
+            let entries = storage.salvage::<U64>(interrupted).await?;
+            assert_eq!(entries, [U64(5), U64(6), U64(7)]);
+
+            Ok(())
+        })
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn salvage_encrypted() -> Result<()> {
+        block_on(async {
+            let plain = memory();
+            let storage = plain.clone().with_encryption([7; 32]);
+            let links = chain(&storage, [0..2, 2..5]).await?;
+            let entries = storage.salvage::<U64>(links[1]).await?;
+            assert_eq!(entries, [U64(2), U64(3), U64(4)]);
+
+            // With entries of the wrong size, the footer doesn't match the file, whose
+            // encrypted content is then read as-is.
+            let file_size = plain.open(links[1], Kind::Delta).await?.file_size();
+            let entries = storage.salvage::<crate::Bytes<3>>(links[1]).await?;
+            assert_eq!(entries.len(), file_size / 3);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn capabilities() -> Result<()> {
+        block_on(async {
+            let (local, _dir) = local();
+            let capabilities = local.capabilities();
+            assert!(capabilities.rename && capabilities.delete && capabilities.list);
+            assert!(!capabilities.presign);
+
+            // Storages which can't rename files can't write links to temporary paths.
+            let memory = memory().with_fallback(local);
+            assert!(!memory.capabilities().rename);
+
+            let mut writer = Writer::<U64>::create_two_phase(None, memory).await?;
+            writer.write_unique(U64(0)).await?;
+            let result = Batch::new().add(writer).await;
+            assert!(matches!(result, Err(Error::Unsupported { .. })));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn read_footer() -> Result<()> {
+        block_on(async {
+            let (storage, probe) = probed();
+            let links = chain(&storage, [0..20, 20..22]).await?;
+            let snapshot = snapshot(&storage, links[1], 22..25).await?;
+
+            // Only the end of the files is read.
+            probe.fail_reads(Some((links[0], Kind::Delta)));
+            let footer = storage.read_footer(links[0], Kind::Delta).await?;
+            assert_eq!((footer.previous, footer.index), (None, ChainIndex::new(0)));
+            assert_eq!(
+                (footer.total, footer.count, footer.version),
+                (20, 20, VERSION)
+            );
+
+            let footer = storage.read_footer(links[1], Kind::Delta).await?;
+            assert_eq!(
+                (footer.previous, footer.total, footer.count),
+                (Some(links[0]), 22, 2)
+            );
+
+            let footer = storage.read_footer(snapshot, Kind::Snapshot).await?;
+            assert_eq!(
+                (footer.previous, footer.index),
+                (Some(links[1]), ChainIndex::new(2))
+            );
+            assert_eq!((footer.total, footer.count), (25, 25));
+
+            assert!(matches!(
+                storage.read_footer(links[1], Kind::Snapshot).await,
+                Err(Error::DoesNotExist {
+                    kind: Kind::Snapshot,
+                    ..
+                })
+            ));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    #[cfg(feature = "encryption")]
+    fn encrypted_round_trip() -> Result<()> {
+        block_on(async {
+            let plain = memory();
+            let storage = plain.clone().with_encryption([7; 32]);
+            let links = chain(&storage, [0..2, 2..4]).await?;
+            let snapshot = snapshot(&storage, links[1], 4..6).await?;
+
+            let reader = Reader::<U64>::open(snapshot, storage.clone()).await?;
+            assert!(reader.iter().map(|(_, entry)| entry.0).eq(0..6));
+            let reader =
+                Reader::<U64>::open_delta_only(links[0], snapshot, storage.clone()).await?;
+            assert!(reader.iter().map(|(_, entry)| entry.0).eq(0..6));
+
+            // The entries aren't stored in plaintext, and can't be read without the key.
+            let mut delta = plain.open(links[1], Kind::Delta).await?;
+            let content = delta.read_vec(delta.file_size()).await?;
+            assert!(!content.windows(8).any(|bytes| bytes == 3u64.to_be_bytes()));
+
+            let wrong = plain.clone().with_encryption([8; 32]);
+            assert!(matches!(
+                Reader::<U64>::open(links[1], wrong).await,
+                Err(Error::Decryption)
+            ));
+
+            // Files which aren't encrypted can still be read.
+            let latest = extend(&plain, Some(snapshot), Some(6..7)).await?[0];
+            let reader = Reader::<U64>::open(latest, storage).await?;
+            assert!(reader.iter().map(|(_, entry)| entry.0).eq(0..7));
+
+            Ok(())
+        })
+    }
+}
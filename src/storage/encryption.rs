@@ -0,0 +1,45 @@
+use chacha20poly1305::{AeadInPlace, ChaCha20Poly1305, KeyInit, Nonce};
+use uuid::Uuid;
+
+use super::Encryption;
+use crate::{Error, Result};
+
+/// The cipher used to encrypt and decrypt the content of files (excluding their
+/// footer).
+pub(crate) struct Cipher(ChaCha20Poly1305);
+
+impl Cipher {
+    /// Creates a new [`Cipher`] using the given key.
+    #[inline]
+    pub(crate) fn new(key: [u8; 32]) -> Self {
+        Self(ChaCha20Poly1305::new(&key.into()))
+    }
+
+    /// Encrypts `content` in-place using a new random nonce, appending the
+    /// authentication tag to it.
+    pub(crate) fn encrypt(&self, content: &mut Vec<u8>) -> Result<Encryption> {
+        // Each file is encrypted with its own random nonce, which is stored in its footer.
+        // The bytes of the UUID storing its version and variant are skipped.
+        let uuid = Uuid::new_v4().into_bytes();
+        let mut nonce = [0; 12];
+        nonce[..6].copy_from_slice(&uuid[..6]);
+        nonce[6..].copy_from_slice(&uuid[9..15]);
+
+        self.0
+            .encrypt_in_place(Nonce::from_slice(&nonce), &[], content)
+            .map_err(|_| Error::Unsupported {
+                operation: "encrypting more than 256 GiB",
+            })?;
+
+        Ok(Encryption::ChaCha20Poly1305 { nonce })
+    }
+
+    /// Decrypts `content` in-place, verifying and removing its authentication tag.
+    pub(crate) fn decrypt(&self, encryption: Encryption, content: &mut Vec<u8>) -> Result<()> {
+        let Encryption::ChaCha20Poly1305 { nonce } = encryption;
+
+        self.0
+            .decrypt_in_place(Nonce::from_slice(&nonce), &[], content)
+            .map_err(|_| Error::Decryption)
+    }
+}
@@ -0,0 +1,397 @@
+//! Helpers shared by the tests of the different modules.
+
+use std::{
+    future::Future,
+    ops::Range,
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use opendal::{
+    Buffer, ErrorKind, Operator,
+    raw::{
+        Access, Layer, LayeredAccess, OpList, OpRead, OpWrite, RpDelete, RpList, RpRead, RpWrite,
+        oio,
+    },
+    services::{Fs, Memory},
+};
+
+use crate::{
+    ChainIndex, DFooter, Entry, LinkId, Reader, Result, Storage, Writer,
+    storage::{self, Kind, Kind::*},
+};
+
+/// An entry made of a single `u64`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct U64(pub(crate) u64);
+
+impl Entry for U64 {
+    const SIZE: usize = 8;
+
+    async fn read(reader: &mut storage::Reader) -> Result<Self> {
+        Ok(Self(reader.read_u64().await?))
+    }
+
+    async fn write(&self, writer: &mut storage::Writer) -> Result<()> {
+        writer.write_u64(self.0).await
+    }
+}
+
+/// A layer observing the operations issued to the storage it wraps.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Probe {
+    /// The number of entries which were returned by listers so far.
+    listed: Arc<AtomicUsize>,
+
+    /// The number of files which were created so far.
+    created: Arc<AtomicUsize>,
+
+    /// The path of the file whose content can't be read, if any.
+    failing: Arc<Mutex<Option<String>>>,
+
+    /// The path of the file whose content is never returned, if any.
+    stalling: Arc<Mutex<Option<String>>>,
+
+    /// The path of the file whose content can't be read temporarily, if any, along with
+    /// the number of reads which still fail.
+    flaky: Arc<Mutex<Option<(String, u32)>>>,
+
+    /// The path of the file whose content is returned without its last requested byte,
+    /// if any.
+    truncating: Arc<Mutex<Option<String>>>,
+}
+
+/// The accessor returned by [`Probe`].
+#[derive(Debug)]
+pub(crate) struct ProbeAccessor<A> {
+    inner: A,
+    probe: Probe,
+}
+
+/// The reader returned by [`ProbeAccessor`].
+pub(crate) struct ProbeReader<R> {
+    inner: R,
+
+    /// The number of bytes which can still be returned, if limited.
+    limit: Option<usize>,
+}
+
+/// The lister returned by [`ProbeAccessor`].
+pub(crate) struct ProbeLister<L> {
+    inner: L,
+    probe: Probe,
+}
+
+impl Probe {
+    /// Returns the number of entries which were returned by listers so far.
+    pub(crate) fn listed(&self) -> usize {
+        self.listed.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of files which were created so far (i.e. the number of
+    /// writers which were created for them, whether or not they were closed).
+    pub(crate) fn created(&self) -> usize {
+        self.created.load(Ordering::Relaxed)
+    }
+
+    /// Makes reading the content of the file of the given kind for the link with the
+    /// given ID fail from now on, or stops failing if `file` is `None`.
+    ///
+    /// Only the reads starting at the beginning of the file fail, which means that its
+    /// footer can still be read (unless the file only contains its footer).
+    pub(crate) fn fail_reads(&self, file: Option<(LinkId, Kind)>) {
+        *self.failing.lock().unwrap() = file.map(|(id, kind)| format!("{id}.{kind}"));
+    }
+
+    /// Makes reading the content of the file of the given kind for the link with the
+    /// given ID never complete from now on, or stops stalling if `file` is `None`.
+    ///
+    /// Like with [`fail_reads()`][1], only the reads starting at the beginning of the
+    /// file are affected.
+    ///
+    /// [1]: Self::fail_reads()
+    pub(crate) fn stall_reads(&self, file: Option<(LinkId, Kind)>) {
+        *self.stalling.lock().unwrap() = file.map(|(id, kind)| format!("{id}.{kind}"));
+    }
+
+    /// Makes the next `times` reads of the content of the file of the given kind for
+    /// the link with the given ID fail with a temporary error.
+    ///
+    /// Like with [`fail_reads()`][1], only the reads starting at the beginning of the
+    /// file are affected.
+    ///
+    /// [1]: Self::fail_reads()
+    pub(crate) fn fail_reads_temporarily(&self, id: LinkId, kind: Kind, times: u32) {
+        *self.flaky.lock().unwrap() = Some((format!("{id}.{kind}"), times));
+    }
+
+    /// Makes reading the content of the file of the given kind for the link with the
+    /// given ID return one byte less than requested from now on (like a misbehaving
+    /// storage would), or stops truncating if `file` is `None`.
+    ///
+    /// Like with [`fail_reads()`][1], only the reads starting at the beginning of the
+    /// file are affected.
+    ///
+    /// [1]: Self::fail_reads()
+    pub(crate) fn truncate_reads(&self, file: Option<(LinkId, Kind)>) {
+        *self.truncating.lock().unwrap() = file.map(|(id, kind)| format!("{id}.{kind}"));
+    }
+}
+
+impl<A: Access> Layer<A> for Probe {
+    type LayeredAccess = ProbeAccessor<A>;
+
+    fn layer(&self, inner: A) -> Self::LayeredAccess {
+        ProbeAccessor {
+            inner,
+            probe: self.clone(),
+        }
+    }
+}
+
+impl<A: Access> LayeredAccess for ProbeAccessor<A> {
+    type Inner = A;
+    type Reader = ProbeReader<A::Reader>;
+    type Writer = A::Writer;
+    type Lister = ProbeLister<A::Lister>;
+    type Deleter = A::Deleter;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    async fn read(&self, path: &str, args: OpRead) -> opendal::Result<(RpRead, Self::Reader)> {
+        let failing = self.probe.failing.lock().unwrap().as_deref() == Some(path);
+        if failing && args.range().offset() == 0 {
+            return Err(opendal::Error::new(
+                ErrorKind::Unexpected,
+                "injected failure",
+            ));
+        }
+
+        let stalling = self.probe.stalling.lock().unwrap().as_deref() == Some(path);
+        if stalling && args.range().offset() == 0 {
+            std::future::pending::<()>().await;
+        }
+
+        if let Some((flaky, times)) = &mut *self.probe.flaky.lock().unwrap()
+            && flaky == path
+            && *times > 0
+            && args.range().offset() == 0
+        {
+            *times -= 1;
+            return Err(
+                opendal::Error::new(ErrorKind::Unexpected, "injected failure").set_temporary(),
+            );
+        }
+
+        let truncating = self.probe.truncating.lock().unwrap().as_deref() == Some(path);
+        let limit = match args.range().size() {
+            Some(size) if truncating && args.range().offset() == 0 => Some(size as usize - 1),
+            _ => None,
+        };
+
+        let (rp, inner) = self.inner.read(path, args).await?;
+
+        Ok((rp, ProbeReader { inner, limit }))
+    }
+
+    async fn write(&self, path: &str, args: OpWrite) -> opendal::Result<(RpWrite, Self::Writer)> {
+        self.probe.created.fetch_add(1, Ordering::Relaxed);
+        self.inner.write(path, args).await
+    }
+
+    async fn delete(&self) -> opendal::Result<(RpDelete, Self::Deleter)> {
+        self.inner.delete().await
+    }
+
+    async fn list(&self, path: &str, args: OpList) -> opendal::Result<(RpList, Self::Lister)> {
+        let (rp, inner) = self.inner.list(path, args).await?;
+        let probe = self.probe.clone();
+
+        Ok((rp, ProbeLister { inner, probe }))
+    }
+}
+
+impl<R: oio::Read> oio::Read for ProbeReader<R> {
+    async fn read(&mut self) -> opendal::Result<Buffer> {
+        let mut buffer = self.inner.read().await?;
+        if let Some(limit) = &mut self.limit {
+            buffer = buffer.slice(0..buffer.len().min(*limit));
+            *limit -= buffer.len();
+        }
+
+        Ok(buffer)
+    }
+}
+
+impl<L: oio::List> oio::List for ProbeLister<L> {
+    async fn next(&mut self) -> opendal::Result<Option<oio::Entry>> {
+        let entry = self.inner.next().await?;
+        if entry.is_some() {
+            self.probe.listed.fetch_add(1, Ordering::Relaxed);
+        }
+
+        Ok(entry)
+    }
+}
+
+/// Runs the given future to completion on the current thread.
+///
+/// This uses a runtime rather than a simple executor since the local filesystem
+/// service requires one.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    runtime.block_on(future)
+}
+
+/// Returns a new storage keeping its files in memory.
+pub(crate) fn memory() -> Storage {
+    probed().0
+}
+
+/// Returns a new storage keeping its files in memory, along with the probe observing
+/// the operations issued to it.
+pub(crate) fn probed() -> (Storage, Probe) {
+    let probe = Probe::default();
+    let operator = Operator::new(Memory::default())
+        .unwrap()
+        .layer(probe.clone());
+
+    (Storage::new(operator.finish()), probe)
+}
+
+/// A directory created for a test, which is deleted when dropped.
+pub(crate) struct LocalDir(PathBuf);
+
+/// Returns a new storage keeping its files in a new local directory, which (contrarily
+/// to [`memory()`]) supports renaming files, along with that directory.
+pub(crate) fn local() -> (Storage, LocalDir) {
+    let path = std::env::temp_dir().join(format!("chaindict-{}", LinkId::random()));
+    let root = path.to_str().unwrap();
+    let operator = Operator::new(Fs::default().root(root)).unwrap().finish();
+
+    (Storage::new(operator), LocalDir(path))
+}
+
+impl Drop for LocalDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Writes a file of the given kind for the link with the given ID, with the given
+/// content stored as-is.
+pub(crate) async fn write_file(
+    storage: &Storage,
+    id: LinkId,
+    kind: Kind,
+    content: &[u8],
+) -> Result<()> {
+    let mut writer = storage.create(id, kind).await?;
+    writer.write_slice(content).await?;
+    writer.finish().await
+}
+
+/// Flips the bits of the byte at `offset` in the file of the given kind for the link
+/// with the given ID.
+pub(crate) async fn corrupt(
+    storage: &Storage,
+    id: LinkId,
+    kind: Kind,
+    offset: usize,
+) -> Result<()> {
+    let mut reader = storage.open(id, kind).await?;
+    let mut content = reader.read_vec(reader.file_size()).await?;
+    content[offset] ^= 0xff;
+
+    write_file(storage, id, kind, &content).await
+}
+
+/// Writes a chain with one link per range of `links`, each containing the entries in
+/// that range, returning the IDs of the links.
+pub(crate) async fn chain(
+    storage: &Storage,
+    links: impl IntoIterator<Item = Range<u64>>,
+) -> Result<Vec<LinkId>> {
+    extend(storage, None, links).await
+}
+
+/// Writes links extending `previous` like [`chain()`] does, returning their IDs.
+pub(crate) async fn extend(
+    storage: &Storage,
+    previous: Option<LinkId>,
+    links: impl IntoIterator<Item = Range<u64>>,
+) -> Result<Vec<LinkId>> {
+    let mut ids = Vec::new();
+    for entries in links {
+        let previous = ids.last().copied().or(previous);
+        let mut writer = Writer::<U64>::create(previous, storage.clone()).await?;
+        for entry in entries {
+            writer.write_unique(U64(entry)).await?;
+        }
+
+        ids.push(writer.finish().await?);
+    }
+
+    Ok(ids)
+}
+
+/// Writes a link extending `previous` with the given entries, along with a full
+/// snapshot, returning its ID.
+pub(crate) async fn snapshot(
+    storage: &Storage,
+    previous: LinkId,
+    entries: Range<u64>,
+) -> Result<LinkId> {
+    let reader = Reader::<U64>::open(previous, storage.clone()).await?;
+    let mut writer = Writer::<U64>::create(Some(previous), storage.clone()).await?;
+    writer.with_snapshot_from(&reader).await?;
+    for entry in entries {
+        writer.write_unique(U64(entry)).await?;
+    }
+
+    writer.finish().await
+}
+
+/// Writes a chain of links with only a delta, containing the given number of
+/// entries each, the way writers of `storage::LEGACY_VERSION` did.
+pub(crate) async fn legacy_chain(storage: &Storage, counts: &[u32]) -> Result<Vec<LinkId>> {
+    let mut links = Vec::new();
+    let mut entry = 0u64;
+    let mut offset = 0;
+
+    for (index, &count) in counts.iter().enumerate() {
+        let mut content = Vec::new();
+        for _ in 0..count {
+            content.extend_from_slice(&entry.to_be_bytes());
+            entry += 1;
+        }
+
+        let footer = DFooter {
+            previous: links.last().copied(),
+            index: ChainIndex::new(index as u32),
+            total: offset + count,
+            count,
+            version: storage::LEGACY_VERSION,
+        };
+
+        content.extend_from_slice(&footer.to_bytes()?);
+
+        let id = LinkId::random();
+        write_file(storage, id, Delta, &content).await?;
+        links.push(id);
+
+        // Legacy writers started after the number of entries in the previous delta.
+        offset = count;
+    }
+
+    Ok(links)
+}
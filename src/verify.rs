@@ -0,0 +1,447 @@
+use std::{io::SeekFrom, ops::Range};
+
+use futures::{Stream, StreamExt, TryStreamExt, stream};
+
+use crate::{
+    ChainIndex, DFooter, Entry, Error, LinkContext, LinkId, Result, SFooter, Storage,
+    reader::read_snapshot,
+    storage::{self, Kind::*},
+};
+
+/// The number of snapshots whose checksums are verified concurrently by [`verify()`].
+const DEFAULT_CONCURRENCY: usize = 8;
+
+/// The result of verifying the links of a chain (see [`verify()`]).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    /// The report for each of the links of the chain, starting with the latest one.
+    pub links: Vec<LinkReport>,
+}
+
+/// The result of verifying a single link of a chain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinkReport {
+    /// The ID of the link.
+    pub link: LinkId,
+
+    /// The index of the link in the chain.
+    pub index: ChainIndex,
+
+    /// The ranges of `u32`s whose entries are corrupt in the link's snapshot, according
+    /// to the checksums it stores.
+    ///
+    /// This is always empty if the link has no snapshot or if its snapshot doesn't
+    /// store checksums (see [`Writer::with_checksums()`][1]).
+    ///
+    /// [1]: crate::Writer::with_checksums()
+    pub corrupt: Vec<Range<u32>>,
+}
+
+impl Report {
+    /// Returns `true` if no corruption was detected in any link.
+    #[inline]
+    pub fn is_ok(&self) -> bool {
+        self.links.iter().all(LinkReport::is_ok)
+    }
+}
+
+impl LinkReport {
+    /// Returns `true` if no corruption was detected in the link.
+    #[inline]
+    pub fn is_ok(&self) -> bool {
+        self.corrupt.is_empty()
+    }
+}
+
+/// Verifies all of the links of the chain whose latest link is `latest`, checking the
+/// entries of each snapshot storing checksums against them.
+///
+/// This verifies up to 8 snapshots concurrently (see [`verify_with_concurrency()`]),
+/// and only returns once all of the links have been verified (see [`verify_stream()`]
+/// to get the report for each link as soon as it has been verified).
+///
+/// Fails if any of the links' files is missing or cannot be decoded, or if the index
+/// of a link isn't the one following the index of its previous link.
+#[inline]
+pub async fn verify<T: Entry>(latest: LinkId, storage: &Storage) -> Result<Report> {
+    verify_with_concurrency::<T>(latest, storage, DEFAULT_CONCURRENCY).await
+}
+
+/// Verifies all of the links of the chain whose latest link is `latest` like
+/// [`verify()`], verifying up to `concurrency` (rounded up to `1`) snapshots
+/// concurrently.
+///
+/// The links' footers are first read one after the other, since the ID of each link's
+/// previous link is stored in its footer. The snapshots storing checksums are then
+/// read and verified concurrently, which can be far faster when verifying deep chains
+/// stored on high-latency storage. The report doesn't depend on `concurrency`.
+pub async fn verify_with_concurrency<T: Entry>(
+    latest: LinkId,
+    storage: &Storage,
+    concurrency: usize,
+) -> Result<Report> {
+    let mut report = Report::default();
+    let mut checksummed = Vec::new();
+    let mut next = Some(latest);
+
+    while let Some(link) = next {
+        let (index, previous, checksums) = read_footer::<T>(storage, link).await?;
+        check_follows(report.links.last(), index)?;
+
+        if checksums > 0 {
+            checksummed.push((report.links.len(), link));
+        }
+
+        report.links.push(LinkReport {
+            link,
+            index,
+            corrupt: Vec::new(),
+        });
+
+        next = previous;
+    }
+
+    // The snapshots are opened again rather than kept open while walking the chain, so
+    // that at most `concurrency` of them are in memory at once.
+    let verify_link = |(position, link)| async move {
+        let corrupt = verify_snapshot::<T>(storage, link).await?;
+        Ok::<_, Error>((position, corrupt))
+    };
+
+    let mut results = stream::iter(checksummed)
+        .map(verify_link)
+        .buffer_unordered(concurrency.max(1));
+
+    while let Some((position, corrupt)) = results.try_next().await? {
+        report.links[position].corrupt = corrupt;
+    }
+
+    Ok(report)
+}
+
+/// Checks that the snapshot of `link` contains exactly the entries of the deltas of
+/// all of the links up to `link` (included), in the same order.
+///
+/// This reads both the snapshot and all of the deltas, and allows making sure that a
+/// snapshot isn't silently wrong (e.g. after compacting a chain).
+///
+/// Fails with [`Error::Mismatch`] if the `u32` assigned to an entry (or the number of
+/// entries) differs, or if the snapshot or any of the deltas is missing or cannot be
+/// decoded.
+pub async fn verify_snapshot_consistency<T: Entry>(link: LinkId, storage: &Storage) -> Result<()> {
+    let mut reader = storage.open(link, Snapshot).await?;
+    let footer = SFooter::read(&mut reader).await?;
+
+    let mut snapshot = Vec::with_capacity(footer.count as usize);
+    let insert = |entry| snapshot.push(entry);
+    read_snapshot(storage, link, reader, &footer, insert).await?;
+
+    let mut deltas = Vec::new();
+    let mut next = Some(link);
+
+    while let Some(link) = next {
+        let mut reader = storage.open(link, Delta).await?;
+        let footer = DFooter::read(&mut reader).await?;
+        footer.check_size(link, &reader, T::SIZE)?;
+
+        let mut delta = Vec::with_capacity(footer.count as usize);
+        let context = LinkContext::delta(link, &footer);
+        for _ in 0..footer.count {
+            delta.push(T::read_with_context(&mut reader, &context).await?);
+        }
+
+        deltas.push(delta);
+        next = footer.previous;
+    }
+
+    let mut snapshot = snapshot.into_iter();
+    let mut index = 0;
+
+    for entry in deltas.into_iter().rev().flatten() {
+        if snapshot.next().as_ref() != Some(&entry) {
+            return Err(Error::Mismatch { link, index });
+        }
+
+        index += 1;
+    }
+
+    if snapshot.next().is_some() {
+        return Err(Error::Mismatch { link, index });
+    }
+
+    Ok(())
+}
+
+/// Verifies the links of the chain whose latest link is `latest` like [`verify()`],
+/// yielding the report for each of them as soon as it has been verified, starting with
+/// the latest one.
+///
+/// This allows reporting progress while verifying huge chains, and stopping early
+/// (by dropping the stream). The report returned by [`verify()`] contains the items of
+/// the stream, in the same order. Up to 8 snapshots are verified concurrently, while
+/// the links' footers are still read one after the other.
+///
+/// The stream ends after yielding the first error, which is returned in the same cases
+/// as [`verify()`].
+pub fn verify_stream<T: Entry>(
+    latest: LinkId,
+    storage: &Storage,
+) -> impl Stream<Item = Result<LinkReport>> + '_ {
+    // The footers are read one after the other, since the ID of each link's previous
+    // link is stored in its footer, along with the index of its successor.
+    let links = stream::try_unfold((Some(latest), None), move |(next, successor)| async move {
+        let Some(link) = next else {
+            return Ok(None);
+        };
+
+        let (index, previous, checksums) = read_footer::<T>(storage, link).await?;
+        check_follows(successor.as_ref(), index)?;
+
+        let report = LinkReport {
+            link,
+            index,
+            corrupt: Vec::new(),
+        };
+
+        Ok(Some((
+            (report.clone(), checksums),
+            (previous, Some(report)),
+        )))
+    });
+
+    let verify_link = move |(mut report, checksums): (LinkReport, usize)| async move {
+        if checksums > 0 {
+            report.corrupt = verify_snapshot::<T>(storage, report.link).await?;
+        }
+
+        Ok(report)
+    };
+
+    links.map_ok(verify_link).try_buffered(DEFAULT_CONCURRENCY)
+}
+
+/// Reads the footer of the snapshot of `link` (or of its delta, if it has no snapshot),
+/// returning the index of the link, the ID of its previous link and the number of
+/// checksums stored in its snapshot.
+async fn read_footer<T: Entry>(
+    storage: &Storage,
+    link: LinkId,
+) -> Result<(ChainIndex, Option<LinkId>, usize)> {
+    match storage.open_maybe(link, Snapshot).await? {
+        Some(mut reader) => {
+            let footer = SFooter::read(&mut reader).await?;
+            Ok((footer.index, footer.previous, footer.checksums()))
+        }
+
+        None => {
+            let mut reader = storage.open(link, Delta).await?;
+            let footer = DFooter::read(&mut reader).await?;
+            footer.check_size(link, &reader, T::SIZE)?;
+            Ok((footer.index, footer.previous, 0))
+        }
+    }
+}
+
+/// Fails with [`Error::Inconsistent`] if the index of the link following a link in its
+/// chain (whose report is `successor`, if any) doesn't follow the link's `index`.
+fn check_follows(successor: Option<&LinkReport>, index: ChainIndex) -> Result<()> {
+    if let Some(successor) = successor
+        && successor.index.get() != index.get() + 1
+    {
+        return Err(Error::Inconsistent {
+            link: successor.link,
+            reason: "link index doesn't follow its previous link's",
+        });
+    }
+
+    Ok(())
+}
+
+/// Opens the snapshot of `link` again and checks its entries against the checksums it
+/// stores, returning the ranges of `u32`s whose entries are corrupt.
+async fn verify_snapshot<T: Entry>(storage: &Storage, link: LinkId) -> Result<Vec<Range<u32>>> {
+    let mut reader = storage.open(link, Snapshot).await?;
+    let footer = SFooter::read(&mut reader).await?;
+
+    verify_checksums::<T>(&mut reader, &footer).await
+}
+
+/// Checks the entries of the snapshot being read by `reader` against the checksums it
+/// stores, returning the ranges of `u32`s whose entries are corrupt.
+async fn verify_checksums<T: Entry>(
+    reader: &mut storage::Reader,
+    footer: &SFooter,
+) -> Result<Vec<Range<u32>>> {
+    let num_checksums = footer.checksums();
+    if num_checksums == 0 {
+        return Ok(Vec::new());
+    }
+
+    // Reading the footer made the reader act as-if the checksums did not exist.
+    let end = reader.file_size();
+    reader.set_file_size(end + num_checksums * 4);
+    reader.seek(SeekFrom::Start(end as u64))?;
+
+    let mut checksums = Vec::with_capacity(num_checksums);
+    for _ in 0..num_checksums {
+        checksums.push(reader.read_u32().await?);
+    }
+
+    reader.set_file_size(end);
+    reader.seek(SeekFrom::Start(footer.prefix))?;
+
+    let mut corrupt: Vec<Range<u32>> = Vec::new();
+    for (block, checksum) in checksums.into_iter().enumerate() {
+        let stored = footer.base_count + footer.prefix_count;
+        let start = stored + block as u32 * footer.checksum_block;
+        let end = start
+            .saturating_add(footer.checksum_block)
+            .min(footer.count);
+
+        let bytes = reader.read_vec((end - start) as usize * T::SIZE).await?;
+        if crc32fast::hash(&bytes) == checksum {
+            continue;
+        }
+
+        // Adjacent corrupt blocks are reported as a single range.
+        match corrupt.last_mut() {
+            Some(range) if range.end == start => range.end = end,
+            _ => corrupt.push(start..end),
+        }
+    }
+
+    Ok(corrupt)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::pin;
+
+    use crate::{
+        Writer,
+        tests::{U64, block_on, chain, corrupt, extend, memory, snapshot},
+    };
+
+    use super::*;
+
+    /// Writes a link extending `previous` with the given entries, along with a snapshot
+    /// storing the checksum of each block of 2 entries, returning its ID.
+    async fn checksummed(
+        storage: &Storage,
+        previous: Option<LinkId>,
+        entries: Range<u64>,
+    ) -> Result<LinkId> {
+        let mut writer = Writer::<U64>::create(previous, storage.clone()).await?;
+        writer.with_checksums(2)?;
+        writer.with_snapshot().await?;
+        for entry in entries {
+            writer.write_unique(U64(entry)).await?;
+        }
+
+        writer.finish().await
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)] // the corrupt ranges are compared with arrays
+    fn corrupt_blocks() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let first = checksummed(&storage, None, 0..7).await?;
+            let latest = extend(&storage, Some(first), Some(7..8)).await?[0];
+
+            assert!(verify::<U64>(latest, &storage).await?.is_ok());
+
+            // Corrupting an entry only reports the block containing it, and adjacent
+            // corrupt blocks are reported as a single range.
+            corrupt(&storage, first, Snapshot, 3 * U64::SIZE).await?;
+            let report = verify::<U64>(latest, &storage).await?;
+            assert!(report.links[0].is_ok());
+            assert_eq!(report.links[1].corrupt, [2..4]);
+
+            corrupt(&storage, first, Snapshot, 4 * U64::SIZE).await?;
+            corrupt(&storage, first, Snapshot, 6 * U64::SIZE + 7).await?;
+            let report = verify::<U64>(latest, &storage).await?;
+            assert_eq!(report.links[1].corrupt, [2..7]);
+            assert!(!report.is_ok());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn concurrent_verify() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let mut links = Vec::new();
+            for start in (0..24).step_by(4) {
+                links.push(checksummed(&storage, links.last().copied(), start..start + 4).await?);
+            }
+
+            corrupt(&storage, links[1], Snapshot, 0).await?;
+            corrupt(&storage, links[4], Snapshot, 9 * U64::SIZE).await?;
+
+            let latest = links[5];
+            let sequential = verify_with_concurrency::<U64>(latest, &storage, 1).await?;
+            assert_eq!(sequential.links.len(), 6);
+            assert_eq!(sequential.links[1].corrupt, Vec::from_iter(Some(8..10)));
+            assert_eq!(sequential.links[4].corrupt, Vec::from_iter(Some(0..2)));
+
+            // The reports don't depend on how many snapshots are verified at once.
+            for concurrency in [0, 2, 8] {
+                let report = verify_with_concurrency::<U64>(latest, &storage, concurrency).await?;
+                assert_eq!(report, sequential);
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn snapshot_consistency() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..4]).await?;
+            let snapshot = snapshot(&storage, links[1], 4..6).await?;
+            verify_snapshot_consistency::<U64>(snapshot, &storage).await?;
+
+            corrupt(&storage, snapshot, Snapshot, 4 * U64::SIZE - 1).await?;
+            assert!(matches!(
+                verify_snapshot_consistency::<U64>(snapshot, &storage).await,
+                Err(Error::Mismatch { link, index: 3 }) if link == snapshot
+            ));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn streamed_report() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let first = checksummed(&storage, None, 0..4).await?;
+            let second = checksummed(&storage, Some(first), 4..10).await?;
+            let latest = extend(&storage, Some(second), [10..11, 11..12]).await?[1];
+            corrupt(&storage, second, Snapshot, 5 * U64::SIZE).await?;
+
+            // The stream yields the links of the report, in the same order.
+            let report = verify::<U64>(latest, &storage).await?;
+            let streamed = verify_stream::<U64>(latest, &storage)
+                .try_collect::<Vec<_>>()
+                .await?;
+            assert_eq!(streamed, report.links);
+            assert_eq!(streamed.len(), 4);
+            assert!(!streamed[2].is_ok());
+
+            // The stream ends with the first error.
+            let missing = LinkId::random();
+            let mut stream = pin!(verify_stream::<U64>(missing, &storage));
+            assert!(matches!(
+                stream.next().await,
+                Some(Err(Error::DoesNotExist { .. }))
+            ));
+            assert!(stream.next().await.is_none());
+
+            Ok(())
+        })
+    }
+}
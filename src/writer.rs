@@ -1,19 +1,37 @@
-use std::{hash::BuildHasher, marker::PhantomData};
+use std::{
+    cmp::Ordering, collections::BTreeMap, fmt, hash::BuildHasher, marker::PhantomData, ops::Range,
+    pin::pin,
+};
 
-use futures::future::try_join;
+use futures::{TryStreamExt, future::try_join};
 
 use crate::{
-    DFooter, Entry, Error, LinkId, Reader, Result, Storage,
+    ChainIndex, DFooter, Entry, Error, LinkId, Reader, Result, Storage,
+    reader::read_snapshot,
     snapshot::Footer as SFooter,
-    storage::{self, Kind::*},
+    storage::{self, Kind, Kind::*},
 };
 
+mod batch;
+mod builder;
 mod lazy;
 
+pub use self::batch::Batch;
+pub use self::builder::WriterBuilder;
 pub use self::lazy::LazyWriter;
 
 /// A writer which allows adding entries to a chain stored in some storage by
 /// creating a new link.
+///
+/// Once some of the link's files have been created, either [`finish()`][1] or
+/// [`abort()`][2] must be called: since they can't be called when the writer is
+/// dropped, dropping it instead leaves the files partly written (without any footer),
+/// which breaks reading or verifying the chain from them. This is reported on the
+/// standard error in debug builds.
+///
+/// [1]: Self::finish()
+/// [2]: Self::abort()
+#[must_use = "the link's files are left partly written unless `finish()` or `abort()` is called"]
 pub struct Writer<T: Entry> {
     storage: Storage,
 
@@ -24,6 +42,14 @@ pub struct Writer<T: Entry> {
     /// inserted as part of the link this is creating.
     count: u32,
 
+    /// The number of `u32`s following `count` which have been reserved (see
+    /// [`Writer::reserve_indices()`]) but whose entry hasn't been written yet.
+    reserved: u32,
+
+    /// The entries written for reserved `u32`s which cannot be written to the link's
+    /// files yet, because the entries for some of the preceding ones haven't been.
+    pending: BTreeMap<u32, T>,
+
     /// The ID of the link this is creating.
     id: LinkId,
 
@@ -32,66 +58,590 @@ pub struct Writer<T: Entry> {
     previous: Option<LinkId>,
 
     /// The index of the link this is creating in the chain of links.
-    index: u32,
+    index: ChainIndex,
 
     /// The writer for the delta file for the link this is creating.
-    delta: storage::Writer,
+    ///
+    /// The delta file is only created when the first entry is written, to avoid
+    /// starting uploads (e.g. multipart ones) for links which end up being empty.
+    delta: Option<storage::Writer>,
 
     /// The writer for the snapshot file for the link this is creating.
     snapshot: Option<storage::Writer>,
 
+    /// The writer for the values file for the link this is creating, along with the
+    /// size of the values and the number of values written to it.
+    ///
+    /// The values file is only created when the first value is written.
+    values: Option<(storage::Writer, usize, u32)>,
+
+    /// The ID of the link whose snapshot is the base of the snapshot file for the link
+    /// this is creating, along with the number of entries it contains, if it is an
+    /// incremental snapshot.
+    base: Option<(LinkId, u32)>,
+
+    /// The size of the previous link's snapshot file stored at the start of the
+    /// snapshot file for the link this is creating, along with the number of entries it
+    /// contains, if it is an appended snapshot.
+    prefix: Option<(u64, u32)>,
+
+    /// The entries of the snapshot file for the link this is creating, if it is a
+    /// sorted snapshot.
+    sorted: Option<SortedEntries<T>>,
+
+    /// Whether the link's files are written to temporary paths, and only moved to
+    /// their final path once all of them have been written.
+    temporary: bool,
+
+    /// How the link's files are finished.
+    strategy: FinishStrategy,
+
+    /// The maximum number of entries the chain is allowed to contain, if any.
+    max_total: Option<u32>,
+
+    /// Whether the link's delta file is skipped if a snapshot file is written for it.
+    snapshot_only: bool,
+
+    /// The number of entries covered by each checksum stored in the snapshot file for
+    /// the link this is creating, if checksums are stored.
+    checksum_block: Option<u32>,
+
+    /// Whether to check that no other link extending the same previous link was
+    /// created once the link is finished.
+    check_fork: bool,
+
+    /// Whether the link can be finished without any entry being added to it, as long as
+    /// a snapshot file is written for it.
+    allow_empty: bool,
+
+    /// The version of the storage format the link's footers are written with.
+    version: u16,
+
     _t: PhantomData<T>,
 }
 
+/// The entries of a sorted snapshot file, which are only written once the link is
+/// finished.
+struct SortedEntries<T> {
+    /// All of the entries of the snapshot, ordered by the `u32` which represent them.
+    entries: Vec<T>,
+
+    /// The function used to sort the entries.
+    cmp: fn(&T, &T) -> Ordering,
+}
+
+/// A link whose files have all been written, but which might not have been moved to
+/// their final path yet.
+struct Prepared {
+    storage: Storage,
+
+    /// Whether the link's files were written to temporary paths.
+    temporary: bool,
+
+    /// Whether a delta file was written for the link.
+    has_delta: bool,
+
+    /// Whether a snapshot file was written for the link.
+    has_snapshot: bool,
+
+    /// Whether a values file was written for the link.
+    has_values: bool,
+
+    /// The ID of the previous link in the chain, which the link is extending.
+    previous: Option<LinkId>,
+
+    /// Whether to check that no other link extending the same previous link was
+    /// created once the link is committed.
+    check_fork: bool,
+
+    /// The information about the link.
+    summary: LinkSummary,
+}
+
+/// How the files of a link are finished by [`Writer::finish()`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FinishStrategy {
+    /// The files are finished concurrently.
+    #[default]
+    Parallel,
+
+    /// The files are finished one after the other, the snapshot first and the delta
+    /// last, so that the presence of a delta file implies that the link is complete.
+    ///
+    /// This also avoids having both files' remaining bytes compete for bandwidth.
+    Sequential,
+}
+
+/// Information about a link created by [`Writer::finish_with_summary()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LinkSummary {
+    /// The ID assigned to the link.
+    pub id: LinkId,
+
+    /// The index of the link in its chain.
+    pub index: ChainIndex,
+
+    /// The number of entries added by the link.
+    pub count: u32,
+
+    /// The number of entries present in the chain up to the link (included).
+    pub total: u32,
+
+    /// Whether a snapshot file was written for the link.
+    pub has_snapshot: bool,
+}
+
 impl<T: Entry> Writer<T> {
     /// Creates a new writer for the given storage, creating a link which is extending
     /// `previous`.
+    ///
+    /// This doesn't check whether `previous` exists (see [`check_previous()`][1]).
+    ///
+    /// [1]: Self::check_previous()
     pub async fn create(previous: Option<LinkId>, storage: Storage) -> Result<Self> {
+        Self::create_with(previous, storage, false).await
+    }
+
+    /// Creates a new writer for the given storage, creating a link which is extending
+    /// `previous`, and which is committed in two phases.
+    ///
+    /// If the storage supports renaming files, the link's files are written to
+    /// temporary paths and only moved to their final path once all of them have been
+    /// written, so that a crash while finishing the link never leaves it half-written.
+    ///
+    /// Otherwise, this falls back to finishing the snapshot file before the delta
+    /// file, so that the presence of a delta file implies that the link is complete.
+    /// Note that in that case, a crash while finishing the link can still leave a
+    /// snapshot file without its delta file behind.
+    pub async fn create_two_phase(previous: Option<LinkId>, storage: Storage) -> Result<Self> {
+        let temporary = storage.supports_rename();
+        let mut writer = Self::create_with(previous, storage, temporary).await?;
+        if !temporary {
+            writer.strategy = FinishStrategy::Sequential;
+        }
+
+        Ok(writer)
+    }
+
+    /// Creates a new writer for the given storage, creating a link which is extending
+    /// `previous`, whose files are written to temporary paths if `temporary` is `true`.
+    async fn create_with(
+        previous: Option<LinkId>,
+        storage: Storage,
+        temporary: bool,
+    ) -> Result<Self> {
         let id = LinkId::random();
-        let delta = storage.create(id, Delta).await?;
 
         Ok(Self {
             storage,
 
             offset: 0,
             count: 0,
+            reserved: 0,
+            pending: BTreeMap::new(),
 
             id,
             previous,
-            index: 0,
+            index: ChainIndex::default(),
 
-            delta,
+            delta: None,
             snapshot: None,
+            base: None,
+            prefix: None,
+            sorted: None,
+
+            temporary,
+            strategy: FinishStrategy::default(),
+            max_total: None,
+            snapshot_only: false,
+            checksum_block: None,
+            check_fork: false,
+            allow_empty: false,
+            version: storage::VERSION,
+            values: None,
 
             _t: PhantomData,
         })
     }
 
+    /// Returns the number of bytes which have been written to the link's file(s) so far,
+    /// including its values file.
+    #[inline]
+    pub fn bytes_written(&self) -> u64 {
+        let delta = self.delta.as_ref().map(storage::Writer::file_size);
+        let snapshot = self.snapshot.as_ref().map(storage::Writer::file_size);
+        let values = self
+            .values
+            .as_ref()
+            .map(|(values, _, _)| values.file_size());
+
+        (delta.unwrap_or_default() + snapshot.unwrap_or_default() + values.unwrap_or_default())
+            as u64
+    }
+
+    /// Assigns the given ID to the link, instead of the random one it was created with.
+    ///
+    /// This allows choosing the IDs of links deterministically (e.g. with
+    /// [`LinkId::from_name()`]). The caller _must_ guarantee that no other link with the
+    /// same ID exists in the storage.
+    ///
+    /// Fails if some of the link's files were already created.
+    pub fn with_id(&mut self, id: LinkId) -> Result<()> {
+        if self.has_files() {
+            return Err(Error::Unsupported {
+                operation: "changing the ID of a link whose files were created",
+            });
+        }
+
+        self.id = id;
+
+        Ok(())
+    }
+
+    /// Sets how the link's files should be finished by [`finish()`][1].
+    ///
+    /// Note that using [`FinishStrategy::Parallel`] with a writer created with
+    /// [`create_two_phase()`][2] on a storage which doesn't support renaming files
+    /// loses the guarantee that the presence of a delta file implies that the link is
+    /// complete.
+    ///
+    /// [1]: Self::finish()
+    /// [2]: Self::create_two_phase()
+    #[inline]
+    pub fn with_finish_strategy(&mut self, strategy: FinishStrategy) {
+        self.strategy = strategy;
+    }
+
+    /// Sets the maximum number of entries the chain is allowed to contain once this
+    /// link has been added to it.
+    ///
+    /// [`write_unique()`][1] then fails with [`Error::CapacityExceeded`] instead of
+    /// writing any entry which would make the chain contain more than `max_total`
+    /// entries.
+    ///
+    /// [1]: Self::write_unique()
+    #[inline]
+    pub fn with_max_total(&mut self, max_total: u32) {
+        self.max_total = Some(max_total);
+    }
+
+    /// Skips writing the link's delta file if a snapshot file is written for it.
+    ///
+    /// Since readers load the snapshot of a link whenever it has one, the delta file of
+    /// such a link only duplicates the entries inserted as part of it. Note that the
+    /// link can then only be loaded from its snapshot, which means that
+    /// [`Reader::reload()`] has to load the whole snapshot when reaching it.
+    ///
+    /// If no snapshot file is written for the link, or entries have already been added
+    /// to the link's delta file, this has no effect.
+    #[inline]
+    pub fn with_snapshot_only(&mut self) {
+        self.snapshot_only = true;
+    }
+
+    /// Checks that no other link extends the same previous link once the link is
+    /// finished, making [`finish()`][1] fail with [`Error::Forked`] if one does (e.g.
+    /// because another writer raced with this one).
+    ///
+    /// Once the link's files have been written, this records that the link extends its
+    /// previous link (as an empty file in a directory next to the previous link's delta
+    /// file), then lists the links recorded as extending it. The link thus exists even
+    /// if this fails, and this only lists the links extending the same previous link,
+    /// whatever the number of links in the storage. Links whose writer didn't check for
+    /// forks aren't recorded, and thus aren't detected.
+    ///
+    /// This is best-effort: on storages where files don't become visible atomically, a
+    /// concurrent link might only become visible after the check. Two writers racing
+    /// with each other are however guaranteed to not both miss the other's link, as
+    /// long as both of them check.
+    ///
+    /// This has no effect if the link is the first one in its chain.
+    ///
+    /// [1]: Self::finish()
+    #[inline]
+    pub fn with_fork_check(&mut self) {
+        self.check_fork = true;
+    }
+
+    /// Allows [`finish()`][1] to succeed even if no entries were added to the link, as
+    /// long as a snapshot file is written for it (e.g. to create a checkpoint link
+    /// materializing the full snapshot of the chain).
+    ///
+    /// The delta file of such a link contains no entries. Without a snapshot, an empty
+    /// link would be meaningless, so [`finish()`][1] still fails with [`Error::Empty`].
+    ///
+    /// [1]: Self::finish()
+    #[inline]
+    pub fn with_empty_allowed(&mut self) {
+        self.allow_empty = true;
+    }
+
+    /// Writes the link's footers with the given version of the storage format rather
+    /// than with [`VERSION`][1].
+    ///
+    /// This is meant to create files as older or newer versions of the crate would
+    /// (e.g. to test that they are read, or rejected with [`Error::Version`]). Only the
+    /// footers are affected, and finishing the link fails if it uses features which
+    /// `version` doesn't support.
+    ///
+    /// [1]: storage::VERSION
+    #[cfg(feature = "format-version")]
+    #[inline]
+    pub fn with_format_version(&mut self, version: u16) {
+        self.version = version;
+    }
+
+    /// Stores the checksum of each block of `block_size` entries (rounded up to `1`) in
+    /// the link's snapshot file, if one is written for it.
+    ///
+    /// This allows [`verify()`][1] to report which entries of the snapshot are corrupt,
+    /// rather than only that the snapshot is.
+    ///
+    /// Fails if the snapshot file was already created, if it is a sorted snapshot, or if
+    /// entries are zero-sized.
+    ///
+    /// [1]: crate::verify()
+    pub fn with_checksums(&mut self, block_size: u32) -> Result<()> {
+        if T::SIZE == 0 {
+            return Err(Error::Unsupported {
+                operation: "checksums of zero-sized entries",
+            });
+        }
+
+        if self.snapshot.is_some() {
+            return Err(Error::Unsupported {
+                operation: "checksums of a snapshot which was already created",
+            });
+        }
+
+        self.checksum_block = Some(block_size.max(1));
+
+        Ok(())
+    }
+
     /// Writes a snapshot file for the link.
     ///
     /// Fails if entries have already been added to the link's delta file.
     pub async fn with_snapshot(&mut self) -> Result<()> {
         // TODO(MLB): optionally start loading snapshot in background
 
-        if self.delta.file_size() != 0 {
+        if self.has_entries() {
             return Err(Error::NotEmpty);
         }
 
-        let mut snapshot = self.storage.create(self.id, Snapshot).await?;
+        let mut snapshot = self.create_snapshot().await?;
         if let Some(previous) = self.previous {
-            // TODO(MLB): if append is supported, copy the file then append to it (ignoring the footer in the middle when reading)
             // TODO(MLB): read + start writing in the background, buffering while preparing
 
-            let mut previous = self.storage.open(previous, Snapshot).await?;
+            let id = previous;
+            let mut previous = self.storage.open(id, Snapshot).await?;
+            let footer = SFooter::read(&mut previous).await?;
+
+            let base = match footer.base {
+                Some(base) => {
+                    let mut base = self.storage.open(base, Snapshot).await?;
+                    let footer = SFooter::read(&mut base).await?;
+                    Some((base, footer))
+                }
+
+                None => None,
+            };
+
+            let copyable = |footer: &SFooter| !footer.sorted && footer.prefix == 0;
+            if !copyable(&footer) || base.as_ref().is_some_and(|(_, footer)| !copyable(footer)) {
+                // Sorted snapshots don't store their entries in the order of the `u32`s
+                // which represent them, and appended snapshots store footers between
+                // them, so they need to be decoded and encoded again.
+                let mut entries = Vec::with_capacity(footer.count as usize);
+                let insert = |entry: T| entries.push(entry);
+                read_snapshot(&self.storage, id, previous, &footer, insert).await?;
+
+                for entry in entries {
+                    snapshot.write_entry(&entry).await?;
+                }
+            } else {
+                // If the previous snapshot is an incremental one, the entries stored in its
+                // base need to be copied first.
+                if let Some((base, _)) = base {
+                    snapshot.copy_from(base).await?;
+                }
+
+                snapshot.copy_from(previous).await?;
+            }
+
+            self.offset = footer.count;
+            self.count = footer.count;
+            self.index = footer.index.next();
+        }
+
+        self.snapshot = Some(snapshot);
+
+        Ok(())
+    }
+
+    /// Writes an incremental snapshot file for the link.
+    ///
+    /// Contrarily to [`with_snapshot()`][1], this doesn't copy all of the entries of the
+    /// previous link's snapshot, but only those which were inserted since the latest
+    /// full snapshot (which is used as the base of the incremental snapshot). Loading
+    /// the link then requires reading both the base's snapshot and this one.
+    ///
+    /// If the link has no previous link, this writes a full snapshot.
+    ///
+    /// Fails if entries have already been added to the link's delta file.
+    ///
+    /// [1]: Self::with_snapshot()
+    pub async fn with_incremental_snapshot(&mut self) -> Result<()> {
+        if self.has_entries() {
+            return Err(Error::NotEmpty);
+        }
+
+        let mut snapshot = self.create_snapshot().await?;
+        if let Some(id) = self.previous {
+            let mut previous = self.storage.open(id, Snapshot).await?;
+            let footer = SFooter::read(&mut previous).await?;
+
+            match footer.base {
+                // The previous snapshot is a full one – it can be used as the base as-is.
+                None => self.base = Some((id, footer.count)),
+
+                // The previous snapshot is an incremental one – we share its base and copy
+                // the entries it contains.
+                Some(base) => {
+                    snapshot.copy_from(previous).await?;
+                    self.base = Some((base, footer.base_count));
+                }
+            }
+
+            self.offset = footer.count;
+            self.count = footer.count;
+            self.index = footer.index.next();
+        }
+
+        self.snapshot = Some(snapshot);
+
+        Ok(())
+    }
+
+    /// Writes an appended snapshot file for the link.
+    ///
+    /// Contrarily to [`with_snapshot()`][1], this doesn't copy the entries of the
+    /// previous link's snapshot one after the other, but stores its whole file as-is
+    /// (including its footer) at the start of the link's snapshot, followed by the
+    /// entries added to the link. If the storage can copy files and append to them,
+    /// the previous link's snapshot isn't even read. Loading the link then requires
+    /// reading the footers of all of the snapshots appended one after the other, from
+    /// the latest to the oldest.
+    ///
+    /// If the link has no previous link, this writes a full snapshot.
+    ///
+    /// Fails if entries have already been added to the link's delta file, or if the
+    /// storage is encrypted (since files are encrypted all at once).
+    ///
+    /// [1]: Self::with_snapshot()
+    pub async fn with_appended_snapshot(&mut self) -> Result<()> {
+        if self.has_entries() {
+            return Err(Error::NotEmpty);
+        }
+
+        if self.storage.is_encrypted() {
+            return Err(Error::Unsupported {
+                operation: "appended snapshot of an encrypted storage",
+            });
+        }
+
+        let Some(id) = self.previous else {
+            self.snapshot = Some(self.create_snapshot().await?);
+            return Ok(());
+        };
+
+        let mut previous = self.storage.open(id, Snapshot).await?;
+        let size = previous.file_size();
+        let footer = SFooter::read(&mut previous).await?;
+
+        let appended = self
+            .storage
+            .create_appended(self.id, Snapshot, id, self.temporary);
+        let mut snapshot = match appended.await? {
+            Some(snapshot) => snapshot,
+            None => {
+                let mut snapshot = create(&self.storage, self.id, Snapshot, self.temporary).await?;
+                snapshot.copy_from(previous.prefix(size)).await?;
+                snapshot
+            }
+        };
+
+        // The checksums only cover the entries stored after the previous link's snapshot.
+        if let Some(block_size) = self.checksum_block {
+            snapshot.with_checksums(block_size as usize * T::SIZE);
+        }
+
+        self.prefix = Some((size as u64, footer.count));
+        self.offset = footer.count;
+        self.count = footer.count;
+        self.index = footer.index.next();
+
+        self.snapshot = Some(snapshot);
+
+        Ok(())
+    }
+
+    /// Writes a sorted snapshot file for the link.
+    ///
+    /// Contrarily to [`with_snapshot()`][1], the entries aren't stored in the order of
+    /// the `u32`s which represent them but sorted, along with the position at which
+    /// each of them is stored, which readers use to assign them their `u32`. Storing
+    /// similar entries next to each other can make the snapshot compress better.
+    ///
+    /// Since entries can only be sorted once all of them are known, all of the
+    /// snapshot's entries (including the previous link's) are kept in memory until
+    /// [`finish()`][2] is called, which also means that entries cannot be written
+    /// with [`write_unique_raw()`][3].
+    ///
+    /// Fails if entries have already been added to the link's delta file, or if
+    /// checksums are stored (see [`with_checksums()`][4]).
+    ///
+    /// [1]: Self::with_snapshot()
+    /// [2]: Self::finish()
+    /// [3]: Self::write_unique_raw()
+    /// [4]: Self::with_checksums()
+    pub async fn with_sorted_snapshot(&mut self) -> Result<()>
+    where
+        T: Ord,
+    {
+        if self.has_entries() {
+            return Err(Error::NotEmpty);
+        }
+
+        // Checksums cover blocks of consecutive entries, which wouldn't be useful to
+        // localize corrupted entries once they are sorted.
+        if self.checksum_block.is_some() {
+            return Err(Error::Unsupported {
+                operation: "checksums of a sorted snapshot",
+            });
+        }
+
+        let snapshot = self.create_snapshot().await?;
+        let mut entries = Vec::new();
+
+        if let Some(id) = self.previous {
+            let mut previous = self.storage.open(id, Snapshot).await?;
             let footer = SFooter::read(&mut previous).await?;
-            snapshot.copy_from(previous).await?;
+
+            entries.reserve(footer.count as usize);
+            let insert = |entry| entries.push(entry);
+            read_snapshot(&self.storage, id, previous, &footer, insert).await?;
 
             self.offset = footer.count;
             self.count = footer.count;
-            self.index = footer.index + 1;
+            self.index = footer.index.next();
         }
 
         self.snapshot = Some(snapshot);
+        self.sorted = Some(SortedEntries {
+            entries,
+            cmp: T::cmp,
+        });
 
         Ok(())
     }
@@ -100,12 +650,13 @@ impl<T: Entry> Writer<T> {
     ///
     /// The latest link loaded by `previous` must be the ID of the previous link.
     ///
-    /// Fails if entries have already been added to the link's delta file.
-    pub async fn with_snapshot_from<S: BuildHasher>(
+    /// Fails if entries have already been added to the link's delta file, or if
+    /// `previous` only loaded a part of its chain (see [`Reader::open_partial()`]).
+    pub async fn with_snapshot_from<S: BuildHasher, V: Entry>(
         &mut self,
-        previous: &Reader<T, S>,
+        previous: &Reader<T, S, V>,
     ) -> Result<()> {
-        if self.delta.file_size() != 0 {
+        if self.has_entries() {
             return Err(Error::NotEmpty);
         }
 
@@ -120,117 +671,1354 @@ impl<T: Entry> Writer<T> {
             });
         }
 
-        let mut snapshot = self.storage.create(self.id, Snapshot).await?;
+        if previous.first_loaded() != 0 {
+            return Err(Error::Unsupported {
+                operation: "snapshot from a partially loaded reader",
+            });
+        }
+
+        let mut snapshot = self.create_snapshot().await?;
         for (_, entry) in previous.iter() {
-            entry.write(&mut snapshot).await?;
+            snapshot.write_entry(entry).await?;
         }
 
         self.offset = previous.len();
         self.count = previous.len();
-        self.index = previous.index() + 1;
+        self.index = previous.current_index().next();
 
-        if let Some(previous) = self.previous {
-            // TODO(MLB): start writing in the background, buffering while preparing
+        self.snapshot = Some(snapshot);
 
-            let mut previous = self.storage.open(previous, Snapshot).await?;
-            let footer = SFooter::read(&mut previous).await?;
-            snapshot.copy_from(previous).await?;
+        Ok(())
+    }
 
-            self.offset = footer.count;
-            self.count = footer.count;
-            self.index = footer.index + 1;
+    /// Creates the snapshot file for the link, computing the checksums of its entries if
+    /// they are stored.
+    async fn create_snapshot(&self) -> Result<storage::Writer> {
+        let mut snapshot = create(&self.storage, self.id, Snapshot, self.temporary).await?;
+        if let Some(block_size) = self.checksum_block {
+            snapshot.with_checksums(block_size as usize * T::SIZE);
         }
 
-        self.snapshot = Some(snapshot);
-
-        Ok(())
+        Ok(snapshot)
     }
 
     /// Writes a unique entry to the link's file(s), returning the `u32` assigned to it.
     ///
     /// The caller _must_ guarantee that the entry has not been inserted in a previous
     /// link.
+    ///
+    /// If some `u32`s are reserved (see [`reserve_indices()`][1]), the entry is assigned
+    /// the `u32` following them, and is only written once all of them have been.
+    ///
+    /// [1]: Self::reserve_indices()
     pub async fn write_unique(&mut self, entry: T) -> Result<u32> {
-        // If `previous` has been set but `index` is still `0`, it means that we are not
-        // writing a snapshot file (i.e. `with_snapshot()` hasn't been called) – we need to
-        // read the previous link's delta footer to get some information about the state of
-        // the chain.
-        if self.index == 0
-            && let Some(previous) = self.previous
-        {
-            let mut previous = self.storage.open(previous, Delta).await?;
-            let footer = DFooter::read(&mut previous).await?;
+        if self.reserved > 0 {
+            let index = self.reserve_indices(1).await?.start;
+            self.write_reserved(index, entry).await?;
 
-            self.offset = footer.count;
-            self.count = footer.count;
-            self.index = footer.index + 1;
+            return Ok(index);
         }
 
-        if self.count == u32::MAX {
-            return Err(Error::TooManyEntries);
+        self.write_next(entry).await
+    }
+
+    /// Reserves the next `n` `u32`s, which will be assigned to the entries written with
+    /// [`write_reserved()`][1], returning them.
+    ///
+    /// This allows several tasks sharing a writer to each get the `u32`s which will be
+    /// assigned to a batch of entries before building them, and write them later. The
+    /// writer itself needs to be shared behind a lock (e.g. an async mutex), since all of
+    /// its methods take `&mut self`, but reserving only holds it briefly.
+    ///
+    /// The entries are still written to the link's files ordered by the `u32` which
+    /// represent them: entries written for reserved `u32`s are kept in memory until the
+    /// entries for all of the preceding ones have been written. Entries written with
+    /// [`write_unique()`][2] while some `u32`s are reserved are assigned the `u32`
+    /// following them. Finishing the link fails if the entries for some of the reserved
+    /// `u32`s haven't been written.
+    ///
+    /// Fails like writing an entry would if the chain cannot contain `n` more entries,
+    /// or if values are written for the link's entries.
+    ///
+    /// [1]: Self::write_reserved()
+    /// [2]: Self::write_unique()
+    pub async fn reserve_indices(&mut self, n: u32) -> Result<Range<u32>> {
+        if self.values.is_some() {
+            return Err(Error::Unsupported {
+                operation: "reserving indices for entries with values",
+            });
         }
 
-        let id = self.count;
-        self.count += 1;
+        self.load_previous().await?;
+        self.check_capacity(n)?;
 
-        // TODO(MLB): validate that exactly `T::SIZE` bytes were written
-        entry.write(&mut self.delta).await?;
-        if let Some(snapshot) = &mut self.snapshot {
-            entry.write(snapshot).await?;
-        }
+        let start = self.count + self.reserved;
+        self.reserved += n;
 
-        Ok(id)
+        Ok(start..start + n)
     }
 
-    /// Finishes writing, flushing all remaining bytes to the file(s) and retuning the
-    /// ID assigned to the newly created link.
+    /// Writes the entry assigned to `index`, which must have been reserved with
+    /// [`reserve_indices()`][1].
     ///
-    /// Fails if no entries were added to the link.
-    pub async fn finish(self) -> Result<LinkId> {
-        let Self {
-            offset,
-            count,
-            id,
-            previous,
-            index,
-            mut delta,
-            snapshot,
-            ..
-        } = self;
+    /// The caller _must_ guarantee that the entry has not been inserted in a previous
+    /// link, nor for another `u32`.
+    ///
+    /// Fails if `index` wasn't reserved, or if its entry was already written.
+    ///
+    /// [1]: Self::reserve_indices()
+    pub async fn write_reserved(&mut self, index: u32, entry: T) -> Result<()> {
+        let reserved = self.count..self.count + self.reserved;
+        if !reserved.contains(&index) || self.pending.contains_key(&index) {
+            return Err(Error::Unsupported {
+                operation: "write to an index which isn't reserved",
+            });
+        }
 
-        if offset == count {
-            return Err(Error::Empty);
+        self.pending.insert(index, entry);
+
+        // The entries can only be written once the entries for all of the preceding
+        // `u32`s have been.
+        while let Some(entry) = self.pending.remove(&self.count) {
+            self.reserved -= 1;
+            self.write_next(entry).await?;
         }
 
-        let dfooter = DFooter {
-            previous,
-            index,
-            total: count,
-            count: count - offset,
-        };
+        Ok(())
+    }
 
-        let sfooter = SFooter {
-            previous,
-            index,
-            count,
-        };
+    /// Writes the entry assigned to the next `u32` to the link's file(s).
+    async fn write_next(&mut self, entry: T) -> Result<u32> {
+        let id = self.next_id().await?;
 
-        let delta = async move {
-            dfooter.write(&mut delta).await?;
-            delta.finish().await
-        };
+        if let Some(delta) = &mut self.delta {
+            delta.write_entry(&entry).await?;
+        }
 
-        let snapshot = async move {
-            if let Some(mut snapshot) = snapshot {
-                sfooter.write(&mut snapshot).await?;
-                snapshot.finish().await
-            } else {
+        if let Some(sorted) = &mut self.sorted {
+            sorted.entries.push(entry);
+        } else if let Some(snapshot) = &mut self.snapshot {
+            snapshot.write_entry(&entry).await?;
+        }
+
+        Ok(id)
+    }
+
+    /// Writes a unique entry to the link's file(s) like [`write_unique()`][1], and writes
+    /// `value` to the link's values file, associating it to the entry.
+    ///
+    /// The value can then be retrieved from a [`Reader`] whose values are of type `V`
+    /// (see [`Reader::get_value_at()`]). If values are written for a link, they must be
+    /// written for all of its entries, and for all of the entries of the previous links.
+    ///
+    /// Fails if entries were already added to the link without a value, if `V` is
+    /// zero-sized, or if `V` isn't the same size as the values which were already
+    /// written.
+    ///
+    /// [1]: Self::write_unique()
+    pub async fn write_unique_with_value<V: Entry>(&mut self, entry: T, value: &V) -> Result<u32> {
+        if V::SIZE == 0 {
+            return Err(Error::Unsupported {
+                operation: "zero-sized values",
+            });
+        }
+
+        match &self.values {
+            Some((_, size, _)) if *size != V::SIZE => {
+                return Err(Error::EntrySize {
+                    expected: *size,
+                    got: V::SIZE,
+                });
+            }
+
+            Some(_) => {}
+            None => {
+                self.load_previous().await?;
+                if self.has_entries() {
+                    return Err(Error::Unsupported {
+                        operation: "values for only some of a link's entries",
+                    });
+                }
+
+                let values = create(&self.storage, self.id, Values, self.temporary).await?;
+                self.values = Some((values, V::SIZE, 0));
+            }
+        }
+
+        let id = self.write_unique(entry).await?;
+
+        if let Some((values, _, count)) = &mut self.values {
+            values.write_entry(value).await?;
+            *count += 1;
+        }
+
+        Ok(id)
+    }
+
+    /// Writes a unique entry, already encoded as the given bytes, to the link's file(s),
+    /// returning the `u32` assigned to it.
+    ///
+    /// This avoids decoding and re-encoding entries which are being forwarded from
+    /// another chain.
+    ///
+    /// The caller _must_ guarantee that `bytes` is a valid encoding of an entry, and
+    /// that the entry has not been inserted in a previous link.
+    ///
+    /// Fails if `bytes` doesn't contain exactly `SIZE` bytes, or if a sorted snapshot
+    /// file is written for the link.
+    pub async fn write_unique_raw(&mut self, bytes: &[u8]) -> Result<u32> {
+        if self.sorted.is_some() {
+            return Err(Error::Unsupported {
+                operation: "raw write to a sorted snapshot",
+            });
+        }
+
+        if self.reserved > 0 {
+            return Err(Error::Unsupported {
+                operation: "raw write while indices are reserved",
+            });
+        }
+
+        if bytes.len() != T::SIZE {
+            return Err(Error::EntrySize {
+                expected: T::SIZE,
+                got: bytes.len(),
+            });
+        }
+
+        let id = self.next_id().await?;
+
+        if let Some(delta) = &mut self.delta {
+            delta.write_slice(bytes).await?;
+        }
+
+        if let Some(snapshot) = &mut self.snapshot {
+            snapshot.write_slice(bytes).await?;
+        }
+
+        Ok(id)
+    }
+
+    /// Returns the `u32` which will be assigned to the next entry written to the link,
+    /// without writing anything.
+    ///
+    /// This allows computing references to entries before writing them (e.g. to encode
+    /// them in the same pass). If the previous link's footer hasn't been read yet, this
+    /// reads it (like [`check_previous()`][1]).
+    ///
+    /// Fails like writing an entry would if the chain cannot contain more entries.
+    ///
+    /// [1]: Self::check_previous()
+    pub async fn next_index(&mut self) -> Result<u32> {
+        self.load_previous().await?;
+        self.check_capacity(1)?;
+
+        Ok(self.count + self.reserved)
+    }
+
+    /// Assigns a `u32` to the next entry written to the link.
+    ///
+    /// This also ensures that the link's delta file has been created, unless it is
+    /// skipped.
+    async fn next_id(&mut self) -> Result<u32> {
+        self.load_previous().await?;
+        self.check_capacity(1)?;
+
+        if self.delta.is_none() && !self.skips_delta() {
+            let delta = create(&self.storage, self.id, Delta, self.temporary).await?;
+            self.delta = Some(delta);
+        }
+
+        let id = self.count;
+        self.count += 1;
+
+        Ok(id)
+    }
+
+    /// Fails if the chain cannot contain `additional` more entries (on top of the ones
+    /// whose `u32` is reserved).
+    fn check_capacity(&self, additional: u32) -> Result<()> {
+        let total = self.count + self.reserved;
+        let Some(attempted) = total.checked_add(additional) else {
+            return Err(Error::TooManyEntries);
+        };
+
+        if let Some(limit) = self.max_total
+            && attempted > limit
+        {
+            return Err(Error::CapacityExceeded { limit, attempted });
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the previous link exists, failing with [`Error::DoesNotExist`] if
+    /// it doesn't.
+    ///
+    /// Otherwise, the previous link's footer is only read when the first entry is
+    /// written (or when the snapshot file is created), which means that a writer
+    /// created with an invalid previous link only fails then. This allows catching
+    /// such links early, at the cost of reading the footer upfront.
+    pub async fn check_previous(&mut self) -> Result<()> {
+        self.load_previous().await
+    }
+
+    /// Makes the link extend `previous` instead of the link it was created with, using
+    /// the given information about it instead of reading its footer.
+    ///
+    /// `total` must be the number of entries present in the chain up to `previous`
+    /// (included), and `index` must be the index of `previous` in the chain. This allows
+    /// re-pointing a link to a specific earlier link (e.g. to skip a corrupted link)
+    /// when its footer cannot be read or isn't trusted.
+    ///
+    /// ## Danger
+    ///
+    /// None of this is checked – if `previous` doesn't exist, or if `total` or `index`
+    /// don't match it, the link will be inconsistent with the rest of the chain, and
+    /// readers will either fail to load it or assign the wrong `u32`s to its entries.
+    /// Entries must also still be unique across the whole chain up to `previous`.
+    ///
+    /// Note that writing a snapshot file (e.g. with [`with_snapshot()`][1]) still reads
+    /// the previous link's snapshot, which overrides `total` and `index`.
+    ///
+    /// Fails if entries have already been added to the link, or if its snapshot file
+    /// was already created.
+    ///
+    /// [1]: Self::with_snapshot()
+    pub fn with_previous_footer(
+        &mut self,
+        total: u32,
+        index: ChainIndex,
+        previous: LinkId,
+    ) -> Result<()> {
+        if self.has_entries() || self.snapshot.is_some() {
+            return Err(Error::NotEmpty);
+        }
+
+        self.previous = Some(previous);
+        self.offset = total;
+        self.count = total;
+        self.index = index.next();
+
+        Ok(())
+    }
+
+    /// Reads the previous link's footer to get information about the state of the
+    /// chain, unless it has already been read.
+    async fn load_previous(&mut self) -> Result<()> {
+        // If `previous` has been set but `index` is still `0`, it means that we are not
+        // writing a snapshot file (i.e. `with_snapshot()` hasn't been called) – we need to
+        // read the previous link's delta footer to get some information about the state of
+        // the chain.
+        let Some(previous) = self.previous else {
+            return Ok(());
+        };
+
+        if self.index != ChainIndex::default() {
+            return Ok(());
+        }
+
+        let (count, index, _) = self.storage.read_total(previous).await?;
+
+        self.offset = count;
+        self.count = count;
+        self.index = index.next();
+
+        Ok(())
+    }
+
+    /// Returns `true` if some of the link's files were created.
+    #[inline]
+    fn has_files(&self) -> bool {
+        self.delta.is_some() || self.snapshot.is_some() || self.values.is_some()
+    }
+
+    /// Returns `true` if entries were added to the link (or `u32`s were reserved for
+    /// them).
+    #[inline]
+    fn has_entries(&self) -> bool {
+        self.count != self.offset || self.reserved > 0
+    }
+
+    /// Returns `true` if the link's delta file is skipped.
+    #[inline]
+    fn skips_delta(&self) -> bool {
+        self.snapshot_only && self.snapshot.is_some() && self.delta.is_none()
+    }
+
+    /// Finishes writing, flushing all remaining bytes to the file(s) and retuning the
+    /// ID assigned to the newly created link.
+    ///
+    /// Fails if no entries were added to the link, unless this was allowed with
+    /// [`with_empty_allowed()`][1] and a snapshot file is written for it.
+    ///
+    /// [1]: Self::with_empty_allowed()
+    #[inline]
+    pub async fn finish(self) -> Result<LinkId> {
+        let summary = self.finish_with_summary().await?;
+        Ok(summary.id)
+    }
+
+    /// Finishes writing like [`finish()`][1], but returns information about the newly
+    /// created link rather than only its ID.
+    ///
+    /// This is the information stored in the link's footers, which would otherwise need
+    /// to be read back from the storage.
+    ///
+    /// [1]: Self::finish()
+    pub async fn finish_with_summary(self) -> Result<LinkSummary> {
+        let prepared = self.prepare().await?;
+
+        prepared.commit_files().await?;
+        prepared.commit_delta().await?;
+        prepared.check_fork().await?;
+
+        Ok(prepared.summary)
+    }
+
+    /// Finishes writing all of the link's files, without moving them to their final
+    /// path if they were written to temporary paths.
+    async fn prepare(mut self) -> Result<Prepared> {
+        if let Err(error) = self.check_complete() {
+            // The link can't be finished, so its files are discarded rather than left
+            // partly written. Discarding them can fail on some storages, in which case
+            // dropping the writer still warns about them.
+            let _ = self.abort().await;
+            return Err(error);
+        }
+
+        let skips_delta = self.skips_delta();
+
+        // The files are taken out of the writer so that dropping it doesn't warn about
+        // them being left unfinished.
+        let delta = self.delta.take();
+        let snapshot = self.snapshot.take();
+        let values = self.values.take();
+        let sorted = self.sorted.take();
+
+        let Self {
+            ref storage,
+            offset,
+            count,
+            id,
+            previous,
+            index,
+            base,
+            prefix,
+            temporary,
+            strategy,
+            checksum_block,
+            check_fork,
+            version,
+            ..
+        } = self;
+
+        let delta = match delta {
+            Some(delta) => Some(delta),
+            None if skips_delta => None,
+            None => Some(create(storage, id, Delta, temporary).await?),
+        };
+
+        let dfooter = DFooter {
+            previous,
+            index,
+            total: count,
+            count: count - offset,
+            version,
+        };
+
+        let sfooter = SFooter {
+            previous,
+            index,
+            count,
+            base: base.map(|(base, _)| base),
+            base_count: base.map(|(_, count)| count).unwrap_or_default(),
+            sorted: sorted.is_some(),
+            checksum_block: checksum_block.unwrap_or_default(),
+            prefix: prefix.map(|(size, _)| size).unwrap_or_default(),
+            prefix_count: prefix.map(|(_, count)| count).unwrap_or_default(),
+            version,
+        };
+
+        let has_delta = delta.is_some();
+        let delta = async move {
+            if let Some(mut delta) = delta {
+                dfooter.write(&mut delta).await?;
+                delta.finish().await
+            } else {
                 Ok(())
             }
         };
 
-        try_join(delta, snapshot).await?;
+        let has_values = values.is_some();
+        if let Some((mut values, _, _)) = values {
+            let footer = DFooter {
+                previous,
+                index,
+                total: count,
+                count: count - offset,
+                version,
+            };
 
-        Ok(id)
+            footer.write_values(&mut values).await?;
+            values.finish().await?;
+        }
+
+        let has_snapshot = snapshot.is_some();
+        let snapshot = async move {
+            if let Some(mut snapshot) = snapshot {
+                if let Some(sorted) = sorted {
+                    sorted.write(&mut snapshot).await?;
+                }
+
+                for checksum in snapshot.take_checksums().unwrap_or_default() {
+                    snapshot.write_u32(checksum).await?;
+                }
+
+                sfooter.write(&mut snapshot).await?;
+                snapshot.finish().await
+            } else {
+                Ok(())
+            }
+        };
+
+        match strategy {
+            FinishStrategy::Parallel => {
+                try_join(delta, snapshot).await?;
+            }
+
+            FinishStrategy::Sequential => {
+                snapshot.await?;
+                delta.await?;
+            }
+        }
+
+        Ok(Prepared {
+            storage: storage.clone(),
+            temporary,
+            has_delta,
+            has_snapshot,
+            has_values,
+            previous,
+            check_fork,
+            summary: LinkSummary {
+                id,
+                index,
+                count: count - offset,
+                total: count,
+                has_snapshot,
+            },
+        })
+    }
+
+    /// Checks that the link can be finished, i.e. that it isn't empty (unless allowed)
+    /// and that entries (and values) were written for all of the `u32`s assigned.
+    fn check_complete(&self) -> Result<()> {
+        if self.reserved > 0 {
+            return Err(Error::Inconsistent {
+                link: self.id,
+                reason: "entries weren't written for all of the reserved indices",
+            });
+        }
+
+        if self.offset == self.count && !(self.allow_empty && self.snapshot.is_some()) {
+            return Err(Error::Empty);
+        }
+
+        if let Some((_, _, count)) = &self.values
+            && *count != self.count - self.offset
+        {
+            return Err(Error::Inconsistent {
+                link: self.id,
+                reason: "values weren't written for all of the link's entries",
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Abandons the link, discarding what was written to its files instead of finishing
+    /// them.
+    ///
+    /// This is best-effort: depending on the storage, parts of the files (e.g. an
+    /// appended snapshot's copy of the previous link's snapshot) might remain, and
+    /// some storages can't discard what was written at all, in which case this fails.
+    pub async fn abort(mut self) -> Result<()> {
+        let files = [self.delta.take(), self.snapshot.take()];
+        let values = self.values.take().map(|(values, _, _)| values);
+
+        for file in files.into_iter().chain([values]).flatten() {
+            file.abort().await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: Entry> Drop for Writer<T> {
+    fn drop(&mut self) {
+        if self.has_files() {
+            warn_dropped(
+                format_args!("the writer for link {}", self.id),
+                ["finish", "abort"],
+                "its files partly written",
+            );
+        }
+    }
+}
+
+impl<T: Entry> SortedEntries<T> {
+    /// Writes the entries sorted, followed by the position at which each of them was
+    /// written, ordered by the `u32` which represent them.
+    async fn write(self, writer: &mut storage::Writer) -> Result<()> {
+        let Self { entries, cmp } = self;
+
+        let mut order = (0..entries.len() as u32).collect::<Vec<_>>();
+        order.sort_unstable_by(|a, b| cmp(&entries[*a as usize], &entries[*b as usize]));
+
+        let mut positions = vec![0; entries.len()];
+        for (position, index) in order.into_iter().enumerate() {
+            writer.write_entry(&entries[index as usize]).await?;
+            positions[index as usize] = position as u32;
+        }
+
+        for position in positions {
+            writer.write_u32(position).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Prepared {
+    /// Moves the link's files other than its delta file to their final path, if they
+    /// were written to temporary paths.
+    ///
+    /// The delta is moved last (see [`commit_delta()`][1]), so that its presence implies
+    /// that the other files have been moved as well.
+    ///
+    /// [1]: Self::commit_delta()
+    async fn commit_files(&self) -> Result<()> {
+        let id = self.summary.id;
+
+        if self.temporary && self.has_snapshot {
+            self.storage.commit(id, Snapshot).await?;
+        }
+
+        if self.temporary && self.has_values {
+            self.storage.commit(id, Values).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves the link's delta file to its final path, if it was written to a temporary
+    /// path.
+    async fn commit_delta(&self) -> Result<()> {
+        if self.temporary && self.has_delta {
+            self.storage.commit(self.summary.id, Delta).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Fails with [`Error::Forked`] if the link should be checked for forks and another
+    /// link extends the same previous link.
+    async fn check_fork(&self) -> Result<()> {
+        let LinkSummary { id, index, .. } = self.summary;
+
+        let Some(previous) = self.previous.filter(|_| self.check_fork) else {
+            return Ok(());
+        };
+
+        // The link is recorded before looking for other ones, so that two writers racing
+        // with each other can't both miss the other's link.
+        self.storage.add_child(previous, id).await?;
+
+        let mut children = pin!(self.storage.list_children(previous));
+        while let Some(sibling) = children.try_next().await? {
+            if sibling != id {
+                return Err(Error::Forked {
+                    index,
+                    link: id,
+                    sibling,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes all of the link's files, whether they were moved to their final path or
+    /// not.
+    async fn discard(&self) -> Result<()> {
+        let id = self.summary.id;
+        let kinds = [
+            (self.has_delta, Delta),
+            (self.has_snapshot, Snapshot),
+            (self.has_values, Values),
+        ];
+
+        for (_, kind) in kinds.into_iter().filter(|(has, _)| *has) {
+            self.storage.delete(id, kind, false).await?;
+            if self.temporary {
+                self.storage.delete(id, kind, true).await?;
+            }
+        }
+
+        if let Some(previous) = self.previous.filter(|_| self.check_fork) {
+            self.storage.remove_child(previous, id).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reports on the standard error, in debug builds, that `dropped` was dropped without
+/// calling either of the given `methods` first, which left files as `leaving`
+/// describes.
+///
+/// Nothing is reported while the thread is panicking, since dropping without calling
+/// them is then expected.
+pub(super) fn warn_dropped(dropped: fmt::Arguments, methods: [&str; 2], leaving: &str) {
+    if cfg!(debug_assertions) && !std::thread::panicking() {
+        let [first, second] = methods;
+        let warning = format!(
+            "chaindict: {dropped} was dropped without calling `{first}()` or `{second}()`, \
+             leaving {leaving}"
+        );
+
+        #[cfg(test)]
+        tests::WARNINGS.with_borrow_mut(|warnings| warnings.push(warning.clone()));
+
+        eprintln!("{warning}");
+    }
+}
+
+/// Creates the file of the given kind for the link with the given ID, at a temporary
+/// path if `temporary` is `true`.
+#[inline]
+async fn create(
+    storage: &Storage,
+    id: LinkId,
+    kind: Kind,
+    temporary: bool,
+) -> Result<storage::Writer> {
+    if temporary {
+        storage.create_temporary(id, kind).await
+    } else {
+        storage.create(id, kind).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, hash::RandomState};
+
+    use crate::{
+        Partial,
+        tests::{U64, block_on, chain, extend, legacy_chain, local, memory, probed, write_file},
+        verify_snapshot_consistency,
+    };
+
+    use super::*;
+
+    thread_local! {
+        /// The warnings reported on the current thread by [`warn_dropped()`].
+        pub(super) static WARNINGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    #[test]
+    fn two_phase_finish() -> Result<()> {
+        block_on(async {
+            let (storage, _dir) = local();
+            let id = LinkId::random();
+
+            let mut writer = Writer::<U64>::create_two_phase(None, storage.clone()).await?;
+            writer.with_id(id)?;
+            writer.with_snapshot().await?;
+            writer.write_unique(U64(0)).await?;
+
+            // The files are only moved to their final path once all of them are written.
+            assert!(storage.open_maybe(id, Delta).await?.is_none());
+            assert!(storage.open_maybe(id, Snapshot).await?.is_none());
+            assert_eq!(writer.finish().await?, id);
+            assert_eq!(storage.read_footer(id, Delta).await?.count, 1);
+            assert_eq!(storage.read_footer(id, Snapshot).await?.count, 1);
+
+            // Storages which can't rename files finish the snapshot before the delta.
+            let storage = memory();
+            let mut writer = Writer::<U64>::create_two_phase(None, storage.clone()).await?;
+            assert!(!writer.temporary);
+            assert!(matches!(writer.strategy, FinishStrategy::Sequential));
+
+            writer.with_snapshot().await?;
+            writer.write_unique(U64(0)).await?;
+            let id = writer.finish().await?;
+            assert_eq!(storage.read_footer(id, Delta).await?.count, 1);
+            assert_eq!(storage.read_footer(id, Snapshot).await?.count, 1);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn delta_is_created_by_the_first_write() -> Result<()> {
+        block_on(async {
+            let (storage, probe) = probed();
+
+            let writer = Writer::<U64>::create(None, storage.clone()).await?;
+            writer.abort().await?;
+            assert_eq!(probe.created(), 0);
+
+            let mut writer = Writer::<U64>::create(None, storage.clone()).await?;
+            assert_eq!(probe.created(), 0);
+
+            writer.write_unique(U64(0)).await?;
+            writer.write_unique(U64(1)).await?;
+            assert_eq!(probe.created(), 1);
+
+            let first = writer.finish().await?;
+
+            // Finishing a link without entries fails before creating its delta file.
+            let writer = Writer::<U64>::create(Some(first), storage.clone()).await?;
+            assert!(matches!(writer.finish().await, Err(Error::Empty)));
+            assert_eq!(probe.created(), 1);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn offsets_follow_the_whole_chain() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let mut previous = None;
+            let mut total = 0;
+
+            for count in [2, 3, 1, 2] {
+                let mut writer = Writer::<U64>::create(previous, storage.clone()).await?;
+                for _ in 0..count {
+                    assert_eq!(writer.write_unique(U64(total.into())).await?, total);
+                    total += 1;
+                }
+
+                let id = writer.finish().await?;
+                let footer = storage.read_footer(id, Delta).await?;
+                assert_eq!((footer.total, footer.count), (total, count));
+
+                previous = Some(id);
+            }
+
+            let reader = Reader::<U64>::open(previous.unwrap(), storage).await?;
+            for index in 0..total {
+                assert_eq!(reader.get_at(index), Some(&U64(index.into())));
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn legacy_totals_are_recomputed() -> Result<()> {
+        block_on(async {
+            let storage = memory().with_legacy_format();
+            let links = legacy_chain(&storage, &[2, 3, 1, 2]).await?;
+            let latest = links[3];
+
+            assert_eq!(storage.read_footer(latest, Delta).await?.total, 3);
+            assert_eq!(storage.read_total(latest).await?.0, 8);
+
+            let Partial { mut reader, .. } =
+                Reader::<U64>::open_partial(latest, storage.clone(), 1).await?;
+            assert_eq!(reader.first_loaded(), 6);
+            assert_eq!(reader.get_index_of_fetch(&U64(5)).await?, Some(5));
+
+            let since = reader.iter_since(links[2]).await?;
+            assert_eq!(since.map(|(index, _)| index).collect::<Vec<_>>(), [6, 7]);
+
+            let mut capped = Reader::<U64>::builder()
+                .max_resident_entries(1)
+                .open_capped(latest, storage.clone())
+                .await?;
+            assert_eq!(capped.get_index_of(&U64(7)).await?, Some(7));
+
+            let mut writer = Writer::<U64>::create(Some(latest), storage).await?;
+            assert_eq!(writer.write_unique(U64(8)).await?, 8);
+            writer.abort().await
+        })
+    }
+
+    #[test]
+    fn snapshot_from_reader_contains_the_chain_once() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+
+            let mut writer = Writer::<U64>::create(None, storage.clone()).await?;
+            writer.with_snapshot().await?;
+            writer.write_unique(U64(0)).await?;
+            writer.write_unique(U64(1)).await?;
+            let first = writer.finish().await?;
+
+            let previous = Reader::<U64>::open(first, storage.clone()).await?;
+            let mut writer = Writer::<U64>::create(Some(first), storage.clone()).await?;
+            writer.with_snapshot_from(&previous).await?;
+            assert_eq!(writer.write_unique(U64(2)).await?, 2);
+            let second = writer.finish().await?;
+
+            let footer = storage.read_footer(second, Snapshot).await?;
+            assert_eq!(footer.total, 3);
+
+            let stat = storage.stat_link(second, Snapshot).await?;
+            assert_eq!(stat.content_length, (3 * 8 + SFooter::SIZE) as u64);
+
+            storage.delete(second, Delta, false).await?;
+            let reader = Reader::<U64>::open(second, storage).await?;
+            let entries = reader.iter().map(|(_, entry)| entry.0).collect::<Vec<_>>();
+            assert_eq!(entries, [0, 1, 2]);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn fork_check() -> Result<()> {
+        block_on(async {
+            let (storage, probe) = probed();
+            let links = chain(&storage, [0..2, 2..4]).await?;
+
+            // Neither unrelated links nor files which aren't links' are looked at.
+            chain(&storage, (0..50).map(|entry| entry..entry + 1)).await?;
+            write_file(&storage, LinkId::random(), Delta, b"in progress").await?;
+
+            let mut first = Writer::<U64>::create(Some(links[1]), storage.clone()).await?;
+            let mut second = Writer::<U64>::create(Some(links[1]), storage.clone()).await?;
+            first.with_fork_check();
+            second.with_fork_check();
+
+            first.write_unique(U64(4)).await?;
+            second.write_unique(U64(5)).await?;
+
+            let before = probe.listed();
+            let first = first.finish().await?;
+            assert_eq!(probe.listed() - before, 1);
+
+            let error = second.finish().await.unwrap_err();
+            let Error::Forked { index, sibling, .. } = error else {
+                panic!("unexpected error: {error}");
+            };
+
+            assert_eq!((index, sibling), (ChainIndex::new(2), first));
+
+            // Links extending another one aren't siblings.
+            let mut next = Writer::<U64>::create(Some(first), storage).await?;
+            next.with_fork_check();
+            next.write_unique(U64(5)).await?;
+            next.finish().await?;
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn values_files_have_their_own_magic() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+
+            let mut writer = Writer::<U64>::create(None, storage.clone()).await?;
+            writer.write_unique_with_value(U64(1), &U64(10)).await?;
+            writer.write_unique_with_value(U64(2), &U64(20)).await?;
+            assert_eq!(writer.bytes_written(), 4 * 8);
+
+            let id = writer.finish().await?;
+
+            let footer = storage.read_footer(id, Values).await?;
+            assert_eq!((footer.total, footer.count), (2, 2));
+
+            let mut values = storage.open(id, Values).await?;
+            let error = DFooter::read(&mut values).await.unwrap_err();
+            assert!(matches!(error, Error::BadMagic { .. }));
+
+            let mut delta = storage.open(id, Delta).await?;
+            let error = DFooter::read_values(&mut delta).await.unwrap_err();
+            assert!(matches!(error, Error::BadMagic { .. }));
+
+            let reader = Reader::<U64, RandomState, U64>::open(id, storage).await?;
+            assert_eq!(reader.get_value_at(1), Some(&U64(20)));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn sorted_snapshot() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let first = chain(&storage, Some(3..5)).await?[0];
+
+            let mut writer = Writer::<U64>::create(Some(first), storage.clone()).await?;
+            writer.with_checksums(2)?;
+            assert!(matches!(
+                writer.with_sorted_snapshot().await,
+                Err(Error::Unsupported { .. })
+            ));
+
+            let mut writer = Writer::<U64>::create(None, storage.clone()).await?;
+            writer.with_sorted_snapshot().await?;
+            writer.write_unique(U64(4)).await?;
+            writer.write_unique(U64(3)).await?;
+            let previous = writer.finish().await?;
+
+            let mut writer = Writer::<U64>::create(Some(previous), storage.clone()).await?;
+            writer.with_sorted_snapshot().await?;
+            writer.with_snapshot_only();
+            writer.write_unique(U64(1)).await?;
+            writer.write_unique(U64(2)).await?;
+            let id = writer.finish().await?;
+
+            // The entries are stored sorted, followed by their position.
+            let mut file = storage.open(id, Snapshot).await?;
+            let footer = SFooter::read(&mut file).await?;
+            assert!(footer.sorted);
+
+            let mut stored = Vec::new();
+            for _ in 0..4 {
+                stored.push(file.read_u64().await?);
+            }
+
+            for _ in 0..4 {
+                stored.push(file.read_u32().await?.into());
+            }
+
+            assert_eq!(stored, [1, 2, 3, 4, 3, 2, 0, 1]);
+
+            let reader = Reader::<U64>::open(id, storage).await?;
+            let entries = reader.iter().map(|(_, entry)| entry.0).collect::<Vec<_>>();
+            assert_eq!(entries, [4, 3, 1, 2]);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn check_previous() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..3]).await?;
+
+            let mut writer = Writer::<U64>::create(Some(links[1]), storage.clone()).await?;
+            writer.check_previous().await?;
+
+            // Creating the writer doesn't read the previous link's footer.
+            let missing = LinkId::random();
+            let mut writer = Writer::<U64>::create(Some(missing), storage).await?;
+            assert!(matches!(
+                writer.check_previous().await,
+                Err(Error::DoesNotExist { link, .. }) if link == missing
+            ));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn next_index() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..5]).await?;
+
+            let mut writer = Writer::<U64>::create(Some(links[1]), storage).await?;
+            assert_eq!(writer.next_index().await?, 5);
+            assert_eq!(writer.next_index().await?, 5);
+            assert_eq!(writer.write_unique(U64(5)).await?, 5);
+
+            // Reserved `u32`s are skipped.
+            assert_eq!(writer.reserve_indices(2).await?, 6..8);
+            assert_eq!(writer.next_index().await?, 8);
+
+            writer.with_max_total(8);
+            assert!(matches!(
+                writer.next_index().await,
+                Err(Error::CapacityExceeded {
+                    limit: 8,
+                    attempted: 9
+                })
+            ));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn racing_writers_fork() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..4]).await?;
+
+            let mut writers = Vec::new();
+            for entry in 4..6 {
+                let mut writer = Writer::<U64>::create(Some(links[1]), storage.clone()).await?;
+                writer.with_fork_check();
+                writer.write_unique(U64(entry)).await?;
+                writers.push(writer.finish());
+            }
+
+            // Whichever writer finishes last, both can't succeed.
+            let results = futures::future::join_all(writers).await;
+            assert!(results.iter().any(Result::is_err));
+            for result in results {
+                match result {
+                    Ok(_) | Err(Error::Forked { .. }) => {}
+                    Err(error) => panic!("unexpected error: {error}"),
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn appended_snapshots() -> Result<()> {
+        block_on(async {
+            let (local, _dir) = local();
+            for storage in [memory(), local] {
+                let mut links = Vec::new();
+                for start in (0..9).step_by(3) {
+                    let previous = links.last().copied();
+                    let mut writer = Writer::<U64>::create(previous, storage.clone()).await?;
+                    writer.with_appended_snapshot().await?;
+                    for entry in start..start + 3 {
+                        writer.write_unique(U64(entry)).await?;
+                    }
+
+                    links.push(writer.finish().await?);
+                }
+
+                // Each snapshot starts with the previous one's whole file.
+                let first = storage.stat_link(links[0], Snapshot).await?;
+                let mut snapshot = storage.open(links[1], Snapshot).await?;
+                let footer = SFooter::read(&mut snapshot).await?;
+                assert_eq!(
+                    (footer.prefix, footer.prefix_count),
+                    (first.content_length, 3)
+                );
+                assert_eq!(footer.count, 6);
+
+                let reader = Reader::<U64>::open(links[2], storage.clone()).await?;
+                assert!(reader.iter().map(|(_, entry)| entry.0).eq(0..9));
+
+                // Full snapshots can still be written after appended ones.
+                let mut writer = Writer::<U64>::create(Some(links[2]), storage.clone()).await?;
+                writer.with_snapshot().await?;
+                writer.write_unique(U64(9)).await?;
+                let latest = writer.finish().await?;
+
+                verify_snapshot_consistency::<U64>(latest, &storage).await?;
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    #[cfg(feature = "format-version")]
+    fn format_version() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let mut links = Vec::new();
+            for version in [storage::LEGACY_VERSION, storage::VERSION + 1] {
+                let mut writer = Writer::<U64>::create(None, storage.clone()).await?;
+                writer.with_format_version(version);
+                writer.write_unique(U64(0)).await?;
+                links.push(writer.finish().await?);
+            }
+
+            // Legacy files are only read if allowed, and newer ones never are.
+            let error = Reader::<U64>::open(links[0], storage.clone()).await.err();
+            assert!(matches!(error, Some(Error::Version { got: 0, .. })));
+            let legacy = storage.clone().with_legacy_format();
+            assert_eq!(Reader::<U64>::open(links[0], legacy).await?.len(), 1);
+
+            let error = Reader::<U64>::open(links[1], storage).await.err();
+            assert!(matches!(
+                error,
+                Some(Error::Version { expected: storage::VERSION, got }) if got == storage::VERSION + 1
+            ));
+
+            // Older versions can't store the features they didn't support.
+            let mut writer = Writer::<U64>::create(None, memory()).await?;
+            writer.with_format_version(storage::LEGACY_VERSION);
+            writer.with_sorted_snapshot().await?;
+            writer.write_unique(U64(0)).await?;
+            assert!(matches!(
+                writer.finish().await,
+                Err(Error::Unsupported { .. })
+            ));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn finish_with_summary() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..5]).await?;
+
+            let mut writer = Writer::<U64>::create(Some(links[1]), storage.clone()).await?;
+            let reader = Reader::<U64>::open(links[1], storage.clone()).await?;
+            writer.with_snapshot_from(&reader).await?;
+            writer.write_unique(U64(5)).await?;
+            let summary = writer.finish_with_summary().await?;
+
+            // The summary matches what is stored in the link's footers.
+            let footer = storage.read_footer(summary.id, Delta).await?;
+            assert_eq!(summary.index, ChainIndex::new(2));
+            assert_eq!(
+                (summary.index, summary.total, summary.count),
+                (footer.index, footer.total, footer.count)
+            );
+            assert_eq!((summary.total, summary.count), (6, 1));
+            assert!(summary.has_snapshot);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn reserved_indices() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, Some(0..2)).await?;
+
+            let mut writer = Writer::<U64>::create(Some(links[0]), storage.clone()).await?;
+            assert_eq!(writer.reserve_indices(2).await?, 2..4);
+            assert_eq!(writer.reserve_indices(1).await?, 4..5);
+            assert_eq!(writer.write_unique(U64(50)).await?, 5);
+
+            // Entries are written ordered by their `u32`, whenever they are provided.
+            writer.write_reserved(4, U64(40)).await?;
+            writer.write_reserved(3, U64(30)).await?;
+            assert!(matches!(
+                writer.write_reserved(3, U64(31)).await,
+                Err(Error::Unsupported { .. })
+            ));
+
+            writer.write_reserved(2, U64(20)).await?;
+            let latest = writer.finish().await?;
+
+            let reader = Reader::<U64>::open(latest, storage.clone()).await?;
+            let entries = reader.iter().map(|(_, entry)| entry.0).collect::<Vec<_>>();
+            assert_eq!(entries, [0, 1, 20, 30, 40, 50]);
+
+            // Links can't be finished until all of the reserved entries are written.
+            let mut writer = Writer::<U64>::create(Some(latest), storage).await?;
+            writer.reserve_indices(2).await?;
+            writer.write_reserved(7, U64(70)).await?;
+            assert!(matches!(
+                writer.finish().await,
+                Err(Error::Inconsistent { .. })
+            ));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn deterministic_ids() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let namespace = uuid::Uuid::NAMESPACE_OID;
+            let id = LinkId::from_name(namespace, b"first");
+            assert_eq!(id, LinkId::from_name(namespace, b"first"));
+            assert_ne!(id, LinkId::from_name(namespace, b"second"));
+
+            let mut writer = Writer::<U64>::create(None, storage.clone()).await?;
+            writer.with_id(id)?;
+            writer.write_unique(U64(0)).await?;
+            assert_eq!(writer.finish().await?, id);
+            assert_eq!(Reader::<U64>::open(id, storage.clone()).await?.len(), 1);
+
+            // The ID can't change once the link's files have been created.
+            let mut writer = Writer::<U64>::create(Some(id), storage).await?;
+            writer.write_unique(U64(1)).await?;
+            assert!(matches!(
+                writer.with_id(LinkId::from_name(namespace, b"second")),
+                Err(Error::Unsupported { .. })
+            ));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn empty_checkpoint() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let links = chain(&storage, [0..2, 2..4]).await?;
+            let reader = Reader::<U64>::open(links[1], storage.clone()).await?;
+
+            let mut writer = Writer::<U64>::create(Some(links[1]), storage.clone()).await?;
+            writer.with_snapshot_from(&reader).await?;
+            assert!(matches!(writer.finish().await, Err(Error::Empty)));
+
+            // Empty links still need a snapshot.
+            let mut writer = Writer::<U64>::create(Some(links[1]), storage.clone()).await?;
+            writer.with_empty_allowed();
+            assert!(matches!(writer.finish().await, Err(Error::Empty)));
+
+            let mut writer = Writer::<U64>::create(Some(links[1]), storage.clone()).await?;
+            writer.with_empty_allowed();
+            writer.with_snapshot_from(&reader).await?;
+            let summary = writer.finish_with_summary().await?;
+            assert_eq!((summary.count, summary.total), (0, 4));
+
+            let checkpoint = Reader::<U64>::open(summary.id, storage.clone()).await?;
+            assert!(checkpoint.loaded_from_snapshot());
+            assert_eq!(checkpoint.len(), 4);
+
+            // The checkpoint can be extended like any other link.
+            let latest = extend(&storage, Some(summary.id), Some(4..5)).await?[0];
+            let reader = Reader::<U64>::open_delta_only(links[0], latest, storage).await?;
+            assert!(reader.iter().map(|(_, entry)| entry.0).eq(0..5));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn warns_when_dropped_with_files() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let take = || WARNINGS.with_borrow_mut(std::mem::take);
+
+            // Nothing is reported if the files were finished, aborted or never created.
+            let writer = Writer::<U64>::create(None, storage.clone()).await?;
+            drop(writer);
+
+            let mut writer = Writer::<U64>::create(None, storage.clone()).await?;
+            writer.write_unique(U64(0)).await?;
+            let first = writer.finish().await?;
+
+            let mut writer = Writer::<U64>::create(Some(first), storage.clone()).await?;
+            writer.write_unique(U64(1)).await?;
+            writer.abort().await?;
+            assert_eq!(take(), Vec::<String>::new());
+
+            let mut writer = Writer::<U64>::create(Some(first), storage).await?;
+            writer.write_unique(U64(1)).await?;
+            let id = writer.id;
+            drop(writer);
+
+            let warnings = take();
+            assert_eq!(warnings.len(), usize::from(cfg!(debug_assertions)));
+            assert!(
+                warnings
+                    .iter()
+                    .all(|warning| warning.contains(&id.to_string()))
+            );
+
+            Ok(())
+        })
     }
 }
@@ -0,0 +1,278 @@
+use super::{LinkSummary, Prepared, Writer, warn_dropped};
+use crate::{Entry, Error, Result};
+
+/// A batch of links, possibly extending different chains (e.g. related dictionaries),
+/// which are committed together: either all of them are added to their chain, or none
+/// of them is.
+///
+/// The links are written by writers created with [`Writer::create_two_phase()`] on
+/// storages supporting renaming files, so that their files are first written to
+/// temporary paths by [`add()`][1]. [`commit()`][2] then moves the files of all of the
+/// links to their final path, their deltas last, so that the presence of any of
+/// them implies that all of the other links' files were moved as well.
+///
+/// This is best-effort: storages can't move several files atomically, so a crash while
+/// committing can still leave only some of the links committed. If committing fails,
+/// the files of all of the links are deleted (including the ones which were already
+/// moved), but deleting them can fail as well.
+///
+/// Either [`commit()`][2] or [`rollback()`][3] must be called: dropping the batch
+/// instead leaves the links' files at their temporary paths, which is reported on the
+/// standard error in debug builds.
+///
+/// [1]: Self::add()
+/// [2]: Self::commit()
+/// [3]: Self::rollback()
+#[derive(Default)]
+#[must_use = "the links are left uncommitted unless `commit()` or `rollback()` is called"]
+pub struct Batch {
+    /// The links whose files have been written to temporary paths, in the order they
+    /// were added.
+    prepared: Vec<Prepared>,
+}
+
+impl Batch {
+    /// Creates a new empty batch.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of links which have been added to the batch.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.prepared.len()
+    }
+
+    /// Returns `true` if no link has been added to the batch yet.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.prepared.is_empty()
+    }
+
+    /// Finishes writing the files of the link created by `writer` like
+    /// [`Writer::finish_with_summary()`], but leaves them at their temporary paths
+    /// until the batch is committed.
+    ///
+    /// Fails with [`Error::Unsupported`] if the link's files aren't written to temporary
+    /// paths (see [`Writer::create_two_phase()`]), or like
+    /// [`Writer::finish_with_summary()`] otherwise. The links which were already added
+    /// are kept, and [`rollback()`][1] should be called to discard them if the batch
+    /// cannot be completed.
+    ///
+    /// [1]: Self::rollback()
+    pub async fn add<T: Entry>(&mut self, writer: Writer<T>) -> Result<LinkSummary> {
+        if !writer.temporary {
+            // The link won't be committed anyway, whether its files could be discarded
+            // or not.
+            let _ = writer.abort().await;
+
+            return Err(Error::Unsupported {
+                operation: "batching links whose files aren't written to temporary paths",
+            });
+        }
+
+        let prepared = writer.prepare().await?;
+        let summary = prepared.summary;
+        self.prepared.push(prepared);
+
+        Ok(summary)
+    }
+
+    /// Commits all of the links which were added to the batch, returning information
+    /// about each of them, in the order they were added.
+    ///
+    /// If moving any of the files fails, the files of all of the links are deleted
+    /// (best-effort) and the error is returned. Links which should be checked for forks
+    /// (see [`Writer::with_fork_check()`]) are only checked once all of the links have
+    /// been committed, in which case they exist even if this fails: if some of them were
+    /// forked, this fails with [`Error::BatchForked`], which reports all of the forked
+    /// links along with the information about all of the committed ones.
+    pub async fn commit(mut self) -> Result<Vec<LinkSummary>> {
+        let prepared = std::mem::take(&mut self.prepared);
+
+        let mut committed = Ok(());
+        for link in &prepared {
+            committed = link.commit_files().await;
+            if committed.is_err() {
+                break;
+            }
+        }
+
+        if committed.is_ok() {
+            for link in &prepared {
+                committed = link.commit_delta().await;
+                if committed.is_err() {
+                    break;
+                }
+            }
+        }
+
+        if let Err(error) = committed {
+            // The error which prevented committing is more relevant than any error
+            // which prevented cleaning up after it.
+            let _ = discard(&prepared).await;
+            return Err(error);
+        }
+
+        let mut forks = Vec::new();
+        for link in &prepared {
+            match link.check_fork().await {
+                Ok(()) => {}
+                Err(Error::Forked { link, sibling, .. }) => forks.push((link, sibling)),
+                Err(error) => return Err(error),
+            }
+        }
+
+        let committed = prepared.into_iter().map(|link| link.summary).collect();
+        if !forks.is_empty() {
+            return Err(Error::BatchForked { committed, forks });
+        }
+
+        Ok(committed)
+    }
+
+    /// Discards all of the links which were added to the batch, deleting their files.
+    pub async fn rollback(mut self) -> Result<()> {
+        let prepared = std::mem::take(&mut self.prepared);
+        discard(&prepared).await
+    }
+}
+
+impl Drop for Batch {
+    fn drop(&mut self) {
+        if !self.prepared.is_empty() {
+            warn_dropped(
+                format_args!("a batch of {} links", self.prepared.len()),
+                ["commit", "rollback"],
+                "their files at temporary paths",
+            );
+        }
+    }
+}
+
+/// Deletes the files of all of the given links, trying to delete all of them even if
+/// deleting some of them fails.
+async fn discard(prepared: &[Prepared]) -> Result<()> {
+    let mut result = Ok(());
+    for link in prepared {
+        if let Err(error) = link.discard().await {
+            result = result.and(Err(error));
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        LinkId, Reader, Storage,
+        tests::{U64, block_on, chain, local, memory},
+    };
+
+    use super::*;
+
+    /// Returns a writer for a link extending `previous` with a single entry, whose files
+    /// are written to temporary paths and which is checked for forks.
+    async fn writer(storage: &Storage, previous: LinkId, entry: u64) -> Result<Writer<U64>> {
+        let mut writer = Writer::create_two_phase(Some(previous), storage.clone()).await?;
+        writer.with_fork_check();
+        writer.write_unique(U64(entry)).await?;
+
+        Ok(writer)
+    }
+
+    #[test]
+    fn commit_reports_all_forks() -> Result<()> {
+        block_on(async {
+            let (storage, _dir) = local();
+            let first = chain(&storage, Some(0..2)).await?[0];
+            let second = chain(&storage, Some(0..2)).await?[0];
+            let third = chain(&storage, Some(0..2)).await?[0];
+
+            let mut siblings = Vec::new();
+            for previous in [first, second] {
+                siblings.push(writer(&storage, previous, 2).await?.finish().await?);
+            }
+
+            let mut batch = Batch::new();
+            let mut ids = Vec::new();
+            for previous in [first, second, third] {
+                ids.push(batch.add(writer(&storage, previous, 3).await?).await?.id);
+            }
+
+            let error = batch.commit().await.unwrap_err();
+            let Error::BatchForked { committed, forks } = error else {
+                panic!("unexpected error: {error}");
+            };
+
+            let committed = committed.iter().map(|summary| summary.id);
+            assert_eq!(committed.collect::<Vec<_>>(), ids);
+            assert_eq!(forks, [(ids[0], siblings[0]), (ids[1], siblings[1])]);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn commit_and_rollback() -> Result<()> {
+        block_on(async {
+            let (storage, _dir) = local();
+            let first = chain(&storage, Some(0..2)).await?[0];
+            let second = chain(&storage, Some(0..2)).await?[0];
+
+            // The links only exist once the batch is committed.
+            let mut batch = Batch::new();
+            let mut ids = Vec::new();
+            for previous in [first, second] {
+                ids.push(batch.add(writer(&storage, previous, 2).await?).await?.id);
+            }
+
+            assert_eq!(batch.len(), 2);
+            assert!(Reader::<U64>::open(ids[0], storage.clone()).await.is_err());
+
+            let committed = batch.commit().await?;
+            assert_eq!(
+                committed
+                    .iter()
+                    .map(|summary| summary.id)
+                    .collect::<Vec<_>>(),
+                ids
+            );
+            for id in ids {
+                assert_eq!(Reader::<U64>::open(id, storage.clone()).await?.len(), 3);
+            }
+
+            // Rolled back links never exist, and don't prevent extending their previous
+            // link again.
+            let mut batch = Batch::new();
+            let id = batch
+                .add(writer(&storage, committed[0].id, 3).await?)
+                .await?
+                .id;
+            batch.rollback().await?;
+            assert!(Reader::<U64>::open(id, storage.clone()).await.is_err());
+
+            writer(&storage, committed[0].id, 3).await?.finish().await?;
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn requires_two_phase_writers() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let mut writer = Writer::<U64>::create(None, storage).await?;
+            writer.write_unique(U64(0)).await?;
+
+            let mut batch = Batch::new();
+            let result = batch.add(writer).await;
+            assert!(matches!(result, Err(Error::Unsupported { .. })));
+            assert!(batch.is_empty());
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,259 @@
+use std::marker::PhantomData;
+
+use super::{FinishStrategy, LazyWriter, Writer};
+use crate::{Entry, LinkId, Result, Storage};
+
+/// A builder combining the options used to create a [`Writer`] (or a [`LazyWriter`]),
+/// as returned by [`Writer::builder()`].
+///
+/// Each option corresponds to one of the `with_*` methods of [`Writer`], which are
+/// called once the writer has been created.
+pub struct WriterBuilder<T: Entry> {
+    /// Whether the link is committed in two phases.
+    two_phase: bool,
+
+    /// The ID assigned to the link, if it isn't random.
+    id: Option<LinkId>,
+
+    /// How the link's files are finished, if not by default.
+    strategy: Option<FinishStrategy>,
+
+    /// The maximum number of entries the chain is allowed to contain, if any.
+    max_total: Option<u32>,
+
+    /// The kind of snapshot file written for the link, if any.
+    snapshot: Option<Snapshot>,
+
+    /// Whether the link's delta file is skipped if a snapshot file is written for it.
+    snapshot_only: bool,
+
+    /// The number of entries covered by each checksum stored in the snapshot file, if
+    /// checksums are stored.
+    checksum_block: Option<u32>,
+
+    /// Whether to check that no other link extending the same previous link was
+    /// created once the link is finished.
+    check_fork: bool,
+
+    /// Whether the link can be finished without any entry being added to it.
+    allow_empty: bool,
+
+    /// The version of the storage format the link's footers are written with, if not
+    /// the current one.
+    #[cfg(feature = "format-version")]
+    version: Option<u16>,
+
+    _t: PhantomData<fn() -> T>,
+}
+
+/// The kinds of snapshot files which can be written for a link by a [`Writer`] created
+/// with a [`WriterBuilder`].
+#[derive(Clone, Copy)]
+enum Snapshot {
+    /// See [`Writer::with_snapshot()`].
+    Full,
+
+    /// See [`Writer::with_incremental_snapshot()`].
+    Incremental,
+
+    /// See [`Writer::with_appended_snapshot()`].
+    Appended,
+}
+
+impl<T: Entry> Writer<T> {
+    /// Returns a builder allowing to combine the options used to create a writer.
+    ///
+    /// Creating a writer with the default options is the same as calling
+    /// [`create()`][1].
+    ///
+    /// [1]: Self::create()
+    #[inline]
+    pub fn builder() -> WriterBuilder<T> {
+        WriterBuilder {
+            two_phase: false,
+            id: None,
+            strategy: None,
+            max_total: None,
+            snapshot: None,
+            snapshot_only: false,
+            checksum_block: None,
+            check_fork: false,
+            allow_empty: false,
+
+            #[cfg(feature = "format-version")]
+            version: None,
+
+            _t: PhantomData,
+        }
+    }
+}
+
+impl<T: Entry> WriterBuilder<T> {
+    /// Commits the link in two phases (see [`Writer::create_two_phase()`]).
+    #[inline]
+    pub fn two_phase(mut self) -> Self {
+        self.two_phase = true;
+        self
+    }
+
+    /// Assigns the given ID to the link (see [`Writer::with_id()`]).
+    #[inline]
+    pub fn id(mut self, id: LinkId) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    /// Sets how the link's files should be finished (see
+    /// [`Writer::with_finish_strategy()`]).
+    #[inline]
+    pub fn finish_strategy(mut self, strategy: FinishStrategy) -> Self {
+        self.strategy = Some(strategy);
+        self
+    }
+
+    /// Sets the maximum number of entries the chain is allowed to contain (see
+    /// [`Writer::with_max_total()`]).
+    #[inline]
+    pub fn max_total(mut self, max_total: u32) -> Self {
+        self.max_total = Some(max_total);
+        self
+    }
+
+    /// Writes a snapshot file for the link (see [`Writer::with_snapshot()`]).
+    #[inline]
+    pub fn snapshot(mut self) -> Self {
+        self.snapshot = Some(Snapshot::Full);
+        self
+    }
+
+    /// Writes an incremental snapshot file for the link (see
+    /// [`Writer::with_incremental_snapshot()`]).
+    #[inline]
+    pub fn incremental_snapshot(mut self) -> Self {
+        self.snapshot = Some(Snapshot::Incremental);
+        self
+    }
+
+    /// Writes an appended snapshot file for the link (see
+    /// [`Writer::with_appended_snapshot()`]).
+    #[inline]
+    pub fn appended_snapshot(mut self) -> Self {
+        self.snapshot = Some(Snapshot::Appended);
+        self
+    }
+
+    /// Skips writing the link's delta file if a snapshot file is written for it (see
+    /// [`Writer::with_snapshot_only()`]).
+    #[inline]
+    pub fn snapshot_only(mut self) -> Self {
+        self.snapshot_only = true;
+        self
+    }
+
+    /// Stores the checksum of each block of `block_size` entries in the link's
+    /// snapshot file (see [`Writer::with_checksums()`]).
+    #[inline]
+    pub fn checksums(mut self, block_size: u32) -> Self {
+        self.checksum_block = Some(block_size);
+        self
+    }
+
+    /// Checks that no other link extends the same previous link once the link is
+    /// finished (see [`Writer::with_fork_check()`]).
+    #[inline]
+    pub fn fork_check(mut self) -> Self {
+        self.check_fork = true;
+        self
+    }
+
+    /// Allows finishing the link without adding any entry to it (see
+    /// [`Writer::with_empty_allowed()`]).
+    #[inline]
+    pub fn empty_allowed(mut self) -> Self {
+        self.allow_empty = true;
+        self
+    }
+
+    /// Writes the link's footers with the given version of the storage format (see
+    /// [`Writer::with_format_version()`]).
+    #[cfg(feature = "format-version")]
+    #[inline]
+    pub fn format_version(mut self, version: u16) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Creates a new writer for the given storage, creating a link which is extending
+    /// `previous` according to the options of the builder.
+    ///
+    /// Fails if any of the options cannot be applied (see the corresponding `with_*`
+    /// methods of [`Writer`]).
+    pub async fn create(self, previous: Option<LinkId>, storage: Storage) -> Result<Writer<T>> {
+        let mut writer = if self.two_phase {
+            Writer::create_two_phase(previous, storage).await?
+        } else {
+            Writer::create(previous, storage).await?
+        };
+
+        // The ID and the checksums must be set before the snapshot file is created.
+        if let Some(id) = self.id {
+            writer.with_id(id)?;
+        }
+
+        if let Some(block_size) = self.checksum_block {
+            writer.with_checksums(block_size)?;
+        }
+
+        if let Some(strategy) = self.strategy {
+            writer.with_finish_strategy(strategy);
+        }
+
+        if let Some(max_total) = self.max_total {
+            writer.with_max_total(max_total);
+        }
+
+        if self.snapshot_only {
+            writer.with_snapshot_only();
+        }
+
+        if self.check_fork {
+            writer.with_fork_check();
+        }
+
+        if self.allow_empty {
+            writer.with_empty_allowed();
+        }
+
+        #[cfg(feature = "format-version")]
+        if let Some(version) = self.version {
+            writer.with_format_version(version);
+        }
+
+        match self.snapshot {
+            Some(Snapshot::Full) => writer.with_snapshot().await?,
+            Some(Snapshot::Incremental) => writer.with_incremental_snapshot().await?,
+            Some(Snapshot::Appended) => writer.with_appended_snapshot().await?,
+            None => {}
+        }
+
+        Ok(writer)
+    }
+
+    /// Creates a new lazy writer for the given storage, which only creates the writer
+    /// for a link extending `previous` according to the options of the builder on the
+    /// first call to [`LazyWriter::write_unique()`].
+    #[inline]
+    pub fn create_lazy(self, previous: Option<LinkId>, storage: Storage) -> LazyWriter<T> {
+        LazyWriter::with_builder(previous, storage, self)
+    }
+}
+
+impl<T: Entry> Clone for WriterBuilder<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            _t: PhantomData,
+            ..*self
+        }
+    }
+}
@@ -1,8 +1,11 @@
-use super::Writer;
+use super::{LinkSummary, Writer, WriterBuilder};
 use crate::{Entry, Error, LinkId, Result, Storage};
 
 /// A lazy version of [`Writer`] which only creates new files when first trying to
 /// write new entries.
+///
+/// Note that [`Writer`] itself already defers creating the delta file until the first
+/// entry is written – this additionally defers creating the snapshot file.
 pub struct LazyWriter<T: Entry> {
     state: State<T>,
 }
@@ -12,7 +15,7 @@ enum State<T: Entry> {
     Uncreated {
         previous: Option<LinkId>,
         storage: Storage,
-        snapshot: bool,
+        builder: WriterBuilder<T>,
     },
 
     Created {
@@ -26,11 +29,23 @@ impl<T: Entry> LazyWriter<T> {
     ///
     /// Contrarily to [`Writer`], this only creates new file on the first call to
     /// [`write_unique()`].
+    #[inline]
     pub fn create(previous: Option<LinkId>, storage: Storage) -> Self {
+        Self::with_builder(previous, storage, Writer::builder())
+    }
+
+    /// Creates a new lazy writer for the given storage, lazily creating a link which
+    /// will extend `previous` with the given builder (see
+    /// [`WriterBuilder::create_lazy()`]).
+    pub(super) fn with_builder(
+        previous: Option<LinkId>,
+        storage: Storage,
+        builder: WriterBuilder<T>,
+    ) -> Self {
         let state = State::Uncreated {
             previous,
             storage,
-            snapshot: false,
+            builder,
         };
 
         Self { state }
@@ -41,8 +56,8 @@ impl<T: Entry> LazyWriter<T> {
     /// Fails if entries have already been added to the link's delta file.
     pub fn with_snapshot(&mut self) -> Result<()> {
         match &mut self.state {
-            State::Uncreated { snapshot, .. } => {
-                *snapshot = true;
+            State::Uncreated { builder, .. } => {
+                *builder = builder.clone().snapshot();
 
                 Ok(())
             }
@@ -64,6 +79,40 @@ impl<T: Entry> LazyWriter<T> {
         let writer = self.state.make_created().await?;
         writer.write_unique(entry).await
     }
+
+    /// Finishes writing the link's files, returning the ID assigned to the newly
+    /// created link (see [`Writer::finish()`]).
+    ///
+    /// Fails with [`Error::Empty`] if no entries were written, in which case no files
+    /// were created.
+    #[inline]
+    pub async fn finish(self) -> Result<LinkId> {
+        let summary = self.finish_with_summary().await?;
+        Ok(summary.id)
+    }
+
+    /// Finishes writing like [`finish()`][1], but returns information about the newly
+    /// created link rather than only its ID (see [`Writer::finish_with_summary()`]).
+    ///
+    /// [1]: Self::finish()
+    pub async fn finish_with_summary(self) -> Result<LinkSummary> {
+        match self.state {
+            State::Uncreated { .. } => Err(Error::Empty),
+            State::Created { writer } => writer.finish_with_summary().await,
+        }
+    }
+
+    /// Abandons the link, discarding what was written to its files (see
+    /// [`Writer::abort()`]).
+    ///
+    /// This does nothing if no entries were written, in which case no files were
+    /// created.
+    pub async fn abort(self) -> Result<()> {
+        match self.state {
+            State::Uncreated { .. } => Ok(()),
+            State::Created { writer } => writer.abort().await,
+        }
+    }
 }
 
 impl<T: Entry> State<T> {
@@ -72,14 +121,11 @@ impl<T: Entry> State<T> {
         if let Self::Uncreated {
             previous,
             storage,
-            snapshot,
+            builder,
         } = self
         {
             // TODO(MLB): don't clone `storage`
-            let mut writer = Writer::create(*previous, storage.clone()).await?;
-            if *snapshot {
-                writer.with_snapshot().await?;
-            }
+            let writer = builder.clone().create(*previous, storage.clone()).await?;
 
             *self = Self::Created { writer };
         }
@@ -91,3 +137,50 @@ impl<T: Entry> State<T> {
         Ok(writer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Reader,
+        tests::{U64, block_on, memory},
+        writer::tests::WARNINGS,
+    };
+
+    use super::*;
+
+    #[test]
+    fn finish_and_abort() -> Result<()> {
+        block_on(async {
+            let storage = memory();
+            let writer = LazyWriter::<U64>::create(None, storage.clone());
+            assert!(matches!(writer.finish().await, Err(Error::Empty)));
+            LazyWriter::<U64>::create(None, storage.clone())
+                .abort()
+                .await?;
+
+            let mut writer = LazyWriter::create(None, storage.clone());
+            writer.write_unique(U64(0)).await?;
+            let first = writer.finish().await?;
+
+            let mut writer = LazyWriter::create(Some(first), storage.clone());
+            writer.write_unique(U64(1)).await?;
+            writer.abort().await?;
+
+            let mut writer = LazyWriter::create(Some(first), storage.clone());
+            writer.write_unique(U64(2)).await?;
+            let summary = writer.finish_with_summary().await?;
+            assert_eq!((summary.count, summary.total), (1, 2));
+
+            // Finished or aborted lazy writers aren't reported as dropped.
+            assert_eq!(
+                WARNINGS.with_borrow_mut(std::mem::take),
+                Vec::<String>::new()
+            );
+
+            let reader = Reader::<U64>::open(summary.id, storage).await?;
+            assert!(reader.iter().map(|(_, entry)| entry.0).eq([0, 2]));
+
+            Ok(())
+        })
+    }
+}